@@ -2,8 +2,6 @@
 
 use std::ops::Add;
 
-use single::{APP_SIZE, PADDING};
-
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Hex {
 	q: isize,
@@ -26,10 +24,10 @@ impl Hex {
 		Hex { q, r, s }
 	}
 
-	pub fn get_coords(&self) -> [f32; 3] {
-		let x = 3.0 / 2.0 * (APP_SIZE + PADDING) / 2.0 * (-self.q - self.s) as f32;
-		let y = 3.0_f32.sqrt() * (APP_SIZE + PADDING) / 2.0
-			* ((-self.q - self.s) as f32 / 2.0 + self.s as f32);
+	/// `spacing` is the distance between the centers of neighboring hexagons.
+	pub fn get_coords(&self, spacing: f32) -> [f32; 3] {
+		let x = 3.0 / 2.0 * spacing / 2.0 * (-self.q - self.s) as f32;
+		let y = 3.0_f32.sqrt() * spacing / 2.0 * ((-self.q - self.s) as f32 / 2.0 + self.s as f32);
 		[x, y, 0.0]
 	}
 
@@ -1,12 +1,20 @@
 mod hex;
 
+use clap::Parser;
 use glam::Quat;
 use hex::Hex;
 use mint::{Quaternion, Vector3};
-use protostar::xdg::{DesktopFile, get_desktop_files};
+use protostar::{
+	config::{AppOrder, force_kiosk, skip_in_kiosk},
+	favorites::favorites,
+	icon_cache::save_icon_cache,
+	index::save_app_index,
+	registry::AppRegistry,
+	usage::sort_by_frecency,
+};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use single::{APP_SIZE, App, BTN_COLOR, BTN_SELECTED_COLOR, MODEL_SCALE};
+use single::{App, Reload, config, rgba};
 use stardust_xr_asteroids::{
 	ClientState, CustomElement, Element, Migrate, Reify, Transformable, client,
 	elements::{Button, Grabbable, Model, ModelPart, PointerMode, Spatial},
@@ -41,11 +49,24 @@ async fn main() {
 	client::run::<HexagonLauncher>(&[&project_local_resources!("../res")]).await
 }
 
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+	/// Only show the apps allowlisted in the `[kiosk]` config section and lock the launcher in place
+	#[clap(long)]
+	kiosk: bool,
+}
+
+/// Nothing gets saved in kiosk mode, so every session starts the same.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HexagonLauncher {
 	/// if the hexagon launcher is expanded
+	#[serde(skip_serializing_if = "skip_in_kiosk")]
 	open: bool,
+	#[serde(skip_serializing_if = "skip_in_kiosk")]
 	pos: Vector3<f32>,
+	#[serde(skip_serializing_if = "skip_in_kiosk")]
 	rot: Quaternion<f32>,
 	#[serde(skip)]
 	/// position in the vector is mapped to hex coordinates
@@ -70,77 +91,117 @@ impl ClientState for HexagonLauncher {
 	const APP_ID: &'static str = "org.protostar.hexagon_launcher";
 
 	fn initial_state_update(&mut self) {
-		// Load desktop files
-		self.apps = get_desktop_files()
-			.filter_map(|d| DesktopFile::parse(d).ok())
-			.filter(|d| !d.no_display)
-			.filter_map(|d| App::new(d).ok())
-			.collect();
+		if Args::parse().kiosk {
+			force_kiosk();
+		}
+		if config().kiosk_enabled() {
+			// every kiosk session starts the same, whatever was saved last time
+			*self = Self::default();
+		}
+		self.apps = load_apps();
+	}
+}
 
-		self.apps.par_iter().for_each(|app| {
-			app.load_icon();
-		});
+/// Discovers the apps and loads their icons, at startup and again when the config changes them.
+fn load_apps() -> Vec<App> {
+	// Load desktop files, only re-parsing the ones that changed since last startup
+	let mut apps = AppRegistry::discover().into_apps();
+	// the most used apps go in the innermost rings of the spiral
+	if config().hexagon.order == AppOrder::Frecency {
+		sort_by_frecency(&mut apps);
+	}
+	let apps = apps
+		.into_iter()
+		.map(App::from_application)
+		.collect::<Vec<_>>();
 
-		// Sort by name
-		self.apps
-			.sort_by_key(|app| app.app.name().unwrap_or_default().to_string());
+	apps.par_iter().for_each(|app| {
+		app.load_icon();
+	});
+	if let Err(e) = save_app_index() {
+		tracing::warn!("Failed to save app index: {e}");
+	}
+	if let Err(e) = save_icon_cache() {
+		tracing::warn!("Failed to save icon cache: {e}");
 	}
+	apps
 }
 impl Reify for HexagonLauncher {
 	#[tracing::instrument(skip_all)]
 	fn reify(&self) -> impl Element<Self> {
+		let config = config();
+		let appearance = config.appearance.clone();
+		let spacing = appearance.app_size + appearance.padding;
+		let kiosk = config.kiosk_enabled();
+
 		// Build UI based on current state
 		Grabbable::new(
 			Shape::Cylinder(CylinderShape {
-				radius: APP_SIZE / 2.0,
+				radius: appearance.app_size / 2.0,
 				length: 0.01,
 			}),
 			self.pos,
 			self.rot,
-			|state: &mut Self, pos, rot| {
-				state.pos = pos;
-				state.rot = rot;
+			move |state: &mut Self, pos, rot| {
+				if !kiosk {
+					state.pos = pos;
+					state.rot = rot;
+				}
 			},
 		)
 		.field_transform(Transform::from_rotation(Quat::from_rotation_x(FRAC_PI_2)))
 		.pointer_mode(PointerMode::Align)
-		.reparentable(true)
+		.reparentable(!kiosk)
 		.build()
+		.child(
+			// favorites and the rest of the config apply on the next reify,
+			// only changes to which apps are shown or their icons need the apps rebuilt
+			Reload::new(load_apps, |state: &mut HexagonLauncher, apps| {
+				App::replace_all(&mut state.apps, apps)
+			})
+			.build(),
+		)
 		.child(
 			Button::new(|state: &mut HexagonLauncher| {
 				state.open = !state.open;
 			})
 			.pos([0.0, 0.0, 0.005])
-			.size([APP_SIZE / 2.0; 2])
+			.size([appearance.app_size / 2.0; 2])
 			.build(),
 		)
 		.child(
 			Model::namespaced("protostar", "hexagon/hexagon")
 				.transform(Transform::from_rotation_scale(
 					Quat::from_rotation_x(PI / 2.0) * Quat::from_rotation_y(PI),
-					[MODEL_SCALE; 3],
+					[appearance.model_scale; 3],
 				))
 				.part(ModelPart::new("Hex").mat_param(
 					"color",
-					MaterialParameter::Color(if self.open {
-						BTN_SELECTED_COLOR
+					MaterialParameter::Color(rgba(if self.open {
+						appearance.button_selected_color
 					} else {
-						BTN_COLOR
-					}),
+						appearance.button_color
+					})),
 				))
 				.build(),
 		)
 		.children(
 			self.open
 				.then(|| {
-					self.apps.iter().enumerate().map(|(i, app)| {
-						Spatial::default()
-							.pos(Hex::spiral(i + 1).get_coords())
-							.build()
-							.child(app.reify_substate(move |state: &mut HexagonLauncher| {
-								state.apps.get_mut(i)
-							}))
-					})
+					// pinned apps take the innermost rings, shared with the other launchers
+					let ids = self.apps.iter().map(App::id).collect::<Vec<_>>();
+					favorites()
+						.order(&ids)
+						.into_iter()
+						.enumerate()
+						.map(move |(pos, i)| {
+							Spatial::default()
+								.pos(Hex::spiral(pos + 1).get_coords(spacing))
+								.build()
+								.child(self.apps[i].reify_substate(
+									move |state: &mut HexagonLauncher| state.apps.get_mut(i),
+								))
+						})
 				})
 				.into_iter()
 				.flatten(),
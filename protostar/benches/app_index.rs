@@ -0,0 +1,43 @@
+//! Compares startup with a cold (empty) and warm application index.
+//! Run with `cargo bench -p protostar --bench app_index`.
+
+use protostar::index::AppIndex;
+use std::time::{Duration, Instant};
+
+const ICON_SIZE: u16 = 64;
+const RUNS: u32 = 5;
+
+fn startup(index: &mut AppIndex) -> usize {
+	let desktop_files = index.discover();
+	for desktop_file in desktop_files.iter().filter(|d| !d.no_display) {
+		index.icon(desktop_file, ICON_SIZE);
+	}
+	desktop_files.len()
+}
+
+fn main() {
+	let dir = tempdir::TempDir::new("protostar_bench").unwrap();
+	let index_path = dir.path().join("app_index.toml");
+
+	let mut cold = Duration::ZERO;
+	let mut warm = Duration::ZERO;
+	let mut apps = 0;
+	for _ in 0..RUNS {
+		let _ = std::fs::remove_file(&index_path);
+		let start = Instant::now();
+		let mut index = AppIndex::load(index_path.clone());
+		apps = startup(&mut index);
+		index.save().unwrap();
+		cold += start.elapsed();
+
+		let start = Instant::now();
+		let mut index = AppIndex::load(index_path.clone());
+		startup(&mut index);
+		index.save().unwrap();
+		warm += start.elapsed();
+	}
+
+	println!("{apps} desktop files, {RUNS} runs each");
+	println!("cold: {:?}/run", cold / RUNS);
+	println!("warm: {:?}/run", warm / RUNS);
+}
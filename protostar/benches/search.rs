@@ -0,0 +1,50 @@
+//! Measures how long a search takes per keystroke over a thousand applications.
+//! Run with `cargo bench -p protostar --bench search`.
+
+use protostar::{registry::AppRegistry, search::SearchIndex};
+use std::time::{Duration, Instant};
+
+const APPS: usize = 1000;
+const RUNS: u32 = 20;
+const WORDS: [&str; 8] = [
+	"Studio", "Editor", "Viewer", "Manager", "Player", "Browser", "Terminal", "Settings",
+];
+
+fn main() {
+	let dir = tempdir::TempDir::new("protostar_bench").unwrap();
+	for i in 0..APPS {
+		let name = format!(
+			"{} {} {i}",
+			WORDS[i % WORDS.len()],
+			WORDS[(i / WORDS.len()) % WORDS.len()]
+		);
+		std::fs::write(
+			dir.path().join(format!("app{i}.desktop")),
+			format!(
+				"[Desktop Entry]\nName={name}\nGenericName=Application number {i}\nExec=app{i} %U\nKeywords=bench;test;app{i};\nCategories=Utility;Development;"
+			),
+		)
+		.unwrap();
+	}
+	let registry = AppRegistry::from_dir(dir.path());
+
+	let start = Instant::now();
+	let index = SearchIndex::new(registry.iter());
+	println!("indexed {} apps in {:?}", registry.len(), start.elapsed());
+
+	let query = "terminal set";
+	for len in 1..=query.len() {
+		let keystroke = &query[..len];
+		let mut total = Duration::ZERO;
+		let mut results = 0;
+		for _ in 0..RUNS {
+			let start = Instant::now();
+			results = index.search(keystroke).len();
+			total += start.elapsed();
+		}
+		println!(
+			"{keystroke:>12}: {:?}/search, {results} results",
+			total / RUNS
+		);
+	}
+}
@@ -1,4 +1,12 @@
-use crate::xdg::{DesktopFile, Icon, IconType};
+use crate::category::Category;
+use crate::config::config;
+use crate::extrude::get_glb_from_png;
+use crate::mime::{default_app_for, mime_for_path};
+use crate::recent::{RecentApplication, RecentFile, recent_files};
+use crate::usage;
+use crate::xdg::{
+	AppSource, DesktopAction, DesktopFile, Icon, IconType, file_uri_to_path, path_to_file_uri,
+};
 use nix::{libc::setsid, unistd::ForkResult};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -9,7 +17,9 @@ use stardust_xr_fusion::{
 };
 use std::{
 	os::unix::process::CommandExt,
+	path::Path,
 	process::{Command, Stdio, exit},
+	time::Duration,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,82 +35,328 @@ impl Application {
 		Ok(Application { desktop_file })
 	}
 
+	pub fn desktop_file(&self) -> &DesktopFile {
+		&self.desktop_file
+	}
+	pub fn id(&self) -> String {
+		self.desktop_file.id()
+	}
+	pub fn source(&self) -> AppSource {
+		self.desktop_file.source()
+	}
+
 	pub fn name(&self) -> Option<&str> {
 		self.desktop_file.name.as_deref()
 	}
+	pub fn generic_name(&self) -> Option<&str> {
+		self.desktop_file.generic_name.as_deref()
+	}
 	pub fn categories(&self) -> &[String] {
 		self.desktop_file.categories.as_slice()
 	}
+	pub fn primary_category(&self) -> Option<Category> {
+		Category::primary(self.categories())
+	}
+	pub fn keywords(&self) -> &[String] {
+		self.desktop_file.keywords.as_slice()
+	}
+	pub fn executable(&self) -> Option<&str> {
+		self.desktop_file.executable()
+	}
+	pub fn actions(&self) -> &[DesktopAction] {
+		self.desktop_file.actions.as_slice()
+	}
+
+	/// Files this app opened recently according to `recently-used.xbel`, most recent first.
+	pub fn recent_files(&self) -> Vec<RecentFile> {
+		let id = self.id();
+		let id = id.strip_suffix(".desktop").unwrap_or(&id);
+		let executable = self.executable();
+		// apps register themselves under all kinds of names, so match anything that identifies them
+		let is_this_app = |app: &RecentApplication| {
+			app.name == id
+				|| self
+					.name()
+					.is_some_and(|n| n.eq_ignore_ascii_case(&app.name))
+				|| (executable.is_some() && app.executable() == executable)
+		};
+
+		let mut files = recent_files()
+			.iter()
+			.filter_map(|file| {
+				let modified = file.applications.iter().find(|a| is_this_app(a))?.modified;
+				Some((modified, file.clone()))
+			})
+			.collect::<Vec<_>>();
+		files.sort_by(|(a, _), (b, _)| b.cmp(a));
+		files.into_iter().map(|(_, file)| file).collect()
+	}
 
+	/// With `prefer_3d`, a glTF icon wins over 2D ones, and with `appearance.extrude_icons` on
+	/// apps without one get their 2D icon extruded into a model, see [`crate::extrude`].
 	pub fn icon(&self, preferred_px_size: u16, prefer_3d: bool) -> Option<Icon> {
 		let raw_icons = self.desktop_file.get_icon(preferred_px_size);
-		let mut icon = raw_icons.iter().max_by_key(|i| i.size).cloned();
+		let icon = raw_icons.iter().max_by_key(|i| i.pixel_size()).cloned();
 		if prefer_3d {
-			icon = raw_icons
+			if let Some(model) = raw_icons
 				.into_iter()
 				.find(|i| i.icon_type == IconType::Gltf)
-				.or(icon);
+			{
+				return Some(model);
+			}
+			if config().appearance.extrude_icons {
+				let flat = icon.and_then(|i| i.cached_process(preferred_px_size).ok())?;
+				return get_glb_from_png(&flat.path)
+					.inspect_err(|e| {
+						tracing::warn!("Failed to extrude icon for {}: {e}", self.id())
+					})
+					.ok()
+					.and_then(|model| Icon::from_path(model, preferred_px_size))
+					.or(Some(flat));
+			}
 		}
 
 		icon.and_then(|i| i.cached_process(preferred_px_size).ok())
 	}
 
-	pub fn launch<T: SpatialRefAspect + Clone>(&self, launch_space: &T) -> NodeResult<()> {
-		let launch_space = launch_space.clone();
-		let client = launch_space.client().clone();
+	/// A generated monogram for apps without an icon, see [`crate::fallback_icon`].
+	pub fn fallback_icon(&self, preferred_px_size: u16) -> Option<Icon> {
+		let id = self.id();
+		let name = self.name().unwrap_or(&id);
+		crate::fallback_icon::fallback_icon(name, &id, preferred_px_size)
+			.inspect_err(|e| tracing::warn!("Failed to render fallback icon for {id}: {e}"))
+			.ok()
+	}
 
+	pub fn launch<T: SpatialRefAspect + Clone>(&self, launch_space: &T) -> NodeResult<()> {
 		let executable = self
 			.desktop_file
 			.command
 			.clone()
 			.ok_or(NodeError::DoesNotExist)?;
-		tokio::task::spawn(async move {
-			let Ok(startup_token) = client
-				.get_root()
-				.generate_state_token(ClientState::from_root(&launch_space).unwrap())
-				.await
-			else {
-				return;
-			};
-
-			let Ok(connection_env) = client.get_root().get_connection_environment().await else {
-				return;
-			};
-			for (k, v) in connection_env.into_iter() {
-				// this should be fine, probably?
-				unsafe {
-					std::env::set_var(k, v);
-				}
-			}
+		spawn(
+			launch_space,
+			strip_field_codes(&executable),
+			relaunch_in_kiosk(),
+			Some(self.id()),
+		)
+	}
 
-			// this should be fine, probably?
-			unsafe {
-				std::env::set_var("STARDUST_STARTUP_TOKEN", startup_token);
-			}
+	/// Launches the app with files passed through the `%f`, `%F`, `%u` or `%U` field codes of its `Exec` key.
+	/// Apps without those field codes get launched without the files, like the spec says.
+	pub fn launch_with_uris<T: SpatialRefAspect + Clone>(
+		&self,
+		uris: &[String],
+		launch_space: &T,
+	) -> NodeResult<()> {
+		let command = open_command(&self.desktop_file, uris).ok_or(NodeError::DoesNotExist)?;
+		spawn(launch_space, command, relaunch_in_kiosk(), Some(self.id()))
+	}
+
+	/// Opens the file this app used most recently again, see [`Application::recent_files`].
+	pub fn reopen_last_document<T: SpatialRefAspect + Clone>(
+		&self,
+		launch_space: &T,
+	) -> NodeResult<()> {
+		let last = self
+			.recent_files()
+			.into_iter()
+			.find(|f| f.path().is_none_or(|p| p.exists()))
+			.ok_or(NodeError::DoesNotExist)?;
+		self.launch_with_uris(&[last.uri], launch_space)
+	}
+
+	/// Launches one of the desktop file's additional actions by its ID.
+	pub fn launch_action<T: SpatialRefAspect + Clone>(
+		&self,
+		action_id: &str,
+		launch_space: &T,
+	) -> NodeResult<()> {
+		let executable = self
+			.actions()
+			.iter()
+			.find(|a| a.id == action_id)
+			.and_then(|a| a.command.clone())
+			.ok_or(NodeError::DoesNotExist)?;
+		spawn(
+			launch_space,
+			strip_field_codes(&executable),
+			relaunch_in_kiosk(),
+			Some(self.id()),
+		)
+	}
+}
 
-			// Strip/ignore field codes https://specifications.freedesktop.org/desktop-entry-spec/latest/ar01s07.html
-			let re = Regex::new(r"%[fFuUdDnNickvm]").unwrap();
-			let exec: std::borrow::Cow<'_, str> = re.replace_all(&executable, "");
-
-			unsafe {
-				if let ForkResult::Child = nix::unistd::fork().expect("fork died???? how?????") {
-					let _ = Command::new("sh")
-						.arg("-c")
-						.arg(exec.to_string())
-						.stdin(Stdio::null())
-						.stdout(Stdio::null())
-						.stderr(Stdio::null())
-						.pre_exec(|| {
-							_ = setsid();
-							Ok(())
-						})
-						.spawn()
-						.expect("Failed to start child process");
-					exit(0);
+/// Opens a file or directory with the default app for its type, like `xdg-open` but through
+/// [`Application::launch_with_uris`]. In kiosk mode the app has to be allowlisted.
+pub fn open_path<T: SpatialRefAspect + Clone>(path: &Path, launch_space: &T) -> NodeResult<()> {
+	let desktop_file = default_app_for(&mime_for_path(path)).ok_or(NodeError::DoesNotExist)?;
+	let config = config();
+	if config.kiosk_enabled() && !config.kiosk.apps.contains(&desktop_file.id()) {
+		tracing::warn!(
+			"Not opening {}, {} is not allowlisted for kiosk mode",
+			path.display(),
+			desktop_file.id()
+		);
+		return Err(NodeError::DoesNotExist);
+	}
+	// not `Application::create`, apps that only open files are often `NoDisplay`
+	Application { desktop_file }.launch_with_uris(&[path_to_file_uri(path)], launch_space)
+}
+
+/// Strip/ignore field codes https://specifications.freedesktop.org/desktop-entry-spec/latest/ar01s07.html
+fn strip_field_codes(exec: &str) -> String {
+	let re = Regex::new(r"%[fFuUdDnNickvm]").unwrap();
+	re.replace_all(exec, "").to_string()
+}
+
+/// The shell command that opens `uris` with an app, see [`Application::launch_with_uris`].
+/// Works for apps hidden with `NoDisplay` too, which are often only there to open files.
+pub fn open_command(desktop_file: &DesktopFile, uris: &[String]) -> Option<String> {
+	Some(expand_field_codes(desktop_file.command.as_deref()?, uris))
+}
+
+/// Fills in the file field codes, `%f` and `%F` only get local files.
+fn expand_field_codes(exec: &str, uris: &[String]) -> String {
+	let quote_all = |args: &mut dyn Iterator<Item = String>| {
+		args.map(|a| shell_quote(&a)).collect::<Vec<_>>().join(" ")
+	};
+	let paths = || {
+		uris.iter()
+			.filter_map(|u| file_uri_to_path(u))
+			.map(|p| p.to_string_lossy().to_string())
+	};
+	exec.split(' ')
+		.map(|arg| match arg {
+			"%f" => quote_all(&mut paths().take(1)),
+			"%F" => quote_all(&mut paths()),
+			"%u" => quote_all(&mut uris.iter().take(1).cloned()),
+			"%U" => quote_all(&mut uris.iter().cloned()),
+			// expanded paths may contain anything, so only strip the other args
+			arg => strip_field_codes(arg),
+		})
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Quotes an argument so `sh -c` passes it through as a single word.
+pub fn shell_quote(arg: &str) -> String {
+	format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Runs a shell command as a detached process connected to this Stardust session,
+/// with a startup token so its windows spawn at `launch_space`.
+pub fn launch_command<T: SpatialRefAspect + Clone>(
+	launch_space: &T,
+	command: String,
+) -> NodeResult<()> {
+	spawn(launch_space, command, false, None)
+}
+
+/// Like [`launch_command`], but only returns once the command has been started,
+/// for clients that exit right after launching something.
+pub async fn launch_command_and_wait<T: SpatialRefAspect + Clone>(
+	launch_space: &T,
+	command: String,
+) -> NodeResult<()> {
+	start(launch_space, command, false, None).await
+}
+
+fn relaunch_in_kiosk() -> bool {
+	let config = config();
+	config.kiosk_enabled() && config.kiosk.relaunch
+}
+
+fn spawn<T: SpatialRefAspect + Clone>(
+	launch_space: &T,
+	command: String,
+	relaunch: bool,
+	app_id: Option<String>,
+) -> NodeResult<()> {
+	let launch_space = launch_space.clone();
+	tokio::task::spawn(async move {
+		if let Err(e) = start(&launch_space, command, relaunch, app_id.as_deref()).await {
+			tracing::warn!("Failed to launch: {e:?}");
+		}
+	});
+
+	Ok(())
+}
+
+/// Forks off the command with the session's environment and a startup token for `launch_space`.
+/// With `relaunch`, the forked process stays around to start the command again whenever it exits.
+/// Launches of `app_id` only count for [`usage`] once the process has been forked off.
+async fn start<T: SpatialRefAspect + Clone>(
+	launch_space: &T,
+	command: String,
+	relaunch: bool,
+	app_id: Option<&str>,
+) -> NodeResult<()> {
+	let client = launch_space.client();
+	let startup_token = client
+		.get_root()
+		.generate_state_token(ClientState::from_root(launch_space).unwrap())
+		.await?;
+
+	let connection_env = client.get_root().get_connection_environment().await?;
+	for (k, v) in connection_env.into_iter() {
+		// this should be fine, probably?
+		unsafe {
+			std::env::set_var(k, v);
+		}
+	}
+
+	// this should be fine, probably?
+	unsafe {
+		std::env::set_var("STARDUST_STARTUP_TOKEN", startup_token);
+	}
+
+	unsafe {
+		if let ForkResult::Child = nix::unistd::fork().expect("fork died???? how?????") {
+			loop {
+				let mut child = Command::new("sh")
+					.arg("-c")
+					.arg(&command)
+					.stdin(Stdio::null())
+					.stdout(Stdio::null())
+					.stderr(Stdio::null())
+					.pre_exec(|| {
+						_ = setsid();
+						Ok(())
+					})
+					.spawn()
+					.expect("Failed to start child process");
+				if !relaunch {
+					break;
 				}
+				let _ = child.wait();
+				// don't spin if the app crashes right away
+				std::thread::sleep(Duration::from_secs(1));
 			}
-		});
+			exit(0);
+		}
+	}
 
-		Ok(())
+	if let Some(app_id) = app_id {
+		// usage tracking is best effort, the app is running already
+		let _ = usage::record_launch(app_id);
 	}
+	Ok(())
+}
+
+#[test]
+fn test_expand_field_codes() {
+	let uris = [
+		"file:///home/user/100%25%20done.txt".to_string(),
+		"https://example.com/".to_string(),
+	];
+	assert_eq!(
+		expand_field_codes("gedit %U", &uris),
+		"gedit 'file:///home/user/100%25%20done.txt' 'https://example.com/'"
+	);
+	assert_eq!(
+		expand_field_codes("gimp %i %f", &uris),
+		"gimp  '/home/user/100% done.txt'"
+	);
+	assert_eq!(expand_field_codes("app", &uris), "app");
 }
@@ -0,0 +1,351 @@
+use crate::icon_cache::get_icon_cache_dir;
+use crate::index::{FileStamp, write_atomic};
+use crate::normalize::normalize_icon;
+use crate::xdg::encode_png;
+use image::RgbaImage;
+use image::imageops;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Bump whenever the layout of the manifest or the pages changes.
+const ATLAS_VERSION: u32 = 1;
+/// Width and height of every page, most GPUs take textures up to 2048 pixels square. Icons are normalized
+/// with a transparent margin, so cells can sit right next to each other without bleeding into one another.
+pub const PAGE_SIZE: u32 = 2048;
+
+/// Where an icon ended up in an [`IconAtlas`], in pixels on its page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRect {
+	pub page: u32,
+	pub page_size: u32,
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+impl AtlasRect {
+	/// The UV offset and scale to map a whole `0..1` texture onto this rect, with `0, 0` in the top left.
+	pub fn uv_offset_scale(&self) -> ([f32; 2], [f32; 2]) {
+		let page = self.page_size as f32;
+		(
+			[self.x as f32 / page, self.y as f32 / page],
+			[self.width as f32 / page, self.height as f32 / page],
+		)
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AtlasEntry {
+	icon: PathBuf,
+	stamp: FileStamp,
+	page: u32,
+	slot: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AtlasFile {
+	version: u32,
+	page_size: u32,
+	cell_size: u32,
+	pages: u32,
+	entries: Vec<AtlasEntry>,
+}
+
+/// All icons of one size packed into a few large textures, so a launcher can bind one texture
+/// instead of loading hundreds, and index into it with [`AtlasRect::uv_offset_scale`].
+///
+/// Icons keep their cell across rebuilds, so adding or updating an icon only redraws its own page.
+/// The launchers don't use it yet, they still load each icon as its own texture.
+#[derive(Debug)]
+pub struct IconAtlas {
+	dir: PathBuf,
+	page_size: u32,
+	cell_size: u32,
+	pages: u32,
+	entries: HashMap<PathBuf, AtlasEntry>,
+}
+impl IconAtlas {
+	/// Loads the atlas for icons of `cell_size` pixels in `dir`, a missing, corrupt or outdated one starts empty.
+	pub fn load(dir: PathBuf, cell_size: u32) -> Self {
+		IconAtlas::load_with_page_size(dir, cell_size, PAGE_SIZE)
+	}
+
+	fn load_with_page_size(dir: PathBuf, cell_size: u32, page_size: u32) -> Self {
+		let cell_size = cell_size.clamp(1, page_size);
+		let file = fs::read_to_string(dir.join("atlas.toml"))
+			.ok()
+			.and_then(|text| toml::de::from_str::<AtlasFile>(&text).ok())
+			.filter(|file| {
+				file.version == ATLAS_VERSION
+					&& file.page_size == page_size
+					&& file.cell_size == cell_size
+			});
+		let (pages, entries) = file.map_or((0, HashMap::new()), |file| {
+			let entries = file
+				.entries
+				.into_iter()
+				.map(|entry| (entry.icon.clone(), entry))
+				.collect();
+			(file.pages, entries)
+		});
+		IconAtlas {
+			dir,
+			page_size,
+			cell_size,
+			pages,
+			entries,
+		}
+	}
+
+	fn cells_per_page(&self) -> u32 {
+		let per_row = self.page_size / self.cell_size;
+		per_row * per_row
+	}
+
+	/// The image of a page, only there once [`IconAtlas::build`] has written it.
+	pub fn page_path(&self, page: u32) -> PathBuf {
+		self.dir.join(format!("page-{page}.png"))
+	}
+	pub fn page_count(&self) -> u32 {
+		self.pages
+	}
+
+	pub fn rect(&self, icon: &Path) -> Option<AtlasRect> {
+		let entry = self.entries.get(icon)?;
+		let per_row = self.page_size / self.cell_size;
+		Some(AtlasRect {
+			page: entry.page,
+			page_size: self.page_size,
+			x: entry.slot % per_row * self.cell_size,
+			y: entry.slot / per_row * self.cell_size,
+			width: self.cell_size,
+			height: self.cell_size,
+		})
+	}
+
+	/// Packs exactly these PNG icons, dropping ones that aren't listed anymore.
+	/// Only pages with a new, changed or dropped icon get drawn again.
+	/// Icons that can't be read, like ones the icon cache evicted, are left out until the next build.
+	pub fn build(&mut self, icons: &[PathBuf]) -> io::Result<()> {
+		let wanted = icons.iter().collect::<HashSet<_>>();
+		// pages that need a cell cleared or drawn, with the icons to draw
+		let mut dirty: HashMap<u32, Vec<(u32, Option<PathBuf>)>> = HashMap::new();
+
+		let removed = self
+			.entries
+			.keys()
+			.filter(|icon| !wanted.contains(icon))
+			.cloned()
+			.collect::<Vec<_>>();
+		for icon in removed {
+			let entry = self.entries.remove(&icon).unwrap();
+			dirty
+				.entry(entry.page)
+				.or_default()
+				.push((entry.slot, None));
+		}
+
+		let mut used = self
+			.entries
+			.values()
+			.map(|entry| (entry.page, entry.slot))
+			.collect::<HashSet<_>>();
+		let mut next_free = (0, 0);
+		// in the given order rather than the set's, so the layout doesn't change from run to run
+		let mut seen = HashSet::new();
+		for icon in icons.iter().filter(|icon| seen.insert(*icon)) {
+			let Some(stamp) = FileStamp::read(icon) else {
+				continue;
+			};
+			let (page, slot) = match self.entries.get(icon) {
+				Some(entry) if entry.stamp == stamp => continue,
+				Some(entry) => (entry.page, entry.slot),
+				None => {
+					// fill gaps left by dropped icons before starting new pages
+					while used.contains(&next_free) {
+						next_free.1 += 1;
+						if next_free.1 == self.cells_per_page() {
+							next_free = (next_free.0 + 1, 0);
+						}
+					}
+					used.insert(next_free);
+					next_free
+				}
+			};
+			self.entries.insert(
+				icon.clone(),
+				AtlasEntry {
+					icon: icon.clone(),
+					stamp,
+					page,
+					slot,
+				},
+			);
+			dirty
+				.entry(page)
+				.or_default()
+				.push((slot, Some(icon.clone())));
+		}
+		let pages = self.pages_in_use();
+
+		fs::create_dir_all(&self.dir)?;
+		for (page, cells) in dirty {
+			if page >= pages {
+				continue;
+			}
+			for icon in self.draw_page(page, &cells)? {
+				self.entries.remove(&icon);
+			}
+		}
+		// counted again as unreadable icons may have been all there was on the last pages
+		let old_pages = self.pages.max(pages);
+		self.pages = self.pages_in_use();
+		for page in self.pages..old_pages {
+			let _ = fs::remove_file(self.page_path(page));
+		}
+		self.save()
+	}
+
+	fn pages_in_use(&self) -> u32 {
+		self.entries
+			.values()
+			.map(|entry| entry.page + 1)
+			.max()
+			.unwrap_or(0)
+	}
+
+	/// Returns the icons that couldn't be read, their cells are left empty.
+	fn draw_page(&self, page: u32, cells: &[(u32, Option<PathBuf>)]) -> io::Result<Vec<PathBuf>> {
+		let path = self.page_path(page);
+		let mut image = image::open(&path)
+			.map(|image| image.to_rgba8())
+			.ok()
+			.filter(|image| image.dimensions() == (self.page_size, self.page_size))
+			.unwrap_or_else(|| RgbaImage::new(self.page_size, self.page_size));
+		let per_row = self.page_size / self.cell_size;
+		let empty = RgbaImage::new(self.cell_size, self.cell_size);
+		let mut unreadable = Vec::new();
+		for (slot, icon) in cells {
+			let icon_image = match icon.as_ref().map(|icon| (icon, image::open(icon))) {
+				Some((_, Ok(icon_image))) => {
+					let icon_image = icon_image.to_rgba8();
+					if icon_image.dimensions() == (self.cell_size, self.cell_size) {
+						icon_image
+					} else {
+						normalize_icon(&icon_image, self.cell_size)
+					}
+				}
+				Some((icon, Err(e))) => {
+					tracing::warn!("Leaving {} out of the icon atlas: {e}", icon.display());
+					unreadable.push(icon.clone());
+					empty.clone()
+				}
+				None => empty.clone(),
+			};
+			let x = slot % per_row * self.cell_size;
+			let y = slot / per_row * self.cell_size;
+			imageops::replace(&mut image, &icon_image, x as i64, y as i64);
+		}
+		write_atomic(&path, encode_png(&image)?)?;
+		Ok(unreadable)
+	}
+
+	fn save(&self) -> io::Result<()> {
+		let file = AtlasFile {
+			version: ATLAS_VERSION,
+			page_size: self.page_size,
+			cell_size: self.cell_size,
+			pages: self.pages,
+			entries: self.entries.values().cloned().collect(),
+		};
+		let text =
+			toml::ser::to_string(&file).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+		write_atomic(&self.dir.join("atlas.toml"), text)
+	}
+}
+
+/// Builds the shared atlas for icons of `cell_size` pixels under `XDG_CACHE_HOME`,
+/// from icons that went through [`crate::xdg::Icon::cached_process`].
+pub fn build_icon_atlas(icons: &[PathBuf], cell_size: u32) -> io::Result<IconAtlas> {
+	let mut atlas = IconAtlas::load(
+		get_icon_cache_dir().join(format!("atlas-{cell_size}")),
+		cell_size,
+	);
+	atlas.build(icons)?;
+	Ok(atlas)
+}
+
+#[test]
+fn test_icon_atlas() {
+	use image::Rgba;
+
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let atlas_dir = dir.path().join("atlas");
+	let icon = |name: &str, color: [u8; 3]| {
+		let path = dir.path().join(format!("{name}.png"));
+		let [r, g, b] = color;
+		RgbaImage::from_pixel(16, 16, Rgba([r, g, b, 255]))
+			.save(&path)
+			.unwrap();
+		path
+	};
+	let red = icon("red", [255, 0, 0]);
+	let green = icon("green", [0, 255, 0]);
+
+	// small pages so the test doesn't spend its time encoding PNGs, 16 pixel cells fit 16 to a page
+	let mut atlas = IconAtlas::load_with_page_size(atlas_dir.clone(), 16, 64);
+	atlas.build(&[red.clone(), green.clone()]).unwrap();
+	assert_eq!(atlas.page_count(), 1);
+	let red_rect = atlas.rect(&red).unwrap();
+	let green_rect = atlas.rect(&green).unwrap();
+	assert_ne!(red_rect, green_rect);
+	let page = image::open(atlas.page_path(0)).unwrap().to_rgba8();
+	let pixel = |rect: AtlasRect| *page.get_pixel(rect.x + 8, rect.y + 8);
+	assert_eq!(pixel(red_rect), Rgba([255, 0, 0, 255]));
+	assert_eq!(pixel(green_rect), Rgba([0, 255, 0, 255]));
+
+	let (offset, scale) = red_rect.uv_offset_scale();
+	assert_eq!(scale, [0.25, 0.25]);
+	assert!(offset.iter().all(|o| (0.0..1.0).contains(o)));
+
+	// 17 icons need a second page, and reloading keeps the cells icons already had
+	let mut icons = (0..15)
+		.map(|i| icon(&i.to_string(), [i * 10, 0, 255]))
+		.collect::<Vec<_>>();
+	icons.extend([red.clone(), green.clone()]);
+	let mut atlas = IconAtlas::load_with_page_size(atlas_dir.clone(), 16, 64);
+	atlas.build(&icons).unwrap();
+	assert_eq!(atlas.page_count(), 2);
+	assert_eq!(atlas.rect(&red), Some(red_rect));
+	assert_eq!(atlas.rect(&green), Some(green_rect));
+
+	// an icon that can't be read is left out instead of failing the whole build
+	let broken = dir.path().join("broken.png");
+	fs::write(&broken, "not a png").unwrap();
+	icons.push(broken.clone());
+	atlas.build(&icons).unwrap();
+	assert_eq!(atlas.rect(&broken), None);
+	assert_eq!(atlas.rect(&red), Some(red_rect));
+
+	// a page that would only hold unreadable icons isn't counted or written
+	let single_dir = dir.path().join("single");
+	let mut single = IconAtlas::load_with_page_size(single_dir, 16, 16);
+	single.build(&[red.clone(), broken.clone()]).unwrap();
+	assert_eq!(single.page_count(), 1);
+	assert!(single.page_path(0).exists());
+	assert!(!single.page_path(1).exists());
+
+	// dropping icons frees their cells and the page they emptied
+	atlas.build(std::slice::from_ref(&green)).unwrap();
+	assert_eq!(atlas.page_count(), 1);
+	assert_eq!(atlas.rect(&red), None);
+	assert!(!atlas.page_path(1).exists());
+	let page = image::open(atlas.page_path(0)).unwrap().to_rgba8();
+	assert_eq!(
+		page.get_pixel(red_rect.x + 8, red_rect.y + 8),
+		&Rgba([0, 0, 0, 0])
+	);
+}
@@ -0,0 +1,119 @@
+use clap::Parser;
+use protostar::application::{launch_command_and_wait, open_command};
+use protostar::config::config;
+use protostar::mime::{default_app_for, mime_for_path};
+use protostar::usage;
+use protostar::xdg::{file_uri_to_path, path_to_file_uri};
+use stardust_xr_fusion::client::Client;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing_subscriber::EnvFilter;
+
+// the same exit codes as xdg-open, so scripts can't tell the difference
+const EXIT_FILE_NOT_FOUND: u8 = 2;
+const EXIT_NO_APP: u8 = 3;
+const EXIT_LAUNCH_FAILED: u8 = 4;
+
+/// Opens a file or URI with its default application, like xdg-open but placed in Stardust space
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+	/// A path or a URI like `https://stardustxr.org`
+	target: String,
+	/// Print the chosen app and the command it would run instead of launching it
+	#[clap(long)]
+	dry_run: bool,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+	tracing_subscriber::fmt()
+		.compact()
+		.with_env_filter(EnvFilter::from_env("LOG_LEVEL"))
+		.init();
+	let args = Args::parse();
+
+	let (uri, mime) = match resolve(&args.target) {
+		Ok(resolved) => resolved,
+		Err(e) => {
+			eprintln!("{e}");
+			return ExitCode::from(EXIT_FILE_NOT_FOUND);
+		}
+	};
+	let Some(app) = default_app_for(&mime) else {
+		eprintln!("No application to open {mime}");
+		return ExitCode::from(EXIT_NO_APP);
+	};
+	let id = app.id();
+	let config = config();
+	if config.kiosk_enabled() && !config.kiosk.apps.contains(&id) {
+		eprintln!("{id} opens {mime} but is not allowlisted for kiosk mode");
+		return ExitCode::from(EXIT_NO_APP);
+	}
+	let Some(command) = open_command(&app, &[uri]) else {
+		eprintln!("{id} has no command to run");
+		return ExitCode::from(EXIT_NO_APP);
+	};
+
+	if args.dry_run {
+		println!("type: {mime}");
+		println!("app: {id} ({})", app.path().display());
+		println!("argv: {:?}", ["sh", "-c", &command]);
+		return ExitCode::SUCCESS;
+	}
+	match launch(command).await {
+		Ok(()) => {
+			// usage tracking is best effort, the app is running already
+			let _ = usage::record_launch(&id);
+			ExitCode::SUCCESS
+		}
+		Err(e) => {
+			eprintln!("Failed to launch {id}: {e}");
+			ExitCode::from(EXIT_LAUNCH_FAILED)
+		}
+	}
+}
+
+/// The URI to hand to the app and the MIME type to pick the app by.
+/// Anything with a scheme other than `file` goes to its `x-scheme-handler/<scheme>`.
+fn resolve(target: &str) -> Result<(String, String), String> {
+	let scheme = target
+		.split_once(':')
+		.map(|(scheme, _)| scheme)
+		.filter(|s| is_scheme(s) && !Path::new(target).exists());
+	let path = match scheme {
+		Some(scheme) if !scheme.eq_ignore_ascii_case("file") => {
+			return Ok((
+				target.to_string(),
+				format!("x-scheme-handler/{}", scheme.to_lowercase()),
+			));
+		}
+		Some(_) => file_uri_to_path(target).ok_or_else(|| format!("Invalid file URI {target}"))?,
+		None => std::path::absolute(PathBuf::from(target)).map_err(|e| e.to_string())?,
+	};
+	if !path.exists() {
+		return Err(format!("No such file: {}", path.display()));
+	}
+	Ok((path_to_file_uri(&path), mime_for_path(&path)))
+}
+
+/// `ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )` from RFC 3986.
+fn is_scheme(scheme: &str) -> bool {
+	scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+		&& scheme
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+/// Connects to the server just long enough to get a startup token. The server places this client's root
+/// where the caller's own startup token says, so the app shows up next to whatever asked to open the file.
+async fn launch(command: String) -> Result<(), String> {
+	let client = Client::connect()
+		.await
+		.map_err(|e| format!("Couldn't connect to the Stardust server: {e:?}"))?;
+	let handle = client.handle();
+	let event_loop = client.async_event_loop();
+	let result = launch_command_and_wait(&handle.get_root(), command).await;
+	let _ = event_loop.stop().await;
+	result.map_err(|e| format!("{e:?}"))
+}
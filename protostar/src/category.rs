@@ -0,0 +1,300 @@
+use crate::application::Application;
+use crate::collate;
+use crate::xdg::{Icon, find_themed_icon};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The freedesktop main categories, plus one for Stardust's own `X-Stardust` apps.
+/// `Audio` and `Video` are folded into [`Category::AudioVideo`] as the spec requires them to be listed with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Category {
+	AudioVideo,
+	Development,
+	Education,
+	Game,
+	Graphics,
+	Network,
+	Office,
+	Science,
+	Settings,
+	System,
+	Utility,
+	Stardust,
+}
+impl Category {
+	pub const ALL: [Category; 12] = [
+		Category::AudioVideo,
+		Category::Development,
+		Category::Education,
+		Category::Game,
+		Category::Graphics,
+		Category::Network,
+		Category::Office,
+		Category::Science,
+		Category::Settings,
+		Category::System,
+		Category::Utility,
+		Category::Stardust,
+	];
+
+	/// Maps a main category from a desktop file's `Categories` key.
+	pub fn from_main(category: &str) -> Option<Self> {
+		Some(match category {
+			"AudioVideo" | "Audio" | "Video" => Category::AudioVideo,
+			"Development" => Category::Development,
+			"Education" => Category::Education,
+			"Game" => Category::Game,
+			"Graphics" => Category::Graphics,
+			"Network" => Category::Network,
+			"Office" => Category::Office,
+			"Science" => Category::Science,
+			"Settings" => Category::Settings,
+			"System" => Category::System,
+			"Utility" => Category::Utility,
+			_ if category.starts_with("X-Stardust") => Category::Stardust,
+			_ => return None,
+		})
+	}
+
+	/// Maps an additional category to the main category the spec relates it to.
+	/// Toolkit and desktop environment categories like `GTK` or `KDE` map to nothing.
+	pub fn from_additional(category: &str) -> Option<Self> {
+		Some(match category {
+			"Midi" | "Mixer" | "Sequencer" | "Tuner" | "TV" | "AudioVideoEditing" | "Player"
+			| "Recorder" | "DiscBurning" | "Music" => Category::AudioVideo,
+			"Building" | "Debugger" | "IDE" | "GUIDesigner" | "Profiling" | "RevisionControl"
+			| "Translation" | "WebDevelopment" => Category::Development,
+			"Art" | "Construction" | "Languages" | "Economy" | "Geography" | "History"
+			| "Humanities" | "Literature" | "Spirituality" | "Sports" => Category::Education,
+			"ActionGame" | "AdventureGame" | "ArcadeGame" | "BoardGame" | "BlocksGame"
+			| "CardGame" | "KidsGame" | "LogicGame" | "RolePlaying" | "Shooter" | "Simulation"
+			| "SportsGame" | "StrategyGame" | "Amusement" => Category::Game,
+			"2DGraphics" | "VectorGraphics" | "RasterGraphics" | "3DGraphics" | "Scanning"
+			| "OCR" | "Photography" | "Publishing" | "Viewer" => Category::Graphics,
+			"Dialup" | "InstantMessaging" | "Chat" | "IRCClient" | "Feed" | "FileTransfer"
+			| "HamRadio" | "News" | "P2P" | "RemoteAccess" | "Telephony" | "VideoConference"
+			| "WebBrowser" | "Email" => Category::Network,
+			"Calendar" | "ContactManagement" | "Database" | "Dictionary" | "Chart"
+			| "FlowChart" | "Finance" | "PDA" | "ProjectManagement" | "Presentation"
+			| "Spreadsheet" | "WordProcessor" => Category::Office,
+			"ArtificialIntelligence"
+			| "Astronomy"
+			| "Biology"
+			| "Chemistry"
+			| "ComputerScience"
+			| "DataVisualization"
+			| "Electricity"
+			| "Electronics"
+			| "Engineering"
+			| "Geology"
+			| "Geoscience"
+			| "ImageProcessing"
+			| "Math"
+			| "NumericalAnalysis"
+			| "MedicalSoftware"
+			| "Physics"
+			| "Robotics"
+			| "ParallelComputing" => Category::Science,
+			"DesktopSettings" | "HardwareSettings" | "Printing" | "PackageManager" => {
+				Category::Settings
+			}
+			"Emulator" | "FileManager" | "TerminalEmulator" | "Filesystem" | "Monitor"
+			| "Security" | "Shell" => Category::System,
+			"TextTools" | "TelephonyTools" | "Archiving" | "Compression" | "FileTools"
+			| "Accessibility" | "Calculator" | "Clock" | "TextEditor" | "Maps" => Category::Utility,
+			_ => return None,
+		})
+	}
+
+	/// The group an app belongs in: its first main category, or failing that the first additional one that maps to one.
+	pub fn primary<S: AsRef<str>>(categories: &[S]) -> Option<Self> {
+		categories
+			.iter()
+			.find_map(|c| Category::from_main(c.as_ref()))
+			.or_else(|| {
+				categories
+					.iter()
+					.find_map(|c| Category::from_additional(c.as_ref()))
+			})
+	}
+
+	pub fn display_name(self) -> &'static str {
+		match self {
+			Category::AudioVideo => "Multimedia",
+			Category::Development => "Development",
+			Category::Education => "Education",
+			Category::Game => "Games",
+			Category::Graphics => "Graphics",
+			Category::Network => "Internet",
+			Category::Office => "Office",
+			Category::Science => "Science",
+			Category::Settings => "Settings",
+			Category::System => "System",
+			Category::Utility => "Accessories",
+			Category::Stardust => "Stardust XR",
+		}
+	}
+
+	/// Icon name from the freedesktop icon naming spec.
+	pub fn icon_name(self) -> &'static str {
+		match self {
+			Category::AudioVideo => "applications-multimedia",
+			Category::Development => "applications-development",
+			Category::Education => "applications-education",
+			Category::Game => "applications-games",
+			Category::Graphics => "applications-graphics",
+			Category::Network => "applications-internet",
+			Category::Office => "applications-office",
+			Category::Science => "applications-science",
+			Category::Settings => "preferences-desktop",
+			Category::System => "applications-system",
+			Category::Utility => "applications-utilities",
+			Category::Stardust => "applications-other",
+		}
+	}
+
+	pub fn icon(self, preferred_px_size: u16) -> Option<Icon> {
+		find_themed_icon(self.icon_name(), preferred_px_size)
+	}
+}
+
+/// Which bucket an app was put in by [`Grouping::group`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GroupKey {
+	Category(Category),
+	Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppGroup<'a> {
+	pub key: GroupKey,
+	pub name: String,
+	pub icon_name: String,
+	pub apps: Vec<&'a Application>,
+}
+
+/// Settings for splitting apps into one group per primary category.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Grouping {
+	/// Display name of the bucket for apps without a known category.
+	pub other_name: String,
+	pub other_icon_name: String,
+	/// Categories with fewer apps than this get merged into the "Other" bucket.
+	pub min_group_size: usize,
+	/// Categories that always go into the "Other" bucket.
+	pub merged_into_other: Vec<Category>,
+}
+impl Default for Grouping {
+	fn default() -> Self {
+		Grouping {
+			other_name: "Other".to_string(),
+			other_icon_name: "applications-other".to_string(),
+			min_group_size: 1,
+			merged_into_other: Vec::new(),
+		}
+	}
+}
+impl Grouping {
+	/// Groups apps by primary category, sorted by group name with "Other" last.
+	/// Apps keep their order within each group.
+	pub fn group<'a>(&self, apps: impl IntoIterator<Item = &'a Application>) -> Vec<AppGroup<'a>> {
+		let mut categories: HashMap<Category, Vec<(usize, &'a Application)>> = HashMap::new();
+		let mut other = Vec::new();
+		for (i, app) in apps.into_iter().enumerate() {
+			match app.primary_category() {
+				Some(category) if !self.merged_into_other.contains(&category) => {
+					categories.entry(category).or_default().push((i, app))
+				}
+				_ => other.push((i, app)),
+			}
+		}
+
+		let mut groups = Vec::new();
+		for (category, apps) in categories {
+			if apps.len() < self.min_group_size {
+				other.extend(apps);
+				continue;
+			}
+			groups.push(AppGroup {
+				key: GroupKey::Category(category),
+				name: category.display_name().to_string(),
+				icon_name: category.icon_name().to_string(),
+				apps: apps.into_iter().map(|(_, app)| app).collect(),
+			});
+		}
+		groups.sort_by_cached_key(|group| collate::sort_key(&group.name));
+
+		if !other.is_empty() {
+			// merged groups were appended out of order
+			other.sort_by_key(|(i, _)| *i);
+			groups.push(AppGroup {
+				key: GroupKey::Other,
+				name: self.other_name.clone(),
+				icon_name: self.other_icon_name.clone(),
+				apps: other.into_iter().map(|(_, app)| app).collect(),
+			});
+		}
+		groups
+	}
+}
+
+#[test]
+fn test_primary_category() {
+	assert_eq!(
+		Category::primary(&["GTK", "Audio", "Player"]),
+		Some(Category::AudioVideo)
+	);
+	assert_eq!(
+		Category::primary(&["Qt", "WebBrowser"]),
+		Some(Category::Network)
+	);
+	assert_eq!(
+		Category::primary(&["X-Stardust-Launcher"]),
+		Some(Category::Stardust)
+	);
+	assert_eq!(Category::primary(&["GNOME", "Core"]), None);
+}
+
+#[test]
+fn test_grouping() {
+	use crate::xdg::DesktopFile;
+	use std::fs;
+
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let apps = [
+		("Tetris", "Game;BlocksGame;"),
+		("Chess", "Game;BoardGame;"),
+		("Terminal", "System;TerminalEmulator;"),
+		("Mystery", "GTK;"),
+	]
+	.iter()
+	.map(|(name, categories)| {
+		let file = dir.path().join(format!("{name}.desktop"));
+		fs::write(
+			&file,
+			format!("[Desktop Entry]\nName={name}\nCategories={categories}"),
+		)
+		.unwrap();
+		Application::create(DesktopFile::parse(file).unwrap()).unwrap()
+	})
+	.collect::<Vec<_>>();
+
+	let groups = Grouping::default().group(&apps);
+	let names = groups.iter().map(|g| g.name.as_str()).collect::<Vec<_>>();
+	assert_eq!(names, ["Games", "System", "Other"]);
+	assert_eq!(groups[0].apps.len(), 2);
+
+	let grouping = Grouping {
+		min_group_size: 2,
+		..Default::default()
+	};
+	let groups = grouping.group(&apps);
+	assert_eq!(groups.len(), 2);
+	let other = groups[1]
+		.apps
+		.iter()
+		.map(|a| a.name().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(other, ["Terminal", "Mystery"]);
+}
@@ -0,0 +1,126 @@
+use lazy_static::lazy_static;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+/// Lowercases and strips diacritics, so "Éditeur" and "editeur" compare equal.
+pub fn fold(text: &str) -> String {
+	text.chars().flat_map(fold_char).collect()
+}
+
+/// Folds a single char, which may turn into zero (combining marks) or several chars.
+pub fn fold_char(c: char) -> impl Iterator<Item = char> {
+	std::iter::once(c)
+		.nfd()
+		.filter(|c| !is_combining_mark(*c))
+		.flat_map(char::to_lowercase)
+}
+
+lazy_static! {
+	/// Whether the user asked for plain byte order through `LC_ALL`/`LC_COLLATE`, read once at startup.
+	static ref POSIX_COLLATION: bool = ["LC_ALL", "LC_COLLATE", "LANG"]
+		.iter()
+		.find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+		.is_some_and(|locale| locale == "C" || locale == "POSIX");
+}
+
+/// A name prepared for [`compare`] ahead of time, for sorting with `sort_by_cached_key`
+/// so each name only gets folded once instead of on every comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+	/// `None` in the `C`/`POSIX` locales, which sort by bytes.
+	folded: Option<String>,
+	original: String,
+}
+impl Ord for SortKey {
+	fn cmp(&self, other: &Self) -> Ordering {
+		match (&self.folded, &other.folded) {
+			(Some(a), Some(b)) => {
+				compare_natural(&mut a.chars().peekable(), &mut b.chars().peekable())
+			}
+			_ => Ordering::Equal,
+		}
+		.then_with(|| self.original.cmp(&other.original))
+	}
+}
+impl PartialOrd for SortKey {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+pub fn sort_key(text: &str) -> SortKey {
+	sort_key_with(text, *POSIX_COLLATION)
+}
+
+fn sort_key_with(text: &str, posix_collation: bool) -> SortKey {
+	SortKey {
+		folded: (!posix_collation)
+			.then(|| fold(text.trim_start_matches(|c: char| !c.is_alphanumeric()))),
+		original: text.to_string(),
+	}
+}
+
+/// Compares two names the way a person would sort them: ignoring case, diacritics and
+/// leading punctuation, with runs of digits compared by value ("App 2" before "App 10").
+///
+/// This is an approximation of locale collation without pulling in ICU,
+/// the `C`/`POSIX` locales still get plain byte order.
+pub fn compare(a: &str, b: &str) -> Ordering {
+	sort_key(a).cmp(&sort_key(b))
+}
+
+#[cfg(test)]
+fn compare_folded(a: &str, b: &str) -> Ordering {
+	sort_key_with(a, false).cmp(&sort_key_with(b, false))
+}
+
+fn compare_natural(a: &mut Peekable<Chars>, b: &mut Peekable<Chars>) -> Ordering {
+	loop {
+		match (a.peek().copied(), b.peek().copied()) {
+			(None, None) => return Ordering::Equal,
+			(None, Some(_)) => return Ordering::Less,
+			(Some(_), None) => return Ordering::Greater,
+			(Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+				let x = take_number(a);
+				let y = take_number(b);
+				let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+				if ordering != Ordering::Equal {
+					return ordering;
+				}
+			}
+			(Some(x), Some(y)) => {
+				if x != y {
+					return x.cmp(&y);
+				}
+				a.next();
+				b.next();
+			}
+		}
+	}
+}
+
+/// Takes a run of digits without its leading zeros.
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+	let mut number = String::new();
+	while let Some(c) = chars.next_if(char::is_ascii_digit) {
+		if !(number.is_empty() && c == '0') {
+			number.push(c);
+		}
+	}
+	number
+}
+
+#[test]
+fn test_collation() {
+	assert_eq!(fold("Éditeur"), "editeur");
+	assert_eq!(compare_folded("éclair", "Eclipse"), Ordering::Less);
+	assert_eq!(compare_folded("App 2", "App 10"), Ordering::Less);
+	assert_eq!(compare_folded("_Hidden", "Gimp"), Ordering::Greater);
+	assert_eq!(compare_folded("zed", "Ärger"), Ordering::Greater);
+	assert_eq!(
+		sort_key_with("éclair", true).cmp(&sort_key_with("Eclipse", true)),
+		Ordering::Greater
+	);
+}
@@ -0,0 +1,430 @@
+use crate::filter::Filter;
+use crate::watch::FileWatcher;
+use crate::xdg::{AppSource, DesktopFile, get_config_home};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+	static ref CONFIG: Mutex<Arc<Config>> = Mutex::new(watch_config(get_config_path()));
+}
+
+pub fn get_config_path() -> PathBuf {
+	get_config_home().join("protostar").join("config.toml")
+}
+
+/// Settings shared by all launchers, from `~/.config/protostar/config.toml`.
+/// Every key is optional, missing ones keep their default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	/// Overrides the icon theme picked up from the desktop environment.
+	pub icon_theme: Option<String>,
+	/// Pixel size icons get rendered at.
+	pub icon_size: u16,
+	/// Megabytes of rendered icons to keep in the cache, the least recently used go first.
+	pub icon_cache_mb: u64,
+	/// Desktop file IDs like `org.gnome.Calculator.desktop` to leave out of every launcher.
+	pub hidden_apps: Vec<String>,
+	pub filter: Filter,
+	pub sources: Sources,
+	pub appearance: Appearance,
+	pub hexagon: HexagonConfig,
+	pub sirius: SiriusConfig,
+	pub kiosk: KioskConfig,
+}
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			icon_theme: None,
+			icon_size: 64,
+			icon_cache_mb: 64,
+			hidden_apps: Vec::new(),
+			filter: Filter::default(),
+			sources: Sources::default(),
+			appearance: Appearance::default(),
+			hexagon: HexagonConfig::default(),
+			sirius: SiriusConfig::default(),
+			kiosk: KioskConfig::default(),
+		}
+	}
+}
+impl Config {
+	/// Reads the config, a missing file gives the defaults.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		match fs::read_to_string(path) {
+			Ok(text) => Self::parse(&text).map_err(|e| format!("{}: {e}", path.display())),
+			Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+			Err(e) => Err(format!("Failed to read {}: {e}", path.display())),
+		}
+	}
+
+	pub fn parse(text: &str) -> Result<Self, String> {
+		let config: Config = toml::de::from_str(text).map_err(|e| e.to_string())?;
+		config.validate()?;
+		Ok(config)
+	}
+
+	/// Catches values that parse fine but make no sense.
+	fn validate(&self) -> Result<(), String> {
+		if !(16..=1024).contains(&self.icon_size) {
+			return Err(format!(
+				"icon_size must be between 16 and 1024, got {}",
+				self.icon_size
+			));
+		}
+		if self.icon_cache_mb == 0 {
+			return Err("icon_cache_mb must be at least 1".to_string());
+		}
+		let appearance = &self.appearance;
+		positive("appearance.app_size", appearance.app_size)?;
+		positive("appearance.model_scale", appearance.model_scale)?;
+		positive(
+			"appearance.activation_distance",
+			appearance.activation_distance,
+		)?;
+		if !(appearance.padding.is_finite() && appearance.padding >= 0.0) {
+			return Err(format!(
+				"appearance.padding must be zero or more, got {}",
+				appearance.padding
+			));
+		}
+		positive("sirius.spacing", self.sirius.spacing)?;
+		if let Some(apps_directory) = &self.sirius.apps_directory
+			&& !apps_directory.is_dir()
+		{
+			return Err(format!(
+				"sirius.apps_directory {} is not a directory",
+				apps_directory.display()
+			));
+		}
+		Ok(())
+	}
+
+	/// Whether an app should be left out, because it's hidden, filtered out, from a disabled source
+	/// or not allowlisted in kiosk mode.
+	/// Every launcher goes through this, usually via [`crate::registry::AppRegistry`].
+	pub fn is_hidden(&self, desktop_file: &DesktopFile) -> bool {
+		!self.sources.allows(desktop_file.source())
+			|| self.hidden_apps.contains(&desktop_file.id())
+			|| !self.filter.allows(desktop_file)
+			|| (self.kiosk_enabled() && !self.kiosk.apps.contains(&desktop_file.id()))
+	}
+
+	/// Whether going from `self` to `new` changes which apps are shown, their order or their icons.
+	/// Everything else is read again on every reify, so it applies without rebuilding the apps.
+	pub fn changes_apps(&self, new: &Config) -> bool {
+		let (old_look, new_look) = (&self.appearance, &new.appearance);
+		self.icon_theme != new.icon_theme
+			|| self.icon_size != new.icon_size
+			|| self.hidden_apps != new.hidden_apps
+			|| self.filter != new.filter
+			|| self.sources != new.sources
+			|| self.kiosk.enabled != new.kiosk.enabled
+			|| self.kiosk.apps != new.kiosk.apps
+			|| self.hexagon.order != new.hexagon.order
+			|| self.sirius.apps_directory != new.sirius.apps_directory
+			// glTF icons are fitted to the app size when they're loaded
+			|| old_look.app_size != new_look.app_size
+			|| old_look.tint_from_icon != new_look.tint_from_icon
+			|| old_look.symbolic_icon_color != new_look.symbolic_icon_color
+			|| old_look.extrude_icons != new_look.extrude_icons
+	}
+
+	/// Locked down mode for demo booths, see [`KioskConfig`].
+	pub fn kiosk_enabled(&self) -> bool {
+		self.kiosk.enabled || KIOSK_FORCED.load(Ordering::Relaxed)
+	}
+}
+
+fn positive(key: &str, value: f32) -> Result<(), String> {
+	if value.is_finite() && value > 0.0 {
+		Ok(())
+	} else {
+		Err(format!("{key} must be a positive number, got {value}"))
+	}
+}
+
+/// Which kinds of installs to show apps from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sources {
+	pub user: bool,
+	pub system: bool,
+	pub flatpak: bool,
+	pub snap: bool,
+}
+impl Default for Sources {
+	fn default() -> Self {
+		Sources {
+			user: true,
+			system: true,
+			flatpak: true,
+			snap: true,
+		}
+	}
+}
+impl Sources {
+	pub fn allows(&self, source: AppSource) -> bool {
+		match source {
+			AppSource::User => self.user,
+			AppSource::System => self.system,
+			AppSource::Flatpak => self.flatpak,
+			AppSource::Snap => self.snap,
+		}
+	}
+}
+
+/// Sizes are in meters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Appearance {
+	pub app_size: f32,
+	/// Gap between apps laid out next to each other.
+	pub padding: f32,
+	/// Scale of the launcher button's model, glTF app icons are fitted to `app_size` instead.
+	pub model_scale: f32,
+	/// How far an app has to be pulled out before letting go launches it.
+	pub activation_distance: f32,
+	pub hex_color: Color,
+	/// Tints each app's hexagon with its icon's most prominent color instead of `hex_color`,
+	/// keeping the alpha of `hex_color`. Favorites keep `favorite_hex_color`.
+	pub tint_from_icon: bool,
+	/// What `-symbolic` icons get drawn in, they're black otherwise.
+	pub symbolic_icon_color: Color,
+	/// Gives apps without a 3D icon one, made by extruding their 2D icon.
+	pub extrude_icons: bool,
+	pub favorite_hex_color: Color,
+	pub button_color: Color,
+	pub button_selected_color: Color,
+}
+impl Default for Appearance {
+	fn default() -> Self {
+		Appearance {
+			app_size: 0.06,
+			padding: 0.005,
+			model_scale: 0.03,
+			activation_distance: 0.05,
+			hex_color: Color::linear(0.0395, 0.8848, 0.3148, 1.0),
+			tint_from_icon: false,
+			symbolic_icon_color: Color::linear(1.0, 1.0, 1.0, 1.0),
+			extrude_icons: false,
+			favorite_hex_color: Color::linear(0.9, 0.6, 0.05, 1.0),
+			button_color: Color::linear(1.0, 1.0, 0.0, 1.0),
+			button_selected_color: Color::linear(0.0, 1.0, 0.0, 1.0),
+		}
+	}
+}
+
+/// A color written as `#rrggbb` or `#rrggbbaa` in sRGB, like in CSS, and kept in linear space for rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+	pub r: f32,
+	pub g: f32,
+	pub b: f32,
+	pub a: f32,
+}
+impl Color {
+	pub const fn linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+		Color { r, g, b, a }
+	}
+}
+impl TryFrom<String> for Color {
+	type Error = String;
+
+	fn try_from(text: String) -> Result<Self, Self::Error> {
+		let invalid = || format!("invalid color {text:?}, expected #rrggbb or #rrggbbaa");
+		let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+		if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+			return Err(invalid());
+		}
+		let channel = |i: usize| {
+			u8::from_str_radix(&hex[i..i + 2], 16)
+				.map(|c| c as f32 / 255.0)
+				.map_err(|_| invalid())
+		};
+		let alpha = if hex.len() == 8 { channel(6)? } else { 1.0 };
+		Ok(Color::linear(
+			srgb_to_linear(channel(0)?),
+			srgb_to_linear(channel(2)?),
+			srgb_to_linear(channel(4)?),
+			alpha,
+		))
+	}
+}
+impl From<Color> for String {
+	fn from(color: Color) -> Self {
+		let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+		format!(
+			"#{:02x}{:02x}{:02x}{:02x}",
+			byte(linear_to_srgb(color.r)),
+			byte(linear_to_srgb(color.g)),
+			byte(linear_to_srgb(color.b)),
+			byte(color.a)
+		)
+	}
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+	if c <= 0.04045 {
+		c / 12.92
+	} else {
+		((c + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+	if c <= 0.0031308 {
+		c * 12.92
+	} else {
+		1.055 * c.powf(1.0 / 2.4) - 0.055
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppOrder {
+	/// Most used first, see [`crate::usage`].
+	#[default]
+	Frecency,
+	Name,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HexagonConfig {
+	pub order: AppOrder,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiriusConfig {
+	/// Used when no directory is passed on the command line.
+	pub apps_directory: Option<PathBuf>,
+	/// Distance between stars.
+	pub spacing: f32,
+}
+impl Default for SiriusConfig {
+	fn default() -> Self {
+		SiriusConfig {
+			apps_directory: None,
+			spacing: 0.1,
+		}
+	}
+}
+
+/// Only the allowlisted apps are shown, launchers can't be moved and their saved state is ignored.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KioskConfig {
+	pub enabled: bool,
+	/// Desktop file IDs of the only apps to show.
+	pub apps: Vec<String>,
+	/// Start apps launched from a kiosk again whenever they exit.
+	pub relaunch: bool,
+}
+
+static KIOSK_FORCED: AtomicBool = AtomicBool::new(false);
+/// Bumped when a reload [changes the apps](Config::changes_apps), see [`apps_changes`].
+static APPS_CHANGES: AtomicU64 = AtomicU64::new(0);
+
+/// For `#[serde(skip_serializing_if = "skip_in_kiosk")]` on launcher state,
+/// so kiosk sessions don't save where things were left.
+pub fn skip_in_kiosk<T>(_: &T) -> bool {
+	config().kiosk_enabled()
+}
+
+/// Turns on kiosk mode regardless of the config, for launchers started with `--kiosk`.
+pub fn force_kiosk() {
+	KIOSK_FORCED.store(true, Ordering::Relaxed);
+}
+
+/// Loads the config and reloads it in the background whenever the file changes.
+fn watch_config(path: PathBuf) -> Arc<Config> {
+	let config = Config::load(&path).unwrap_or_else(|e| {
+		tracing::error!("Invalid config, using the defaults: {e}");
+		Config::default()
+	});
+	// without inotify, changes only apply on restart
+	if let Ok(watcher) = FileWatcher::new(&path) {
+		let _ = watcher.spawn(move || match Config::load(&path) {
+			Ok(config) => {
+				let mut current = CONFIG.lock().unwrap();
+				if current.changes_apps(&config) {
+					APPS_CHANGES.fetch_add(1, Ordering::Release);
+				}
+				*current = Arc::new(config);
+			}
+			Err(e) => tracing::error!("Invalid config, keeping the previous one: {e}"),
+		});
+	}
+	Arc::new(config)
+}
+
+/// The current config, kept up to date with the file, so every setting applies without a restart.
+/// Cheap enough to call every time the UI is rebuilt.
+pub fn config() -> Arc<Config> {
+	CONFIG.lock().unwrap().clone()
+}
+
+/// How many times the config got reloaded with [changes to the apps](Config::changes_apps).
+/// Launchers compare it from frame to frame and rebuild their app list when it moved.
+pub fn apps_changes() -> u64 {
+	APPS_CHANGES.load(Ordering::Acquire)
+}
+
+#[test]
+fn test_parse_config() {
+	let config = Config::parse(
+		r##"
+icon_theme = "Papirus"
+hidden_apps = ["htop.desktop"]
+
+[sources]
+snap = false
+
+[appearance]
+app_size = 0.08
+hex_color = "#ff000080"
+
+[hexagon]
+order = "name"
+"##,
+	)
+	.unwrap();
+	assert_eq!(config.icon_theme.as_deref(), Some("Papirus"));
+	assert_eq!(config.icon_size, 64);
+	assert!(!config.sources.allows(AppSource::Snap));
+	assert!(config.sources.allows(AppSource::Flatpak));
+	assert_eq!(config.appearance.app_size, 0.08);
+	assert_eq!(config.appearance.padding, 0.005);
+	assert_eq!(config.appearance.hex_color.r, 1.0);
+	assert_eq!(config.hexagon.order, AppOrder::Name);
+
+	let color = Color::try_from("#80ff00".to_string()).unwrap();
+	assert_eq!(String::from(color), "#80ff00ff");
+
+	let error = Config::parse("[appearance]\napp_size = -1.0").unwrap_err();
+	assert!(error.contains("appearance.app_size"), "{error}");
+	let error = Config::parse("[appearance]\nhex_color = \"green\"").unwrap_err();
+	assert!(error.contains("invalid color \"green\""), "{error}");
+	let error = Config::parse("[sirius]\napps_directory = \"/nonexistent/apps\"").unwrap_err();
+	assert!(error.contains("sirius.apps_directory"), "{error}");
+	let error = Config::parse("icon_sise = 32").unwrap_err();
+	assert!(error.contains("icon_sise"), "{error}");
+	assert_eq!(Config::parse("").unwrap(), Config::default());
+
+	let config = Config::parse("[kiosk]\nenabled = true\napps = [\"game.desktop\"]").unwrap();
+	assert!(config.kiosk_enabled());
+	assert_eq!(config.kiosk.apps, ["game.desktop"]);
+
+	let default = Config::default();
+	assert!(!default.changes_apps(&Config::parse("[sirius]\nspacing = 0.2").unwrap()));
+	assert!(default.changes_apps(&Config::parse("hidden_apps = [\"htop.desktop\"]").unwrap()));
+	assert!(default.changes_apps(&Config::parse("icon_size = 32").unwrap()));
+}
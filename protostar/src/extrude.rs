@@ -0,0 +1,560 @@
+use crate::palette::dominant_color;
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Cells across the icon the outline gets traced on, more follows the shape closer but makes more triangles.
+const GRID: u32 = 32;
+/// The model is 1 unit across, this thick, facing +Z.
+const DEPTH: f32 = 0.12;
+/// How far the beveled edge reaches in from the outline, and back from the faces. Under half a cell,
+/// so the bevels on both sides of a line one cell wide don't run into each other.
+const BEVEL: f32 = 0.01;
+
+#[derive(Default)]
+struct Mesh {
+	positions: Vec<[f32; 3]>,
+	normals: Vec<[f32; 3]>,
+	uvs: Vec<[f32; 2]>,
+	indices: Vec<u32>,
+}
+impl Mesh {
+	/// A flat shaded triangle or quad, flipped if needed so it faces `facing`.
+	fn face(&mut self, vertices: &[[f32; 3]], uvs: &[[f32; 2]], facing: [f32; 3]) {
+		let [a, b, c] = [vertices[0], vertices[1], vertices[2]];
+		let normal = normalize(cross(sub(b, a), sub(c, a)));
+		let flip = dot(normal, facing) < 0.0;
+		let normal = if flip { normal.map(|n| -n) } else { normal };
+
+		let start = self.positions.len() as u32;
+		self.positions.extend_from_slice(vertices);
+		self.normals
+			.extend(std::iter::repeat_n(normal, vertices.len()));
+		self.uvs.extend_from_slice(uvs);
+		for i in 1..vertices.len() as u32 - 1 {
+			if flip {
+				self.indices.extend([start, start + i + 1, start + i]);
+			} else {
+				self.indices.extend([start, start + i, start + i + 1]);
+			}
+		}
+	}
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+	[a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+	[
+		a[1] * b[2] - a[2] * b[1],
+		a[2] * b[0] - a[0] * b[2],
+		a[0] * b[1] - a[1] * b[0],
+	]
+}
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+	a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+	let length = dot(v, v).sqrt();
+	if length == 0.0 {
+		v
+	} else {
+		v.map(|c| c / length)
+	}
+}
+
+/// Which cells of the [`GRID`] are mostly opaque.
+struct Mask {
+	cells: Vec<bool>,
+}
+impl Mask {
+	fn new(image: &RgbaImage) -> Self {
+		let (width, height) = image.dimensions();
+		let mut cells = Vec::with_capacity((GRID * GRID) as usize);
+		for j in 0..GRID {
+			for i in 0..GRID {
+				let (x0, x1) = (
+					i * width / GRID,
+					((i + 1) * width / GRID).max(i * width / GRID + 1),
+				);
+				let (y0, y1) = (
+					j * height / GRID,
+					((j + 1) * height / GRID).max(j * height / GRID + 1),
+				);
+				let mut alpha = 0;
+				let mut count = 0;
+				for y in y0..y1.min(height) {
+					for x in x0..x1.min(width) {
+						alpha += image.get_pixel(x, y).0[3] as u32;
+						count += 1;
+					}
+				}
+				cells.push(count > 0 && alpha / count >= 128);
+			}
+		}
+		Mask { cells }
+	}
+
+	fn get(&self, i: i32, j: i32) -> bool {
+		(0..GRID as i32).contains(&i)
+			&& (0..GRID as i32).contains(&j)
+			&& self.cells[(j as u32 * GRID + i as u32) as usize]
+	}
+}
+
+/// Where a point on the grid ends up in the model, with `u` and `v` counted in cells from the top left.
+fn grid_point(u: f32, v: f32, z: f32) -> [f32; 3] {
+	[u / GRID as f32 - 0.5, 0.5 - v / GRID as f32, z]
+}
+fn grid_uv(u: f32, v: f32) -> [f32; 2] {
+	[u / GRID as f32, v / GRID as f32]
+}
+
+/// Turns an icon into a glTF binary: its opaque outline extruded into a slab with beveled edges,
+/// the icon on the front and back and its edge colors along the sides.
+pub fn extrude_icon(image: &RgbaImage) -> Result<Vec<u8>, String> {
+	let mask = Mask::new(image);
+	if !mask.cells.contains(&true) {
+		return Err("the icon is fully transparent".to_string());
+	}
+	write_glb(&extrude_mesh(&mask), &texture(image)?)
+}
+
+/// The sides stand on the outline and the faces slope down to them, so the bevel stays inside the
+/// outline and no two parts of the surface ever cover the same spot.
+fn extrude_mesh(mask: &Mask) -> Mesh {
+	let front = DEPTH / 2.0;
+	let mut mesh = Mesh::default();
+	// cells with all eight neighbours filled are flat, runs of them in a row are merged into one quad
+	let interior = |i: i32, j: i32| (-1..=1).all(|dj| (-1..=1).all(|di| mask.get(i + di, j + dj)));
+	for j in 0..GRID as i32 {
+		let mut i = 0;
+		while i < GRID as i32 {
+			if !interior(i, j) {
+				if mask.get(i, j) {
+					outline_cell(&mut mesh, mask, i, j);
+				}
+				i += 1;
+				continue;
+			}
+			let start = i;
+			while interior(i, j) {
+				i += 1;
+			}
+			let corners =
+				[(start, j + 1), (i, j + 1), (i, j), (start, j)].map(|(u, v)| (u as f32, v as f32));
+			let uvs = corners.map(|(u, v)| grid_uv(u, v));
+			for z in [front, -front] {
+				mesh.face(
+					&corners.map(|(u, v)| grid_point(u, v, z)),
+					&uvs,
+					[0.0, 0.0, z],
+				);
+			}
+		}
+	}
+	mesh
+}
+
+/// A filled cell next to an empty one. It's split into a border of [`BEVEL`] wide strips around a flat middle,
+/// the points on the outline get lowered by the bevel, and open sides get a straight side between front and back.
+fn outline_cell(mesh: &mut Mesh, mask: &Mask, i: i32, j: i32) {
+	let front = DEPTH / 2.0;
+	let inset = BEVEL * GRID as f32;
+	let steps = [0.0, inset, 1.0 - inset, 1.0];
+	// on an open side, or a corner of the cell with any of the cells around it empty
+	let on_outline = |a: usize, c: usize| {
+		let towards = |step: usize| match step {
+			0 => -1,
+			3 => 1,
+			_ => 0,
+		};
+		match (towards(a), towards(c)) {
+			(0, 0) => false,
+			(di, 0) => !mask.get(i + di, j),
+			(0, dj) => !mask.get(i, j + dj),
+			(di, dj) => !(mask.get(i + di, j) && mask.get(i, j + dj) && mask.get(i + di, j + dj)),
+		}
+	};
+
+	for facing in [1.0, -1.0] {
+		let vertex = |(a, c): (usize, usize)| {
+			let (u, v) = (i as f32 + steps[a], j as f32 + steps[c]);
+			let z = if on_outline(a, c) {
+				front - BEVEL
+			} else {
+				front
+			};
+			(grid_point(u, v, z * facing), grid_uv(u, v))
+		};
+		for c in 0..3 {
+			for a in 0..3 {
+				let corners = [(a, c), (a + 1, c), (a + 1, c + 1), (a, c + 1)].map(vertex);
+				let positions = corners.map(|(p, _)| p);
+				let uvs = corners.map(|(_, uv)| uv);
+				if a == 1 || c == 1 {
+					mesh.face(&positions, &uvs, [0.0, 0.0, facing]);
+					continue;
+				}
+				// corners fold along the diagonal from the cell's corner, mitring the bevels meeting there
+				let k = match (a, c) {
+					(0, 0) => 0,
+					(2, 0) => 1,
+					(2, 2) => 2,
+					_ => 3,
+				};
+				for triangle in [[k, k + 1, k + 2], [k, k + 2, k + 3]] {
+					let triangle = triangle.map(|n| n % 4);
+					mesh.face(
+						&triangle.map(|n| positions[n]),
+						&triangle.map(|n| uvs[n]),
+						[0.0, 0.0, facing],
+					);
+				}
+			}
+		}
+	}
+
+	let uvs = [grid_uv(i as f32 + 0.5, j as f32 + 0.5); 4];
+	let sides = [
+		((-1, 0), (0, 0), (0, 1)),
+		((1, 0), (1, 0), (1, 1)),
+		((0, -1), (0, 0), (1, 0)),
+		((0, 1), (0, 1), (1, 1)),
+	];
+	for ((di, dj), a, b) in sides {
+		if mask.get(i + di, j + dj) {
+			continue;
+		}
+		let at = |(u, v): (i32, i32), z: f32| grid_point((i + u) as f32, (j + v) as f32, z);
+		let z = front - BEVEL;
+		mesh.face(
+			&[at(a, z), at(b, z), at(b, -z), at(a, -z)],
+			&uvs,
+			[di as f32, -dj as f32, 0.0],
+		);
+	}
+}
+
+/// The icon with its transparent pixels filled in, since the faces are cut along cells rather than pixels.
+fn texture(image: &RgbaImage) -> Result<Vec<u8>, String> {
+	let [r, g, b] = dominant_color(image).unwrap_or([128, 128, 128]);
+	let background = [r, g, b].map(|c| c as f32);
+	let filled = RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+		let [r, g, b, a] = image.get_pixel(x, y).0;
+		let alpha = a as f32 / 255.0;
+		let [r, g, b] = [r, g, b].map(|c| c as f32).map(|c| c * alpha);
+		Rgba([
+			(r + background[0] * (1.0 - alpha)).round() as u8,
+			(g + background[1] * (1.0 - alpha)).round() as u8,
+			(b + background[2] * (1.0 - alpha)).round() as u8,
+			255,
+		])
+	});
+	let mut png = io::Cursor::new(Vec::new());
+	filled
+		.write_to(&mut png, image::ImageFormat::Png)
+		.map_err(|e| e.to_string())?;
+	Ok(png.into_inner())
+}
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+fn write_glb(mesh: &Mesh, png: &[u8]) -> Result<Vec<u8>, String> {
+	let mut bin = Vec::new();
+	let mut views = Vec::new();
+	let mut push_view = |bin: &mut Vec<u8>, data: Vec<u8>, target: Option<u32>| {
+		let offset = bin.len();
+		let length = data.len();
+		bin.extend(data);
+		// every view starts 4 byte aligned
+		bin.resize(bin.len().next_multiple_of(4), 0);
+		let mut view = json!({"buffer": 0, "byteOffset": offset, "byteLength": length});
+		if let Some(target) = target {
+			view["target"] = json!(target);
+		}
+		views.push(view);
+	};
+	let floats = |values: &mut dyn Iterator<Item = f32>| {
+		values.flat_map(f32::to_le_bytes).collect::<Vec<_>>()
+	};
+
+	push_view(
+		&mut bin,
+		floats(&mut mesh.positions.iter().flatten().copied()),
+		Some(ARRAY_BUFFER),
+	);
+	push_view(
+		&mut bin,
+		floats(&mut mesh.normals.iter().flatten().copied()),
+		Some(ARRAY_BUFFER),
+	);
+	push_view(
+		&mut bin,
+		floats(&mut mesh.uvs.iter().flatten().copied()),
+		Some(ARRAY_BUFFER),
+	);
+	push_view(
+		&mut bin,
+		mesh.indices.iter().flat_map(|i| i.to_le_bytes()).collect(),
+		Some(ELEMENT_ARRAY_BUFFER),
+	);
+	push_view(&mut bin, png.to_vec(), None);
+
+	let (min, max) = mesh
+		.positions
+		.iter()
+		.fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), p| {
+			(
+				[0, 1, 2].map(|i| min[i].min(p[i])),
+				[0, 1, 2].map(|i| max[i].max(p[i])),
+			)
+		});
+	let vertices = mesh.positions.len();
+	let document = json!({
+		"asset": {"version": "2.0", "generator": "protostar"},
+		"scene": 0,
+		"scenes": [{"nodes": [0]}],
+		"nodes": [{"mesh": 0, "name": "Icon"}],
+		"meshes": [{"primitives": [{
+			"attributes": {"POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2},
+			"indices": 3,
+			"material": 0,
+		}]}],
+		"materials": [{
+			"name": "Icon",
+			"pbrMetallicRoughness": {
+				"baseColorTexture": {"index": 0},
+				"metallicFactor": 0.0,
+				"roughnessFactor": 0.7,
+			},
+		}],
+		"textures": [{"source": 0, "sampler": 0}],
+		// linear filtering with mipmaps, clamped so the edges don't pick up the other side
+		"samplers": [{"magFilter": 9729, "minFilter": 9987, "wrapS": 33071, "wrapT": 33071}],
+		"images": [{"bufferView": 4, "mimeType": "image/png"}],
+		"accessors": [
+			{"bufferView": 0, "componentType": FLOAT, "count": vertices, "type": "VEC3", "min": min, "max": max},
+			{"bufferView": 1, "componentType": FLOAT, "count": vertices, "type": "VEC3"},
+			{"bufferView": 2, "componentType": FLOAT, "count": vertices, "type": "VEC2"},
+			{"bufferView": 3, "componentType": UNSIGNED_INT, "count": mesh.indices.len(), "type": "SCALAR"},
+		],
+		"bufferViews": views,
+		"buffers": [{"byteLength": bin.len()}],
+	});
+
+	let mut json = serde_json::to_vec(&document).map_err(|e| e.to_string())?;
+	// the JSON chunk is padded with spaces, the binary one with zeros
+	json.resize(json.len().next_multiple_of(4), b' ');
+	let length = 12 + 8 + json.len() + 8 + bin.len();
+	let length =
+		u32::try_from(length).map_err(|_| "the model is too large for a glb".to_string())?;
+
+	let mut glb = Vec::with_capacity(length as usize);
+	for word in [GLB_MAGIC, 2, length, json.len() as u32, CHUNK_JSON] {
+		glb.extend(word.to_le_bytes());
+	}
+	glb.extend(json);
+	for word in [bin.len() as u32, CHUNK_BIN] {
+		glb.extend(word.to_le_bytes());
+	}
+	glb.extend(bin);
+	Ok(glb)
+}
+
+/// Extrudes a PNG icon into a glb in the shared icon cache, see [`extrude_icon`].
+pub fn get_glb_from_png(png_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+	let png_path = std::fs::canonicalize(png_path)?;
+	crate::icon_cache::get_or_derive(&png_path, "extruded.glb", |png| {
+		let image = image::load_from_memory(png)
+			.map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?
+			.to_rgba8();
+		extrude_icon(&image).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+	})
+}
+
+#[test]
+fn test_extrude_icon() {
+	assert!(extrude_icon(&RgbaImage::new(64, 64)).is_err());
+
+	// a square with a notch taken out of one side, for concave corners too
+	let mut image = RgbaImage::new(64, 64);
+	for y in 16..48 {
+		for x in 16..48 {
+			if !(x >= 40 && (28..36).contains(&y)) {
+				image.put_pixel(x, y, Rgba([200, 40, 40, 255]));
+			}
+		}
+	}
+	let glb = extrude_icon(&image).unwrap();
+
+	let word = |offset: usize| u32::from_le_bytes(glb[offset..offset + 4].try_into().unwrap());
+	assert_eq!(word(0), GLB_MAGIC);
+	assert_eq!(word(4), 2);
+	assert_eq!(word(8) as usize, glb.len());
+	let json_length = word(12) as usize;
+	assert_eq!(word(16), CHUNK_JSON);
+	assert_eq!(json_length % 4, 0);
+	let bin_start = 20 + json_length;
+	let bin_length = word(bin_start) as usize;
+	assert_eq!(word(bin_start + 4), CHUNK_BIN);
+	assert_eq!(bin_start + 8 + bin_length, glb.len());
+	let bin = &glb[bin_start + 8..];
+
+	let document: serde_json::Value = serde_json::from_slice(&glb[20..bin_start]).unwrap();
+	assert_eq!(document["asset"]["version"], "2.0");
+	assert_eq!(document["buffers"][0]["byteLength"], bin_length);
+	let view = |index: &serde_json::Value| {
+		let view = &document["bufferViews"][index.as_u64().unwrap() as usize];
+		let offset = view["byteOffset"].as_u64().unwrap() as usize;
+		let length = view["byteLength"].as_u64().unwrap() as usize;
+		assert_eq!(offset % 4, 0);
+		assert!(offset + length <= bin_length);
+		&bin[offset..offset + length]
+	};
+	let floats = |bytes: &[u8]| {
+		bytes
+			.chunks(4)
+			.map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+			.collect::<Vec<_>>()
+	};
+
+	let accessors = document["accessors"].as_array().unwrap();
+	let vertices = accessors[0]["count"].as_u64().unwrap() as usize;
+	for (accessor, components) in accessors[..3].iter().zip([3, 3, 2]) {
+		assert_eq!(accessor["count"], vertices);
+		assert_eq!(
+			view(&accessor["bufferView"]).len(),
+			vertices * components * 4
+		);
+	}
+	let positions = floats(view(&accessors[0]["bufferView"]));
+	let min = accessors[0]["min"].as_array().unwrap();
+	let max = accessors[0]["max"].as_array().unwrap();
+	for position in positions.chunks(3) {
+		for axis in 0..3 {
+			assert!(position[axis] >= min[axis].as_f64().unwrap() as f32);
+			assert!(position[axis] <= max[axis].as_f64().unwrap() as f32);
+		}
+	}
+	// the square covers the middle half, the bevel stays inside it
+	assert_eq!(max[2].as_f64().unwrap() as f32, DEPTH / 2.0);
+	assert_eq!(max[0].as_f64().unwrap() as f32, 0.25);
+
+	let normals = floats(view(&accessors[1]["bufferView"]));
+	for normal in normals.chunks(3) {
+		let length = normal.iter().map(|n| n * n).sum::<f32>().sqrt();
+		assert!((length - 1.0).abs() < 1e-4);
+	}
+	for uv in floats(view(&accessors[2]["bufferView"])) {
+		assert!((0.0..=1.0).contains(&uv));
+	}
+	let indices = view(&accessors[3]["bufferView"]);
+	assert_eq!(
+		accessors[3]["count"].as_u64().unwrap() as usize * 4,
+		indices.len()
+	);
+	assert_eq!(indices.len() % 12, 0);
+	for index in indices.chunks(4) {
+		assert!((u32::from_le_bytes(index.try_into().unwrap()) as usize) < vertices);
+	}
+
+	let texture = view(&document["images"][0]["bufferView"]);
+	let texture = image::load_from_memory(texture).unwrap().to_rgba8();
+	assert_eq!(texture.dimensions(), (64, 64));
+	// transparent pixels get the icon's color, so cells cut along the outline don't show black
+	assert_eq!(texture.get_pixel(0, 0), &Rgba([200, 40, 40, 255]));
+}
+
+#[test]
+fn test_extrude_no_overlap() {
+	// a concave corner, a one cell gap, cells only touching diagonally and a one cell wide line
+	let rows = [
+		"####.#.#", //
+		"####..#.", //
+		"##......", //
+		"##.#####", //
+	];
+	let mut cells = vec![false; (GRID * GRID) as usize];
+	for (j, row) in rows.iter().enumerate() {
+		for (i, cell) in row.chars().enumerate() {
+			cells[j * GRID as usize + i] = cell == '#';
+		}
+	}
+	let mask = Mask { cells };
+	let mesh = extrude_mesh(&mask);
+	let triangles = mesh
+		.indices
+		.chunks(3)
+		.map(|t| {
+			let t: [u32; 3] = t.try_into().unwrap();
+			(
+				t.map(|n| mesh.positions[n as usize]),
+				mesh.normals[t[0] as usize],
+			)
+		})
+		.collect::<Vec<_>>();
+
+	// every spot is covered once from the front and once from the back, or not at all,
+	// sampled away from the edges and diagonals the faces get split along
+	let covers = |p: [f32; 2], [a, b, c]: [[f32; 3]; 3]| {
+		let side = |a: [f32; 3], b: [f32; 3]| {
+			(b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+		};
+		let sides = [side(a, b), side(b, c), side(c, a)];
+		sides.iter().all(|s| *s > 0.0) || sides.iter().all(|s| *s < 0.0)
+	};
+	let samples = [0.137, 0.509, 0.883]
+		.into_iter()
+		.flat_map(|du| [0.071, 0.462, 0.917].map(|dv| (du, dv)));
+	for j in 0..rows.len() as i32 + 1 {
+		for i in 0..rows[0].len() as i32 + 1 {
+			for (du, dv) in samples.clone() {
+				let [x, y, _] = grid_point(i as f32 + du, j as f32 + dv, 0.0);
+				let expected = mask.get(i, j) as usize;
+				for facing in [1.0, -1.0] {
+					let count = triangles
+						.iter()
+						.filter(|(t, normal)| normal[2] * facing > 0.0 && covers([x, y], *t))
+						.count();
+					assert_eq!(count, expected, "cell {i}, {j} at {du}, {dv}");
+				}
+			}
+		}
+	}
+
+	// the sides stand on the outline, so none of them share any length
+	let mut sides = triangles
+		.iter()
+		.filter(|(_, normal)| normal[2] == 0.0)
+		.map(|(t, _)| {
+			let mut ends = t.map(|p| [p[0], p[1]]);
+			ends.sort_by(|a, b| a.partial_cmp(b).unwrap());
+			[ends[0], ends[2]]
+		})
+		.collect::<Vec<_>>();
+	sides.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	sides.dedup();
+	for (n, [a, b]) in sides.iter().enumerate() {
+		for [c, d] in &sides[n + 1..] {
+			let horizontal = a[1] == b[1] && c[1] == d[1] && a[1] == c[1];
+			let vertical = a[0] == b[0] && c[0] == d[0] && a[0] == c[0];
+			let overlap = if horizontal {
+				b[0].min(d[0]) - a[0].max(c[0])
+			} else if vertical {
+				b[1].min(d[1]) - a[1].max(c[1])
+			} else {
+				0.0
+			};
+			assert!(overlap <= 0.0, "sides {a:?}-{b:?} and {c:?}-{d:?} overlap");
+		}
+	}
+	assert!(!sides.is_empty());
+}
@@ -0,0 +1,96 @@
+use crate::icon_cache::get_icon_cache_dir;
+use crate::index::write_atomic;
+use crate::xdg::{Icon, get_png_from_svg};
+use rustc_hash::FxHasher;
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+
+/// Up to two letters standing in for an app, like `LW` for `LibreOffice Writer` or `F` for `Firefox`.
+pub fn initials(name: &str) -> String {
+	name.split(|c: char| !c.is_alphanumeric())
+		.filter_map(|word| word.chars().next())
+		.take(2)
+		.flat_map(char::to_uppercase)
+		.collect()
+}
+
+/// A background color picked from the app's ID, so an app keeps its color and neighbours rarely share one.
+pub fn background_color(id: &str) -> [u8; 3] {
+	let mut hasher = FxHasher::default();
+	hasher.write(id.as_bytes());
+	let hue = (hasher.finish() % 360) as f32;
+	// saturated and dark enough for white text on top
+	hsl_to_rgb(hue, 0.55, 0.42)
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [u8; 3] {
+	let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+	let sector = hue / 60.0;
+	let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+	let (r, g, b) = match sector as u32 {
+		0 => (chroma, x, 0.0),
+		1 => (x, chroma, 0.0),
+		2 => (0.0, chroma, x),
+		3 => (0.0, x, chroma),
+		4 => (x, 0.0, chroma),
+		_ => (chroma, 0.0, x),
+	};
+	let m = lightness - chroma / 2.0;
+	[r, g, b].map(|c| ((c + m) * 255.0).round() as u8)
+}
+
+/// The monogram as an SVG, a rounded square in the app's color with its initials on top.
+pub fn fallback_svg(name: &str, id: &str) -> String {
+	let [r, g, b] = background_color(id);
+	// only alphanumerics, so nothing to escape
+	let initials = initials(name);
+	// two letters need a smaller font to fit
+	let font_size = if initials.chars().count() > 1 { 42 } else { 56 };
+	format!(
+		r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+	<rect x="4" y="4" width="92" height="92" rx="20" fill="#{r:02x}{g:02x}{b:02x}"/>
+	<text x="50" y="50" dy="0.35em" text-anchor="middle" font-family="sans-serif" font-weight="bold" font-size="{font_size}" fill="#ffffff">{initials}</text>
+</svg>"##
+	)
+}
+
+/// A generated icon for apps that don't have one, rendered and cached like any other SVG icon.
+pub fn fallback_icon(name: &str, id: &str, size: u16) -> io::Result<Icon> {
+	let dir = get_icon_cache_dir().join("fallback");
+	fs::create_dir_all(&dir)?;
+	let svg_path = dir.join(format!("{id}.svg"));
+	let svg = fallback_svg(name, id);
+	// rewriting an unchanged file would bump its mtime and make the cache hash it again
+	if fs::read_to_string(&svg_path).ok().as_deref() != Some(svg.as_str()) {
+		write_atomic(&svg_path, svg)?;
+	}
+	let png_path = get_png_from_svg(&svg_path, size)?;
+	Icon::from_path(png_path, size).ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))
+}
+
+#[test]
+fn test_fallback_icon() {
+	assert_eq!(initials("LibreOffice Writer"), "LW");
+	assert_eq!(initials("firefox"), "F");
+	assert_eq!(initials("  GNU Image Manipulation Program"), "GI");
+	assert_eq!(initials("ñandú"), "Ñ");
+	assert_eq!(initials(""), "");
+
+	// deterministic, but different apps get different colors
+	assert_eq!(
+		background_color("org.gnome.Calculator.desktop"),
+		background_color("org.gnome.Calculator.desktop")
+	);
+	assert_ne!(
+		background_color("org.gnome.Calculator.desktop"),
+		background_color("firefox.desktop")
+	);
+	assert_eq!(hsl_to_rgb(0.0, 1.0, 0.5), [255, 0, 0]);
+	assert_eq!(hsl_to_rgb(240.0, 1.0, 0.5), [0, 0, 255]);
+
+	let svg = fallback_svg("Tom & Jerry", "tom.desktop");
+	assert!(svg.contains(">TJ</text>"));
+	let [r, g, b] = background_color("tom.desktop");
+	assert!(svg.contains(&format!("fill=\"#{r:02x}{g:02x}{b:02x}\"")));
+}
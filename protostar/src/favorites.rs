@@ -0,0 +1,163 @@
+use crate::index::write_atomic;
+use crate::watch::FileWatcher;
+use crate::xdg::get_config_home;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+lazy_static! {
+	static ref FAVORITES: Mutex<Favorites> = Mutex::new(watch_favorites(get_favorites_path()));
+}
+
+pub fn get_favorites_path() -> PathBuf {
+	get_config_home().join("protostar").join("favorites.toml")
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct FavoritesFile {
+	/// Desktop file IDs, in the order they're shown.
+	favorites: Vec<String>,
+}
+
+/// Apps the user pinned, shared by all launchers through a file in `XDG_CONFIG_HOME`.
+#[derive(Debug, Clone)]
+pub struct Favorites {
+	path: PathBuf,
+	ids: Vec<String>,
+}
+impl Favorites {
+	/// Loads the favorites, starting with none if the file is missing or unreadable.
+	pub fn load(path: PathBuf) -> Self {
+		let ids = fs::read_to_string(&path)
+			.ok()
+			.and_then(|text| toml::de::from_str::<FavoritesFile>(&text).ok())
+			.unwrap_or_default()
+			.favorites;
+		Favorites { path, ids }
+	}
+
+	pub fn ids(&self) -> &[String] {
+		&self.ids
+	}
+	pub fn contains(&self, id: &str) -> bool {
+		self.ids.iter().any(|i| i == id)
+	}
+
+	/// Adds an app at the end, returns false if it was already pinned.
+	pub fn pin(&mut self, id: &str) -> bool {
+		if self.contains(id) {
+			return false;
+		}
+		self.ids.push(id.to_string());
+		true
+	}
+
+	/// Returns false if the app wasn't pinned.
+	pub fn unpin(&mut self, id: &str) -> bool {
+		let len = self.ids.len();
+		self.ids.retain(|i| i != id);
+		self.ids.len() != len
+	}
+
+	/// Moves a pinned app to `index`, clamped to the end. Returns false if the app wasn't pinned.
+	pub fn reorder(&mut self, id: &str, index: usize) -> bool {
+		let Some(current) = self.ids.iter().position(|i| i == id) else {
+			return false;
+		};
+		let id = self.ids.remove(current);
+		self.ids.insert(index.min(self.ids.len()), id);
+		true
+	}
+
+	/// Indices into `ids` with pinned apps first in pinned order, then the rest in their original order.
+	pub fn order<S: AsRef<str>>(&self, ids: &[S]) -> Vec<usize> {
+		let mut order = (0..ids.len()).collect::<Vec<_>>();
+		order.sort_by_key(|i| {
+			self.ids
+				.iter()
+				.position(|f| f == ids[*i].as_ref())
+				.unwrap_or(usize::MAX)
+		});
+		order
+	}
+
+	/// Writes the favorites to disk, replacing the old file atomically.
+	pub fn save(&self) -> std::io::Result<()> {
+		let file = FavoritesFile {
+			favorites: self.ids.clone(),
+		};
+		let text = toml::ser::to_string(&file)
+			.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		write_atomic(&self.path, text)
+	}
+}
+
+/// Loads the favorites and picks up changes made by other launchers in the background.
+fn watch_favorites(path: PathBuf) -> Favorites {
+	let favorites = Favorites::load(path.clone());
+	// without inotify, changes from other launchers only show up on restart
+	if let Ok(watcher) = FileWatcher::new(&path) {
+		let _ = watcher.spawn(move || *FAVORITES.lock().unwrap() = Favorites::load(path.clone()));
+	}
+	favorites
+}
+
+/// The current favorites, kept up to date with changes from other launchers.
+/// Cheap enough to call every time the UI is rebuilt.
+pub fn favorites() -> Favorites {
+	FAVORITES.lock().unwrap().clone()
+}
+
+pub fn is_favorite(id: &str) -> bool {
+	FAVORITES.lock().unwrap().contains(id)
+}
+
+fn update(f: impl FnOnce(&mut Favorites) -> bool) -> std::io::Result<()> {
+	let mut favorites = FAVORITES.lock().unwrap();
+	// another launcher may have saved since the watcher last reloaded
+	*favorites = Favorites::load(favorites.path.clone());
+	if f(&mut favorites) {
+		favorites.save()?;
+	}
+	Ok(())
+}
+
+pub fn pin(id: &str) -> std::io::Result<()> {
+	update(|f| f.pin(id))
+}
+
+pub fn unpin(id: &str) -> std::io::Result<()> {
+	update(|f| f.unpin(id))
+}
+
+pub fn reorder(id: &str, index: usize) -> std::io::Result<()> {
+	update(|f| f.reorder(id, index))
+}
+
+#[test]
+fn test_favorites() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let path = dir.path().join("favorites.toml");
+
+	let mut favorites = Favorites::load(path.clone());
+	assert!(favorites.pin("a.desktop"));
+	assert!(favorites.pin("b.desktop"));
+	assert!(favorites.pin("c.desktop"));
+	assert!(!favorites.pin("a.desktop"));
+	assert!(favorites.reorder("c.desktop", 0));
+	assert!(favorites.unpin("b.desktop"));
+	assert!(!favorites.unpin("b.desktop"));
+	favorites.save().unwrap();
+
+	let favorites = Favorites::load(path);
+	assert_eq!(favorites.ids(), ["c.desktop", "a.desktop"]);
+	assert_eq!(
+		favorites.order(&["x.desktop", "a.desktop", "y.desktop", "c.desktop"]),
+		[3, 1, 0, 2]
+	);
+}
@@ -0,0 +1,221 @@
+use crate::xdg::DesktopFile;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Which apps to show, configured through the `[filter]` section of the config:
+///
+/// ```toml
+/// [filter]
+/// deny = [
+///     { field = "id", glob = "org.kde.*settings*" },
+///     { field = "exec", regex = "uninstall|--remove" },
+/// ]
+/// # if not empty, only apps matching one of these rules are shown
+/// allow = [{ field = "category", glob = "Game" }]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filter {
+	pub allow: Vec<FilterRule>,
+	pub deny: Vec<FilterRule>,
+}
+impl Filter {
+	pub fn allows(&self, desktop_file: &DesktopFile) -> bool {
+		let id = desktop_file.id();
+		let allowed =
+			self.allow.is_empty() || self.allow.iter().any(|r| r.matches(&id, desktop_file));
+		allowed && !self.deny.iter().any(|r| r.matches(&id, desktop_file))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterField {
+	/// The desktop file ID, like `org.gnome.Settings.desktop`.
+	Id,
+	Name,
+	/// Matches if any of the categories does.
+	Category,
+	/// The command line from the `Exec` key.
+	Exec,
+}
+
+/// Matches one field of a desktop file against a glob or a regex.
+/// Globs have to match the whole value, regexes anywhere in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawFilterRule", into = "RawFilterRule")]
+pub struct FilterRule {
+	pub field: FilterField,
+	pattern: Pattern,
+	regex: Regex,
+}
+impl PartialEq for FilterRule {
+	fn eq(&self, other: &Self) -> bool {
+		self.field == other.field && self.pattern == other.pattern
+	}
+}
+impl FilterRule {
+	pub fn glob(field: FilterField, glob: &str) -> Result<Self, String> {
+		Self::new(field, Pattern::Glob(glob.to_string()))
+	}
+	pub fn regex(field: FilterField, regex: &str) -> Result<Self, String> {
+		Self::new(field, Pattern::Regex(regex.to_string()))
+	}
+
+	fn new(field: FilterField, pattern: Pattern) -> Result<Self, String> {
+		let regex = match &pattern {
+			Pattern::Glob(glob) => Regex::new(&glob_to_regex(glob))
+				.map_err(|e| format!("invalid glob {glob:?}: {e}"))?,
+			Pattern::Regex(regex) => {
+				Regex::new(regex).map_err(|e| format!("invalid regex {regex:?}: {e}"))?
+			}
+		};
+		Ok(FilterRule {
+			field,
+			pattern,
+			regex,
+		})
+	}
+
+	fn matches(&self, id: &str, desktop_file: &DesktopFile) -> bool {
+		match self.field {
+			FilterField::Id => self.regex.is_match(id),
+			FilterField::Name => desktop_file
+				.name
+				.as_deref()
+				.is_some_and(|n| self.regex.is_match(n)),
+			FilterField::Category => desktop_file
+				.categories
+				.iter()
+				.any(|c| self.regex.is_match(c)),
+			FilterField::Exec => desktop_file
+				.command
+				.as_deref()
+				.is_some_and(|c| self.regex.is_match(c)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+	Glob(String),
+	Regex(String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFilterRule {
+	field: FilterField,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	glob: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	regex: Option<String>,
+}
+impl TryFrom<RawFilterRule> for FilterRule {
+	type Error = String;
+
+	fn try_from(raw: RawFilterRule) -> Result<Self, Self::Error> {
+		match (raw.glob, raw.regex) {
+			(Some(glob), None) => FilterRule::glob(raw.field, &glob),
+			(None, Some(regex)) => FilterRule::regex(raw.field, &regex),
+			_ => Err("a filter rule needs exactly one of `glob` or `regex`".to_string()),
+		}
+	}
+}
+impl From<FilterRule> for RawFilterRule {
+	fn from(rule: FilterRule) -> Self {
+		let (glob, regex) = match rule.pattern {
+			Pattern::Glob(glob) => (Some(glob), None),
+			Pattern::Regex(regex) => (None, Some(regex)),
+		};
+		RawFilterRule {
+			field: rule.field,
+			glob,
+			regex,
+		}
+	}
+}
+
+/// Supports `*`, `?` and `[...]` classes, with `[!...]` for negation.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+	let mut regex = String::from("^");
+	let mut chars = glob.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'*' => regex.push_str(".*"),
+			'?' => regex.push('.'),
+			'[' => {
+				regex.push('[');
+				if chars.next_if_eq(&'!').is_some() {
+					regex.push('^');
+				}
+				for c in chars.by_ref() {
+					if c == ']' {
+						break;
+					}
+					if c == '\\' || c == '[' {
+						regex.push('\\');
+					}
+					regex.push(c);
+				}
+				regex.push(']');
+			}
+			c => regex.push_str(&regex::escape(&c.to_string())),
+		}
+	}
+	regex.push('$');
+	regex
+}
+
+#[test]
+fn test_filter() {
+	use std::fs;
+
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let parse = |name: &str, data: &str| {
+		let path = dir.path().join(name);
+		fs::write(&path, data).unwrap();
+		DesktopFile::parse(path).unwrap()
+	};
+	let settings = parse(
+		"org.kde.kcm_settings.desktop",
+		"[Desktop Entry]\nName=System Settings\nExec=systemsettings\nCategories=Settings;Qt;",
+	);
+	let uninstall = parse(
+		"game-uninstall.desktop",
+		"[Desktop Entry]\nName=Uninstall Game\nExec=game --uninstall\nCategories=Game;",
+	);
+	let game = parse(
+		"game.desktop",
+		"[Desktop Entry]\nName=Game\nExec=game\nCategories=Game;",
+	);
+
+	let filter: Filter = toml::de::from_str(
+		r#"
+deny = [
+	{ field = "id", glob = "org.kde.*" },
+	{ field = "exec", regex = "--uninstall" },
+]
+"#,
+	)
+	.unwrap();
+	assert!(!filter.allows(&settings));
+	assert!(!filter.allows(&uninstall));
+	assert!(filter.allows(&game));
+
+	let filter = Filter {
+		allow: vec![FilterRule::glob(FilterField::Category, "Set[!u]ings").unwrap()],
+		deny: Vec::new(),
+	};
+	assert!(filter.allows(&settings));
+	assert!(!filter.allows(&game));
+
+	let error = toml::de::from_str::<Filter>(r#"deny = [{ field = "name", regex = "(" }]"#)
+		.unwrap_err()
+		.to_string();
+	assert!(error.contains("invalid regex"), "{error}");
+	let error = toml::de::from_str::<Filter>(r#"deny = [{ field = "name" }]"#)
+		.unwrap_err()
+		.to_string();
+	assert!(error.contains("exactly one"), "{error}");
+}
@@ -0,0 +1,326 @@
+use glam::{Mat4, Quat, Vec3};
+use serde_json::Value;
+use std::path::Path;
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+
+/// An axis aligned bounding box in the model's own units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+	pub min: Vec3,
+	pub max: Vec3,
+}
+impl Bounds {
+	pub fn size(&self) -> Vec3 {
+		self.max - self.min
+	}
+	pub fn center(&self) -> Vec3 {
+		(self.min + self.max) / 2.0
+	}
+
+	fn union(self, other: Bounds) -> Bounds {
+		Bounds {
+			min: self.min.min(other.min),
+			max: self.max.max(other.max),
+		}
+	}
+
+	fn transformed(self, matrix: Mat4) -> Bounds {
+		let corners = (0..8).map(|i| {
+			let corner = Vec3::new(
+				if i & 1 == 0 { self.min.x } else { self.max.x },
+				if i & 2 == 0 { self.min.y } else { self.max.y },
+				if i & 4 == 0 { self.min.z } else { self.max.z },
+			);
+			matrix.transform_point3(corner)
+		});
+		corners
+			.map(|corner| Bounds {
+				min: corner,
+				max: corner,
+			})
+			.reduce(Bounds::union)
+			.unwrap()
+	}
+
+	/// The uniform scale and offset that center the model on the origin and fit its largest side
+	/// into `size`, to be applied before any rotation.
+	pub fn normalization(&self, size: f32) -> Normalization {
+		let extent = self.size().max_element();
+		let scale = if extent > 0.0 { size / extent } else { 1.0 };
+		Normalization {
+			scale,
+			translation: -self.center() * scale,
+		}
+	}
+}
+
+/// Maps a model's own units to a fixed size, see [`Bounds::normalization`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+	pub scale: f32,
+	pub translation: Vec3,
+}
+
+/// The JSON document of a glTF binary, after checking its header and chunk layout.
+pub fn glb_json(data: &[u8]) -> Result<Value, String> {
+	let word = |offset: usize| {
+		data.get(offset..offset + 4)
+			.map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+			.ok_or_else(|| "glb is truncated".to_string())
+	};
+	if word(0)? != GLB_MAGIC {
+		return Err("not a glb file".to_string());
+	}
+	if word(4)? != 2 {
+		return Err(format!("unsupported glb version {}", word(4)?));
+	}
+	if word(8)? as usize != data.len() {
+		return Err(format!(
+			"glb header says {} bytes but the file has {}",
+			word(8)?,
+			data.len()
+		));
+	}
+
+	let mut json = None;
+	let mut offset = 12;
+	while offset < data.len() {
+		let length = word(offset)? as usize;
+		let kind = word(offset + 4)?;
+		let start = offset + 8;
+		let end = start
+			.checked_add(length)
+			.filter(|end| *end <= data.len())
+			.ok_or("glb chunk runs past the end of the file")?;
+		if !length.is_multiple_of(4) {
+			return Err("glb chunk isn't 4 byte aligned".to_string());
+		}
+		match kind {
+			CHUNK_JSON if offset == 12 => json = Some(&data[start..end]),
+			CHUNK_JSON => return Err("glb has more than one JSON chunk".to_string()),
+			_ if offset == 12 => return Err("glb doesn't start with a JSON chunk".to_string()),
+			// the binary chunk and any extension chunks don't matter for the bounds
+			_ => (),
+		}
+		offset = end;
+	}
+	let json = json.ok_or("glb has no JSON chunk")?;
+	serde_json::from_slice(json).map_err(|e| format!("invalid glTF JSON: {e}"))
+}
+
+/// The bounds of everything in the default scene, from the `min` and `max` the spec requires
+/// every `POSITION` accessor to have, so no vertex data needs to be read.
+pub fn document_bounds(document: &Value) -> Result<Bounds, String> {
+	let version = document["asset"]["version"].as_str().unwrap_or_default();
+	if !version.starts_with("2.") {
+		return Err(format!("unsupported glTF version {version:?}"));
+	}
+	let nodes = document["nodes"]
+		.as_array()
+		.map(Vec::as_slice)
+		.unwrap_or_default();
+	let roots = match document["scenes"].as_array() {
+		Some(scenes) => {
+			let scene = document["scene"].as_u64().unwrap_or(0) as usize;
+			let scene = scenes.get(scene).ok_or("glTF scene doesn't exist")?;
+			scene["nodes"]
+				.as_array()
+				.map(|nodes| nodes.iter().map(index).collect::<Result<Vec<_>, _>>())
+				.transpose()?
+				.unwrap_or_default()
+		}
+		// without scenes every node that isn't a child is shown
+		None => {
+			let children = nodes
+				.iter()
+				.flat_map(|node| node["children"].as_array().into_iter().flatten())
+				.filter_map(Value::as_u64)
+				.collect::<Vec<_>>();
+			(0..nodes.len())
+				.filter(|i| !children.contains(&(*i as u64)))
+				.collect()
+		}
+	};
+
+	nodes_bounds(document, roots)?.ok_or_else(|| "glTF has no meshes to show".to_string())
+}
+
+fn index(value: &Value) -> Result<usize, String> {
+	value
+		.as_u64()
+		.map(|i| i as usize)
+		.ok_or_else(|| format!("invalid glTF index {value}"))
+}
+
+fn floats<const N: usize>(value: &Value) -> Option<[f32; N]> {
+	let values = value.as_array()?;
+	if values.len() != N {
+		return None;
+	}
+	let mut result = [0.0; N];
+	for (result, value) in result.iter_mut().zip(values) {
+		*result = value.as_f64()? as f32;
+		if !result.is_finite() {
+			return None;
+		}
+	}
+	Some(result)
+}
+
+fn node_transform(node: &Value) -> Result<Mat4, String> {
+	if !node["matrix"].is_null() {
+		let matrix = floats::<16>(&node["matrix"]).ok_or("invalid glTF node matrix")?;
+		return Ok(Mat4::from_cols_array(&matrix));
+	}
+	let optional = |key: &str, default| {
+		if node[key].is_null() {
+			Ok(default)
+		} else {
+			floats::<3>(&node[key]).ok_or_else(|| format!("invalid glTF node {key}"))
+		}
+	};
+	let translation = optional("translation", [0.0; 3])?;
+	let scale = optional("scale", [1.0; 3])?;
+	let rotation = if node["rotation"].is_null() {
+		Quat::IDENTITY
+	} else {
+		Quat::from_array(floats::<4>(&node["rotation"]).ok_or("invalid glTF node rotation")?)
+	};
+	Ok(Mat4::from_scale_rotation_translation(
+		scale.into(),
+		rotation,
+		translation.into(),
+	))
+}
+
+/// The bounds of the meshes in the trees of nodes under `roots`. The spec requires every node to have
+/// at most one parent, so reaching a node twice, through a loop or a second parent, rejects the file
+/// instead of walking shared subtrees over and over.
+fn nodes_bounds(document: &Value, roots: Vec<usize>) -> Result<Option<Bounds>, String> {
+	let mut visited = vec![false; document["nodes"].as_array().map_or(0, Vec::len)];
+	// a stack rather than recursion, so deeply nested nodes can't overflow it
+	let mut pending = roots
+		.into_iter()
+		.map(|root| (root, Mat4::IDENTITY))
+		.collect::<Vec<_>>();
+	let mut bounds: Option<Bounds> = None;
+	while let Some((node, parent)) = pending.pop() {
+		let Some(seen) = visited.get_mut(node) else {
+			return Err(format!("glTF node {node} doesn't exist"));
+		};
+		if std::mem::replace(seen, true) {
+			return Err(format!(
+				"glTF node {node} has more than one parent or is its own ancestor"
+			));
+		}
+		let node = &document["nodes"][node];
+		let transform = parent * node_transform(node)?;
+
+		if !node["mesh"].is_null() {
+			let mesh = index(&node["mesh"])?;
+			let primitives = document["meshes"][mesh]["primitives"]
+				.as_array()
+				.ok_or_else(|| format!("glTF mesh {mesh} doesn't exist or has no primitives"))?;
+			for primitive in primitives {
+				let position = &primitive["attributes"]["POSITION"];
+				if position.is_null() {
+					continue;
+				}
+				let accessor = &document["accessors"][index(position)?];
+				let (Some(min), Some(max)) =
+					(floats::<3>(&accessor["min"]), floats::<3>(&accessor["max"]))
+				else {
+					return Err("glTF POSITION accessor has no valid min and max".to_string());
+				};
+				if (0..3).any(|axis| min[axis] > max[axis]) {
+					return Err("glTF POSITION accessor has min above max".to_string());
+				}
+				let local = Bounds {
+					min: min.into(),
+					max: max.into(),
+				};
+				let world = local.transformed(transform);
+				bounds = Some(bounds.map_or(world, |b| b.union(world)));
+			}
+		}
+		for child in node["children"].as_array().into_iter().flatten() {
+			pending.push((index(child)?, transform));
+		}
+	}
+	Ok(bounds)
+}
+
+/// The bounds of a `.glb` or `.gltf` file, rejecting ones that are malformed.
+pub fn model_bounds(path: impl AsRef<Path>) -> Result<Bounds, String> {
+	let data = std::fs::read(path.as_ref()).map_err(|e| e.to_string())?;
+	let document = if data.starts_with(b"glTF") {
+		glb_json(&data)?
+	} else {
+		serde_json::from_slice(&data).map_err(|e| format!("invalid glTF JSON: {e}"))?
+	};
+	document_bounds(&document)
+}
+
+#[test]
+fn test_model_bounds() {
+	use serde_json::json;
+
+	let mut document = json!({
+		"asset": {"version": "2.0"},
+		"scene": 0,
+		"scenes": [{"nodes": [0]}],
+		"nodes": [
+			{"children": [1, 2], "scale": [2.0, 2.0, 2.0]},
+			{"mesh": 0, "translation": [10.0, 0.0, 0.0]},
+			{"mesh": 0, "rotation": [0.0, 0.0, 0.70710677, 0.70710677]},
+		],
+		"meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+		"accessors": [{"count": 3, "type": "VEC3", "componentType": 5126, "min": [0.0, 0.0, 0.0], "max": [1.0, 2.0, 3.0]}],
+	});
+	let bounds = document_bounds(&document).unwrap();
+	// the rotated copy turns its y extent into -x, both are scaled by their parent
+	let close = |a: Vec3, b: Vec3| (a - b).abs().max_element() < 1e-4;
+	assert!(close(bounds.min, Vec3::new(-4.0, 0.0, 0.0)));
+	assert!(close(bounds.max, Vec3::new(22.0, 4.0, 6.0)));
+
+	let normalization = bounds.normalization(0.05);
+	assert!((normalization.scale - 0.05 / 26.0).abs() < 1e-6);
+	let fitted_min = bounds.min * normalization.scale + normalization.translation;
+	let fitted_max = bounds.max * normalization.scale + normalization.translation;
+	assert!(close(fitted_min, -fitted_max));
+	assert!((fitted_max.x - 0.025).abs() < 1e-6);
+
+	// the glb the extruder makes goes through the binary path
+	let mut image = image::RgbaImage::new(32, 32);
+	image.put_pixel(16, 16, image::Rgba([255, 255, 255, 255]));
+	let glb = crate::extrude::extrude_icon(&image).unwrap();
+	assert!(document_bounds(&glb_json(&glb).unwrap()).is_ok());
+
+	// malformed files are rejected rather than guessed at
+	assert!(glb_json(&glb[..glb.len() - 4]).is_err());
+	assert!(glb_json(b"glTF\x01\0\0\0\x0c\0\0\0").is_err());
+	document["nodes"][1]["children"] = json!([0]);
+	assert!(document_bounds(&document).is_err());
+	document["nodes"][1]["children"] = json!([]);
+	document["nodes"][0]["children"] = json!([1, 1]);
+	assert!(document_bounds(&document).is_err());
+	document["nodes"][0]["children"] = json!([1, 2]);
+	document["accessors"][0]["max"] = json!([1.0, 2.0]);
+	assert!(document_bounds(&document).is_err());
+	document["accessors"][0]["max"] = json!([1.0, 2.0, 3.0]);
+	document["asset"]["version"] = json!("1.0");
+	assert!(document_bounds(&document).is_err());
+	document["asset"]["version"] = json!("2.0");
+
+	// a chain listing every child twice would take 2^64 steps to walk if shared nodes were allowed
+	let mut chain = (0..64)
+		.map(|i| json!({"children": [i + 1, i + 1]}))
+		.collect::<Vec<_>>();
+	chain.push(json!({"mesh": 0}));
+	document["scenes"][0]["nodes"] = json!([0]);
+	document["nodes"] = json!(chain);
+	let error = document_bounds(&document).unwrap_err();
+	assert!(error.contains("more than one parent"), "{error}");
+}
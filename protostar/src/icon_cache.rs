@@ -0,0 +1,382 @@
+use crate::index::{FileStamp, write_atomic};
+use crate::xdg::get_cache_home;
+use lazy_static::lazy_static;
+use rustc_hash::FxHasher;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hasher;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Bump whenever the way icons get rendered changes, so old renders get replaced instead of reused.
+const CACHE_VERSION: u32 = 3;
+/// How stale a cached file's mtime has to be before a hit bumps it, so hits don't all cost a write.
+const TOUCH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Eviction goes this far below the size cap, so a full cache isn't scanned again on every render.
+const EVICT_TO_PERCENT: u64 = 90;
+
+lazy_static! {
+	static ref ICON_CACHE: Mutex<IconCache> = Mutex::new(IconCache::load(
+		get_icon_cache_dir(),
+		crate::config::config().icon_cache_mb * 1024 * 1024,
+	));
+}
+
+pub fn get_icon_cache_dir() -> PathBuf {
+	get_cache_home().join("protostar_icon_cache")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SourceEntry {
+	stamp: FileStamp,
+	/// Hex, since TOML integers can't hold every `u64`.
+	hash: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ManifestFile {
+	version: u32,
+	/// Size of the renders at the last save, see [`IconCache::bytes`].
+	#[serde(default)]
+	bytes: Option<u64>,
+	sources: Vec<(PathBuf, SourceEntry)>,
+}
+
+/// What [`IconCache::lookup`] found.
+enum Lookup {
+	Hit(PathBuf),
+	/// Not rendered yet, with where the render goes and the source's contents to make it from.
+	Miss(PathBuf, Vec<u8>),
+}
+
+/// Rendered PNGs of icons that can't be used as textures directly, shared by every launcher process.
+///
+/// Files are named by a hash of the source's contents and the size, so the same name from two themes
+/// can't collide and identical files share a render. The manifest only remembers which hash each
+/// source had at which mtime, to skip re-hashing unchanged files. Last use is tracked through the
+/// rendered files' mtimes, so other processes' hits count towards the LRU order without any locking.
+#[derive(Debug)]
+pub struct IconCache {
+	dir: PathBuf,
+	max_bytes: u64,
+	/// A running total of the renders' size, so the cache dir only gets scanned when it may be over
+	/// the cap. Other processes' renders aren't counted until then, the scan puts it right again.
+	bytes: Option<u64>,
+	sources: HashMap<PathBuf, SourceEntry>,
+	dirty: bool,
+}
+impl IconCache {
+	/// Loads the manifest in `dir`, a missing, corrupt or outdated one starts empty.
+	pub fn load(dir: PathBuf, max_bytes: u64) -> Self {
+		let file = fs::read_to_string(dir.join("manifest.toml"))
+			.ok()
+			.and_then(|text| toml::de::from_str::<ManifestFile>(&text).ok())
+			.filter(|file| file.version == CACHE_VERSION)
+			.unwrap_or_default();
+		IconCache {
+			dir,
+			max_bytes,
+			bytes: file.bytes,
+			sources: file.sources.into_iter().collect(),
+			dirty: false,
+		}
+	}
+
+	/// The cached render of `source` at `size`, calling `render` with the source's contents
+	/// to make the PNG data if there isn't one or the source changed since.
+	pub fn get_or_render(
+		&mut self,
+		source: &Path,
+		size: u16,
+		render: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+	) -> io::Result<PathBuf> {
+		self.get_or_create(source, &format!("{size}.png"), render)
+	}
+
+	/// Like [`IconCache::get_or_render`] for anything else worth keeping about a source, like its
+	/// [`crate::palette`] color. `kind` tells apart what's stored and ends up in the file name.
+	pub fn get_or_derive(
+		&mut self,
+		source: &Path,
+		kind: &str,
+		derive: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+	) -> io::Result<PathBuf> {
+		self.get_or_create(source, kind, derive)
+	}
+
+	fn get_or_create(
+		&mut self,
+		source: &Path,
+		suffix: &str,
+		create: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+	) -> io::Result<PathBuf> {
+		let (path, data) = match self.lookup(source, suffix)? {
+			Lookup::Hit(path) => return Ok(path),
+			Lookup::Miss(path, data) => (path, data),
+		};
+		let contents = create(&data)?;
+		store(&path, &contents)?;
+		self.record(&path, contents.len() as u64);
+		Ok(path)
+	}
+
+	/// Finds the render of `source` with `suffix`, hashing the source if it changed since it was last seen.
+	fn lookup(&mut self, source: &Path, suffix: &str) -> io::Result<Lookup> {
+		let stamp = FileStamp::read(source).ok_or(ErrorKind::NotFound)?;
+		let mut data = None;
+		let hash = match self.sources.get(source) {
+			Some(entry) if entry.stamp == stamp => entry.hash.clone(),
+			_ => {
+				let contents = fs::read(source)?;
+				let hash = content_hash(&contents);
+				self.sources.insert(
+					source.to_path_buf(),
+					SourceEntry {
+						stamp,
+						hash: hash.clone(),
+					},
+				);
+				self.dirty = true;
+				data = Some(contents);
+				hash
+			}
+		};
+
+		let path = self.dir.join(format!("{hash}-{suffix}"));
+		if path.exists() {
+			touch(&path);
+			return Ok(Lookup::Hit(path));
+		}
+		let data = match data {
+			Some(data) => data,
+			None => fs::read(source)?,
+		};
+		Ok(Lookup::Miss(path, data))
+	}
+
+	/// Counts a stored render towards the size cap, evicting once the cache may have gone over it.
+	fn record(&mut self, path: &Path, len: u64) {
+		self.dirty = true;
+		match self.bytes {
+			Some(bytes) if bytes.saturating_add(len) <= self.max_bytes => {
+				self.bytes = Some(bytes + len)
+			}
+			_ => match self.evict(path) {
+				Ok(bytes) => self.bytes = Some(bytes),
+				Err(e) => tracing::warn!("Failed to evict old icons from the cache: {e}"),
+			},
+		}
+	}
+
+	/// If the cache is over its size cap, removes the least recently used files until it's
+	/// [`EVICT_TO_PERCENT`] of it, never `keep`. Returns the size of what's left.
+	fn evict(&self, keep: &Path) -> io::Result<u64> {
+		let mut files = Vec::new();
+		let mut total = 0;
+		for entry in fs::read_dir(&self.dir)? {
+			let entry = entry?;
+			let path = entry.path();
+			// only what get_or_create made, not the manifest, fallback SVGs or someone's half written file
+			let name = entry.file_name();
+			let name = name.to_string_lossy();
+			if name == "manifest.toml" || name.ends_with(".tmp") || !entry.file_type()?.is_file() {
+				continue;
+			}
+			let Ok(metadata) = entry.metadata() else {
+				continue;
+			};
+			total += metadata.len();
+			if path != keep {
+				let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+				files.push((used, metadata.len(), path));
+			}
+		}
+		if total <= self.max_bytes {
+			return Ok(total);
+		}
+		let target = self.max_bytes - self.max_bytes / 100 * (100 - EVICT_TO_PERCENT);
+		files.sort();
+		for (_, len, path) in files {
+			if total <= target {
+				break;
+			}
+			// someone else may have evicted it already
+			if fs::remove_file(&path).is_ok() {
+				total -= len;
+			}
+		}
+		Ok(total)
+	}
+
+	/// Writes the manifest if anything changed, merging in sources other launchers added since we loaded.
+	pub fn save(&mut self) -> io::Result<()> {
+		if !self.dirty {
+			return Ok(());
+		}
+		let on_disk = IconCache::load(self.dir.clone(), self.max_bytes);
+		let mut sources = on_disk.sources;
+		sources.extend(self.sources.clone());
+		// forget sources that were uninstalled, the renders themselves age out through eviction
+		sources.retain(|path, _| path.exists());
+
+		let file = ManifestFile {
+			version: CACHE_VERSION,
+			bytes: self.bytes,
+			sources: sources.into_iter().collect(),
+		};
+		let text =
+			toml::ser::to_string(&file).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+		fs::create_dir_all(&self.dir)?;
+		write_atomic(&self.dir.join("manifest.toml"), text)?;
+		self.dirty = false;
+		Ok(())
+	}
+}
+
+/// Writes a render made for a [`Lookup::Miss`].
+fn store(path: &Path, contents: &[u8]) -> io::Result<()> {
+	// another launcher may be rendering the same icon, whichever rename lands last wins
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	write_atomic(path, contents)
+}
+
+fn content_hash(data: &[u8]) -> String {
+	let mut hasher = FxHasher::default();
+	hasher.write_u32(CACHE_VERSION);
+	hasher.write(data);
+	format!("{:016x}", hasher.finish())
+}
+
+/// Marks a render as recently used, failing is harmless since it only affects eviction order.
+fn touch(path: &Path) {
+	let now = SystemTime::now();
+	let stale = fs::metadata(path)
+		.and_then(|m| m.modified())
+		.is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > TOUCH_INTERVAL);
+	if stale && let Ok(file) = fs::File::options().write(true).open(path) {
+		let _ = file.set_modified(now);
+	}
+}
+
+/// [`IconCache::get_or_create`] on the shared cache, only holding the lock to look up and record
+/// renders, so launchers loading icons on several threads can render in parallel.
+fn get_or_create(
+	source: &Path,
+	suffix: &str,
+	create: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+) -> io::Result<PathBuf> {
+	let lookup = ICON_CACHE.lock().unwrap().lookup(source, suffix)?;
+	let (path, data) = match lookup {
+		Lookup::Hit(path) => return Ok(path),
+		Lookup::Miss(path, data) => (path, data),
+	};
+	let contents = create(&data)?;
+	if let Err(e) = store(&path, &contents) {
+		// another thread or launcher rendering the same icon may have stored it first
+		if path.is_file() {
+			return Ok(path);
+		}
+		return Err(e);
+	}
+	ICON_CACHE
+		.lock()
+		.unwrap()
+		.record(&path, contents.len() as u64);
+	Ok(path)
+}
+
+/// Derives through the shared cache under `XDG_CACHE_HOME`.
+pub fn get_or_derive(
+	source: &Path,
+	kind: &str,
+	derive: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+) -> io::Result<PathBuf> {
+	get_or_create(source, kind, derive)
+}
+
+/// Renders through the shared cache under `XDG_CACHE_HOME`.
+pub fn get_or_render(
+	source: &Path,
+	size: u16,
+	render: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+) -> io::Result<PathBuf> {
+	get_or_create(source, &format!("{size}.png"), render)
+}
+
+/// Persists the shared cache's manifest, call this once icons have been loaded.
+pub fn save_icon_cache() -> io::Result<()> {
+	ICON_CACHE.lock().unwrap().save()
+}
+
+#[test]
+fn test_icon_cache_invalidation() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let cache_dir = dir.path().join("cache");
+	let source = dir.path().join("a/firefox.svg");
+	let other = dir.path().join("b/firefox.svg");
+	fs::create_dir_all(source.parent().unwrap()).unwrap();
+	fs::create_dir_all(other.parent().unwrap()).unwrap();
+	fs::write(&source, "one").unwrap();
+	fs::write(&other, "two").unwrap();
+
+	let mut cache = IconCache::load(cache_dir.clone(), u64::MAX);
+	let render = |data: &[u8]| Ok(data.to_vec());
+	let first = cache.get_or_render(&source, 32, render).unwrap();
+	assert_eq!(fs::read(&first).unwrap(), b"one");
+	// same name in another theme doesn't collide
+	let second = cache.get_or_render(&other, 32, render).unwrap();
+	assert_ne!(first, second);
+	// hits don't render again
+	let hit = cache
+		.get_or_render(&source, 32, |_| panic!("should be cached"))
+		.unwrap();
+	assert_eq!(hit, first);
+	cache.save().unwrap();
+
+	// the manifest is picked up by other processes, and a changed source gets rendered again
+	let mut cache = IconCache::load(cache_dir, u64::MAX);
+	assert_eq!(cache.sources.len(), 2);
+	fs::write(&source, "three").unwrap();
+	let changed = cache.get_or_render(&source, 32, render).unwrap();
+	assert_ne!(changed, first);
+	assert_eq!(fs::read(&changed).unwrap(), b"three");
+}
+
+#[test]
+fn test_icon_cache_eviction() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let mut cache = IconCache::load(dir.path().join("cache"), 250);
+	let render = |_: &[u8]| Ok(vec![0; 100]);
+
+	let mut renders = Vec::new();
+	for i in 0..3 {
+		let source = dir.path().join(format!("{i}.svg"));
+		fs::write(&source, i.to_string()).unwrap();
+		let png = cache.get_or_render(&source, 32, render).unwrap();
+		// oldest first, without relying on mtime resolution
+		let used = SystemTime::UNIX_EPOCH + Duration::from_secs(i + 1);
+		fs::File::options()
+			.write(true)
+			.open(&png)
+			.unwrap()
+			.set_modified(used)
+			.unwrap();
+		renders.push(png);
+	}
+	// the third render pushed the cache over 250 bytes, so the least recently used one went
+	assert!(!renders[0].exists());
+	assert!(renders[1].exists());
+	assert!(renders[2].exists());
+	// and the running total means the cache dir isn't scanned again until it could be over
+	assert_eq!(cache.bytes, Some(200));
+	cache.save().unwrap();
+	assert_eq!(
+		IconCache::load(dir.path().join("cache"), 250).bytes,
+		Some(200)
+	);
+}
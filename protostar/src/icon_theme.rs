@@ -0,0 +1,575 @@
+use crate::xdg::{get_data_dirs, get_icon_theme};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// In order of preference, the spec only has the first three but older apps ship the others as pixmaps.
+const ICON_EXTENSIONS: [&str; 8] = ["png", "svg", "xpm", "webp", "ico", "bmp", "glb", "gltf"];
+/// Every theme falls back to this one, whether it inherits from it or not.
+const FALLBACK_THEME: &str = "hicolor";
+
+lazy_static! {
+	static ref ICON_THEMES: IconThemes = IconThemes::new(get_icon_dirs(), get_pixmap_dirs());
+}
+
+/// Where icon themes are installed: `~/.icons`, then `icons` in each data dir.
+pub fn get_icon_dirs() -> Vec<PathBuf> {
+	dirs::home_dir()
+		.map(|home| home.join(".icons"))
+		.into_iter()
+		.chain(
+			dirs::data_dir()
+				.into_iter()
+				.chain(get_data_dirs())
+				.map(|dir| dir.join("icons")),
+		)
+		.filter(|dir| dir.is_dir())
+		.unique()
+		.collect()
+}
+
+/// Where icons that aren't part of any theme are installed, searched when no theme has an icon.
+pub fn get_pixmap_dirs() -> Vec<PathBuf> {
+	dirs::data_dir()
+		.into_iter()
+		.chain(get_data_dirs())
+		.map(|dir| dir.join("pixmaps"))
+		.filter(|dir| dir.is_dir())
+		.unique()
+		.collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectoryType {
+	/// Only for icons of exactly `size`.
+	Fixed,
+	/// For anything from `min_size` to `max_size`, usually SVGs.
+	Scalable,
+	/// For `size` give or take `threshold`.
+	Threshold,
+}
+
+/// A subdirectory of a theme, as described by its group in `index.theme`.
+#[derive(Debug)]
+pub struct IconDirectory {
+	/// Relative to the theme, like `48x48/apps`.
+	pub path: String,
+	pub size: u32,
+	pub scale: u32,
+	pub min_size: u32,
+	pub max_size: u32,
+	pub threshold: u32,
+	pub kind: DirectoryType,
+	pub context: Option<String>,
+	/// The files in each base dir's copy of this directory, read on the first lookup.
+	entries: Vec<(PathBuf, OnceLock<HashSet<String>>)>,
+}
+impl IconDirectory {
+	fn matches_size(&self, size: u32, scale: u32) -> bool {
+		if self.scale != scale {
+			return false;
+		}
+		match self.kind {
+			DirectoryType::Fixed => self.size == size,
+			DirectoryType::Scalable => (self.min_size..=self.max_size).contains(&size),
+			DirectoryType::Threshold => (self.size.saturating_sub(self.threshold)
+				..=self.size + self.threshold)
+				.contains(&size),
+		}
+	}
+
+	/// How far off in device pixels an icon from here would be, 0 if it fits.
+	fn size_distance(&self, size: u32, scale: u32) -> u32 {
+		let wanted = size * scale;
+		// the spec uses `MinSize` and `MaxSize` for threshold dirs, which are meant to be the threshold bounds
+		let (min, max) = match self.kind {
+			DirectoryType::Fixed => (self.size, self.size),
+			DirectoryType::Scalable => (self.min_size, self.max_size),
+			DirectoryType::Threshold => (
+				self.size.saturating_sub(self.threshold),
+				self.size + self.threshold,
+			),
+		};
+		if wanted < min * self.scale {
+			min * self.scale - wanted
+		} else {
+			wanted.saturating_sub(max * self.scale)
+		}
+	}
+
+	/// The first base dir that has the icon, trying the extensions in order.
+	fn find(&self, icon_name: &str) -> Option<PathBuf> {
+		self.entries.iter().find_map(|(dir, entries)| {
+			let entries = entries.get_or_init(|| read_file_names(dir));
+			ICON_EXTENSIONS
+				.iter()
+				.map(|ext| format!("{icon_name}.{ext}"))
+				.find(|file_name| entries.contains(file_name))
+				.map(|file_name| dir.join(file_name))
+		})
+	}
+}
+
+fn read_file_names(dir: &Path) -> HashSet<String> {
+	fs::read_dir(dir)
+		.into_iter()
+		.flatten()
+		.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+		.collect()
+}
+
+/// An icon file found in a theme, with the size it was drawn for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconFile {
+	pub path: PathBuf,
+	/// Logical size in pixels.
+	pub size: u32,
+	/// Physical pixels per logical pixel, like 2 for icons from `@2` directories.
+	pub scale: u32,
+	/// SVGs look sharp at any size.
+	pub scalable: bool,
+}
+impl IconFile {
+	pub fn pixel_size(&self) -> u32 {
+		self.size * self.scale
+	}
+}
+
+/// An icon theme parsed from its `index.theme`.
+#[derive(Debug)]
+pub struct IconTheme {
+	/// The directory name, like `Adwaita`, not the display name.
+	pub name: String,
+	pub inherits: Vec<String>,
+	pub directories: Vec<IconDirectory>,
+}
+impl IconTheme {
+	/// Parses the first `index.theme` found for the theme, searching its directories in all the base dirs.
+	pub fn load(name: &str, base_dirs: &[PathBuf]) -> Result<Self, String> {
+		let theme_dirs = base_dirs
+			.iter()
+			.map(|dir| dir.join(name))
+			.filter(|dir| dir.is_dir())
+			.collect::<Vec<_>>();
+		let index_path = theme_dirs
+			.iter()
+			.map(|dir| dir.join("index.theme"))
+			.find(|path| path.is_file())
+			.ok_or_else(|| format!("Icon theme {name} has no index.theme"))?;
+		let text = fs::read_to_string(&index_path)
+			.map_err(|e| format!("Failed to read {}: {e}", index_path.display()))?;
+		Ok(Self::parse(name, &text, &theme_dirs))
+	}
+
+	fn parse(name: &str, text: &str, theme_dirs: &[PathBuf]) -> Self {
+		let mut groups: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+		let mut group = None;
+		for line in text.lines().map(str::trim) {
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+				group = Some(name);
+				continue;
+			}
+			if let Some(group) = group
+				&& let Some((key, value)) = line.split_once('=')
+			{
+				groups
+					.entry(group)
+					.or_default()
+					.insert(key.trim(), value.trim());
+			}
+		}
+
+		let theme = groups.get("Icon Theme");
+		let list = |key: &str| {
+			theme
+				.and_then(|t| t.get(key))
+				.map(|value| {
+					value
+						.split(',')
+						.map(str::trim)
+						.filter(|v| !v.is_empty())
+						.map(str::to_string)
+						.collect::<Vec<_>>()
+				})
+				.unwrap_or_default()
+		};
+		let directories = list("Directories")
+			.into_iter()
+			.chain(list("ScaledDirectories"))
+			.unique()
+			.filter_map(|path| {
+				let values = groups.get(path.as_str())?;
+				let number = |key: &str| values.get(key).and_then(|v| v.parse::<u32>().ok());
+				let size = number("Size")?;
+				let kind = match values.get("Type").copied() {
+					Some("Fixed") => DirectoryType::Fixed,
+					Some("Scalable") => DirectoryType::Scalable,
+					_ => DirectoryType::Threshold,
+				};
+				Some(IconDirectory {
+					entries: theme_dirs
+						.iter()
+						.map(|dir| (dir.join(&path), OnceLock::new()))
+						.collect(),
+					size,
+					scale: number("Scale").unwrap_or(1).max(1),
+					min_size: number("MinSize").unwrap_or(size),
+					max_size: number("MaxSize").unwrap_or(size),
+					threshold: number("Threshold").unwrap_or(2),
+					kind,
+					context: values.get("Context").map(|c| c.to_string()),
+					path,
+				})
+			})
+			.collect();
+
+		IconTheme {
+			name: name.to_string(),
+			inherits: list("Inherits"),
+			directories,
+		}
+	}
+
+	/// An icon of exactly the right size if there is one, else the closest one in this theme.
+	pub fn lookup(&self, icon_name: &str, size: u32, scale: u32) -> Option<PathBuf> {
+		if let Some(path) = self
+			.directories
+			.iter()
+			.filter(|dir| dir.matches_size(size, scale))
+			.find_map(|dir| dir.find(icon_name))
+		{
+			return Some(path);
+		}
+
+		let mut closest = None;
+		let mut min_distance = u32::MAX;
+		for dir in &self.directories {
+			let distance = dir.size_distance(size, scale);
+			if distance < min_distance
+				&& let Some(path) = dir.find(icon_name)
+			{
+				closest = Some(path);
+				min_distance = distance;
+			}
+		}
+		closest
+	}
+
+	/// The sharpest icon for `pixels` physical pixels at any scale: the smallest raster that's at least that large,
+	/// else an SVG, else the largest raster there is.
+	pub fn best_for_pixels(&self, icon_name: &str, pixels: u32) -> Option<IconFile> {
+		let candidates = self
+			.directories
+			.iter()
+			.filter_map(|dir| {
+				let path = dir.find(icon_name)?;
+				Some(IconFile {
+					scalable: path.extension().is_some_and(|ext| ext == "svg"),
+					path,
+					size: dir.size,
+					scale: dir.scale,
+				})
+			})
+			.collect::<Vec<_>>();
+		let rasters = || candidates.iter().filter(|c| !c.scalable);
+		// between equally sharp ones, the one drawn for the larger logical size has more detail
+		rasters()
+			.filter(|c| c.pixel_size() >= pixels)
+			.min_by_key(|c| (c.pixel_size(), c.scale))
+			.or_else(|| candidates.iter().find(|c| c.scalable))
+			.or_else(|| rasters().max_by_key(|c| (c.pixel_size(), std::cmp::Reverse(c.scale))))
+			.cloned()
+	}
+}
+
+/// Loads icon themes on demand and looks up icons in them the way the icon theme spec describes.
+pub struct IconThemes {
+	base_dirs: Vec<PathBuf>,
+	pixmap_dirs: Vec<PathBuf>,
+	/// `None` for themes that aren't installed, so they're only searched for once.
+	themes: Mutex<HashMap<String, Option<Arc<IconTheme>>>>,
+}
+impl IconThemes {
+	pub fn new(base_dirs: Vec<PathBuf>, pixmap_dirs: Vec<PathBuf>) -> Self {
+		IconThemes {
+			base_dirs,
+			pixmap_dirs,
+			themes: Mutex::new(HashMap::new()),
+		}
+	}
+
+	pub fn theme(&self, name: &str) -> Option<Arc<IconTheme>> {
+		self.themes
+			.lock()
+			.unwrap()
+			.entry(name.to_string())
+			.or_insert_with(|| match IconTheme::load(name, &self.base_dirs) {
+				Ok(theme) => Some(Arc::new(theme)),
+				Err(e) => {
+					tracing::debug!("{e}");
+					None
+				}
+			})
+			.clone()
+	}
+
+	/// The theme followed by everything it inherits from, depth first, with `hicolor` last.
+	pub fn theme_chain(&self, name: &str) -> Vec<Arc<IconTheme>> {
+		let mut chain: Vec<Arc<IconTheme>> = Vec::new();
+		let mut stack = vec![name.to_string()];
+		while let Some(name) = stack.pop() {
+			if name == FALLBACK_THEME || chain.iter().any(|t| t.name == name) {
+				continue;
+			}
+			let Some(theme) = self.theme(&name) else {
+				continue;
+			};
+			stack.extend(theme.inherits.iter().rev().cloned());
+			chain.push(theme);
+		}
+		chain.extend(self.theme(FALLBACK_THEME));
+		chain
+	}
+
+	/// Finds an icon by name for `size` logical pixels at `scale`,
+	/// looking through `theme`, the themes it inherits from, `hicolor` and finally the pixmap dirs.
+	pub fn find_icon(
+		&self,
+		icon_name: &str,
+		size: u32,
+		scale: u32,
+		theme: &str,
+	) -> Option<PathBuf> {
+		let icon_name = strip_icon_extension(icon_name);
+		self.theme_chain(theme)
+			.iter()
+			.find_map(|theme| theme.lookup(icon_name, size, scale))
+			.or_else(|| self.find_pixmap(icon_name))
+	}
+
+	/// Like [`IconThemes::find_icon`], but picks the sharpest file for `pixels` physical pixels,
+	/// see [`IconTheme::best_for_pixels`].
+	///
+	/// Full color icons win over their `-symbolic` variant even if the symbolic one was asked for,
+	/// and the symbolic variant is used when that's all there is, see [`crate::symbolic`].
+	pub fn find_icon_for_pixels(
+		&self,
+		icon_name: &str,
+		pixels: u32,
+		theme: &str,
+	) -> Option<IconFile> {
+		let icon_name = strip_icon_extension(icon_name);
+		match icon_name.strip_suffix("-symbolic") {
+			Some(full_color) => self
+				.find_exact_for_pixels(full_color, pixels, theme)
+				.or_else(|| self.find_exact_for_pixels(icon_name, pixels, theme)),
+			None => self
+				.find_exact_for_pixels(icon_name, pixels, theme)
+				.or_else(|| {
+					self.find_exact_for_pixels(&format!("{icon_name}-symbolic"), pixels, theme)
+				}),
+		}
+	}
+
+	fn find_exact_for_pixels(&self, icon_name: &str, pixels: u32, theme: &str) -> Option<IconFile> {
+		self.theme_chain(theme)
+			.iter()
+			.find_map(|theme| theme.best_for_pixels(icon_name, pixels))
+			.or_else(|| {
+				let path = self.find_pixmap(icon_name)?;
+				let scalable = path.extension().is_some_and(|ext| ext == "svg");
+				// pixmaps don't say how large they are, so look
+				let size = if scalable {
+					pixels
+				} else {
+					image::image_dimensions(&path).map_or(pixels, |(width, _)| width)
+				};
+				Some(IconFile {
+					path,
+					size,
+					scale: 1,
+					scalable,
+				})
+			})
+	}
+
+	fn find_pixmap(&self, icon_name: &str) -> Option<PathBuf> {
+		self.pixmap_dirs.iter().find_map(|dir| {
+			ICON_EXTENSIONS
+				.iter()
+				.map(|ext| dir.join(format!("{icon_name}.{ext}")))
+				.find(|path| path.is_file())
+		})
+	}
+}
+
+/// Some desktop files have `Icon=name.png`, which the spec doesn't allow but everyone supports.
+fn strip_icon_extension(icon_name: &str) -> &str {
+	Path::new(icon_name)
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.filter(|ext| ICON_EXTENSIONS.contains(ext))
+		.and_then(|ext| icon_name.strip_suffix(&format!(".{ext}")))
+		.unwrap_or(icon_name)
+}
+
+/// Finds an icon in the configured theme, see [`IconThemes::find_icon`].
+pub fn find_icon(icon_name: &str, size: u32, scale: u32) -> Option<PathBuf> {
+	ICON_THEMES.find_icon(icon_name, size, scale, &get_icon_theme())
+}
+
+/// Finds the sharpest icon in the configured theme, see [`IconThemes::find_icon_for_pixels`].
+pub fn find_icon_for_pixels(icon_name: &str, pixels: u32) -> Option<IconFile> {
+	ICON_THEMES.find_icon_for_pixels(icon_name, pixels, &get_icon_theme())
+}
+
+#[test]
+fn test_icon_theme_lookup() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let user_icons = dir.path().join("user");
+	let system_icons = dir.path().join("system");
+	let pixmaps = dir.path().join("pixmaps");
+	let write = |path: PathBuf, data: &str| {
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, data).unwrap();
+	};
+	write(
+		system_icons.join("Base/index.theme"),
+		"[Icon Theme]\nName=Base\nInherits=Loop\nDirectories=16x16/apps,32x32/apps,scalable/apps\nScaledDirectories=16x16@2/apps\n\n[16x16/apps]\nSize=16\nType=Fixed\n\n[16x16@2/apps]\nSize=16\nScale=2\nType=Fixed\n\n[32x32/apps]\nSize=32\n\n[scalable/apps]\nSize=64\nMinSize=8\nMaxSize=512\nType=Scalable\n",
+	);
+	// loops back to Base, which mustn't hang
+	write(
+		system_icons.join("Loop/index.theme"),
+		"[Icon Theme]\nName=Loop\nInherits=Base\nDirectories=48x48/apps\n\n[48x48/apps]\nSize=48\nType=Fixed\n",
+	);
+	write(
+		system_icons.join("hicolor/index.theme"),
+		"[Icon Theme]\nName=Hicolor\nDirectories=48x48/apps\n\n[48x48/apps]\nSize=48\nType=Fixed\n",
+	);
+	// the user's copy of the theme has no index.theme, but its icons count
+	write(user_icons.join("Base/16x16/apps/user.png"), "");
+	write(system_icons.join("Base/16x16/apps/small.png"), "");
+	write(system_icons.join("Base/16x16@2/apps/small.png"), "");
+	write(system_icons.join("Base/32x32/apps/small.png"), "");
+	write(system_icons.join("Base/scalable/apps/vector.svg"), "");
+	write(system_icons.join("Base/16x16/apps/hidpi.png"), "");
+	write(system_icons.join("Base/16x16@2/apps/hidpi.png"), "");
+	write(system_icons.join("Base/32x32/apps/vector.png"), "");
+	write(system_icons.join("Loop/48x48/apps/inherited.png"), "");
+	write(system_icons.join("hicolor/48x48/apps/fallback.png"), "");
+	write(pixmaps.join("legacy.png"), "");
+	write(system_icons.join("Base/scalable/apps/both.svg"), "");
+	write(
+		system_icons.join("Base/scalable/apps/both-symbolic.svg"),
+		"",
+	);
+	write(
+		system_icons.join("Base/scalable/apps/only-symbolic.svg"),
+		"",
+	);
+
+	let themes = IconThemes::new(
+		vec![user_icons.clone(), system_icons.clone()],
+		vec![pixmaps.clone()],
+	);
+	let find = |name: &str, size: u32, scale: u32| {
+		themes
+			.find_icon(name, size, scale, "Base")
+			.map(|p| p.strip_prefix(dir.path()).unwrap().to_path_buf())
+	};
+	let path = |p: &str| Some(PathBuf::from(p));
+
+	assert_eq!(
+		themes
+			.theme_chain("Base")
+			.iter()
+			.map(|t| t.name.as_str())
+			.collect::<Vec<_>>(),
+		["Base", "Loop", "hicolor"]
+	);
+	assert_eq!(find("user", 16, 1), path("user/Base/16x16/apps/user.png"));
+	// exact matches, with 30 in the threshold of the 32 dir
+	assert_eq!(
+		find("small", 16, 1),
+		path("system/Base/16x16/apps/small.png")
+	);
+	assert_eq!(
+		find("small", 16, 2),
+		path("system/Base/16x16@2/apps/small.png")
+	);
+	assert_eq!(
+		find("small", 30, 1),
+		path("system/Base/32x32/apps/small.png")
+	);
+	// 24 is closer to 32 than to 16
+	assert_eq!(
+		find("small", 24, 1),
+		path("system/Base/32x32/apps/small.png")
+	);
+	// both dirs fit 32 and the first listed wins, only the scalable one fits 100
+	assert_eq!(
+		find("vector", 32, 1),
+		path("system/Base/32x32/apps/vector.png")
+	);
+	assert_eq!(
+		find("vector", 100, 1),
+		path("system/Base/scalable/apps/vector.svg")
+	);
+	assert_eq!(
+		find("vector.svg", 100, 1),
+		path("system/Base/scalable/apps/vector.svg")
+	);
+	assert_eq!(
+		find("inherited", 16, 1),
+		path("system/Loop/48x48/apps/inherited.png")
+	);
+	assert_eq!(
+		find("fallback", 16, 1),
+		path("system/hicolor/48x48/apps/fallback.png")
+	);
+	assert_eq!(find("legacy", 16, 1), path("pixmaps/legacy.png"));
+	assert_eq!(find("missing", 16, 1), None);
+
+	let find_for_pixels = |name: &str, pixels: u32| {
+		let file = themes.find_icon_for_pixels(name, pixels, "Base").unwrap();
+		let path = file.path.strip_prefix(dir.path()).unwrap().to_path_buf();
+		(path, file.pixel_size(), file.scalable)
+	};
+	let raster = |p: &str, pixels: u32| (PathBuf::from(p), pixels, false);
+	// the 16 pixel icon at scale 2 is sharper than scaling up the plain one
+	assert_eq!(
+		find_for_pixels("hidpi", 32),
+		raster("system/Base/16x16@2/apps/hidpi.png", 32)
+	);
+	assert_eq!(
+		find_for_pixels("hidpi", 8),
+		raster("system/Base/16x16/apps/hidpi.png", 16)
+	);
+	assert_eq!(
+		find_for_pixels("vector", 20),
+		raster("system/Base/32x32/apps/vector.png", 32)
+	);
+	assert!(find_for_pixels("vector", 256).2);
+	// nothing is large enough and there's no SVG, so the largest will have to do
+	assert_eq!(
+		find_for_pixels("small", 256),
+		raster("system/Base/32x32/apps/small.png", 32)
+	);
+	// full color beats symbolic, which is only used when it's all there is
+	let vector = |p: &str| (PathBuf::from(p), 64, true);
+	assert_eq!(
+		find_for_pixels("both-symbolic", 64),
+		vector("system/Base/scalable/apps/both.svg")
+	);
+	assert_eq!(
+		find_for_pixels("only", 64),
+		vector("system/Base/scalable/apps/only-symbolic.svg")
+	);
+	assert!(themes.theme("Missing").is_none());
+}
@@ -0,0 +1,405 @@
+use crate::icon_theme::{get_icon_dirs, get_pixmap_dirs};
+use crate::xdg::{
+	DesktopAction, DesktopFile, Icon, get_cache_home, get_desktop_files, get_icon_theme,
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+
+/// Bump whenever the layout of [`IndexEntry`] or the parsing rules change,
+/// so stale indexes get thrown away instead of misread.
+const INDEX_VERSION: u32 = 5;
+
+lazy_static! {
+	static ref APP_INDEX: Mutex<AppIndex> = Mutex::new(AppIndex::load(get_app_index_path()));
+}
+
+pub fn get_app_index_path() -> PathBuf {
+	get_cache_home().join("protostar_app_index.toml")
+}
+
+/// Size and modification time of a file, used to tell if a cached entry is still valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FileStamp {
+	mtime: u64,
+	size: u64,
+}
+impl FileStamp {
+	pub(crate) fn read(path: &Path) -> Option<Self> {
+		let metadata = fs::metadata(path).ok()?;
+		let mtime = metadata
+			.modified()
+			.ok()?
+			.duration_since(UNIX_EPOCH)
+			.ok()?
+			.as_nanos() as u64;
+		Some(FileStamp {
+			mtime,
+			size: metadata.len(),
+		})
+	}
+}
+
+/// Writes `path` through a temporary file renamed over it, so other processes never read half of it.
+/// Each call gets its own temporary file, so threads writing the same path don't clobber each other.
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+	static WRITES: AtomicU64 = AtomicU64::new(0);
+	let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+	tmp_name.push(format!(
+		".{}.{}.tmp",
+		std::process::id(),
+		WRITES.fetch_add(1, Ordering::Relaxed)
+	));
+	let tmp_path = path.with_file_name(tmp_name);
+	let result = fs::write(&tmp_path, contents).and_then(|_| fs::rename(&tmp_path, path));
+	if result.is_err() {
+		let _ = fs::remove_file(&tmp_path);
+	}
+	result
+}
+
+/// The newest modification time of the icon and pixmap dirs and of the themes in them,
+/// which changes when an icon theme is installed or updated.
+fn icon_dirs_mtime() -> u64 {
+	let icon_dirs = get_icon_dirs();
+	let themes = icon_dirs
+		.iter()
+		.filter_map(|dir| fs::read_dir(dir).ok())
+		.flatten()
+		.filter_map(|entry| Some(entry.ok()?.path()));
+	icon_dirs
+		.iter()
+		.cloned()
+		.chain(themes)
+		.chain(get_pixmap_dirs())
+		.filter_map(|dir| FileStamp::read(&dir))
+		.map(|stamp| stamp.mtime)
+		.max()
+		.unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedIcon {
+	size: u16,
+	/// `None` records that the lookup was done and found nothing.
+	path: Option<PathBuf>,
+	/// The size and scale of the icon that was found, which can differ from the requested size.
+	icon_size: u16,
+	scale: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+	path: PathBuf,
+	stamp: FileStamp,
+	name: Option<String>,
+	generic_name: Option<String>,
+	command: Option<String>,
+	categories: Vec<String>,
+	keywords: Vec<String>,
+	mime_types: Vec<String>,
+	icon: Option<String>,
+	actions: Vec<DesktopAction>,
+	no_display: bool,
+	icons: Vec<CachedIcon>,
+}
+impl IndexEntry {
+	fn new(desktop_file: &DesktopFile, stamp: FileStamp) -> Self {
+		IndexEntry {
+			path: desktop_file.path.clone(),
+			stamp,
+			name: desktop_file.name.clone(),
+			generic_name: desktop_file.generic_name.clone(),
+			command: desktop_file.command.clone(),
+			categories: desktop_file.categories.clone(),
+			keywords: desktop_file.keywords.clone(),
+			mime_types: desktop_file.mime_types.clone(),
+			icon: desktop_file.icon.clone(),
+			actions: desktop_file.actions.clone(),
+			no_display: desktop_file.no_display,
+			icons: Vec::new(),
+		}
+	}
+
+	fn desktop_file(&self) -> DesktopFile {
+		DesktopFile {
+			path: self.path.clone(),
+			name: self.name.clone(),
+			generic_name: self.generic_name.clone(),
+			command: self.command.clone(),
+			categories: self.categories.clone(),
+			keywords: self.keywords.clone(),
+			mime_types: self.mime_types.clone(),
+			icon: self.icon.clone(),
+			actions: self.actions.clone(),
+			no_display: self.no_display,
+		}
+	}
+
+	fn cached_icon(&self, size: u16) -> Option<Option<Icon>> {
+		let cached = self.icons.iter().find(|i| i.size == size)?;
+		match &cached.path {
+			// the icon theme may have been changed or uninstalled since
+			Some(path) if !path.exists() => None,
+			Some(path) => Some(
+				Icon::from_path(path.clone(), cached.icon_size).map(|icon| Icon {
+					scale: cached.scale,
+					..icon
+				}),
+			),
+			None => Some(None),
+		}
+	}
+
+	fn record_icon(&mut self, size: u16, icon: Option<&Icon>) {
+		self.icons.retain(|i| i.size != size);
+		self.icons.push(CachedIcon {
+			size,
+			path: icon.map(|i| i.path.clone()),
+			icon_size: icon.map_or(size, |i| i.size),
+			scale: icon.map_or(1, |i| i.scale),
+		});
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+	version: u32,
+	icon_theme: String,
+	/// See [`icon_dirs_mtime`], icons that weren't found are only trusted while it stays the same.
+	icon_dirs_mtime: u64,
+	entries: Vec<IndexEntry>,
+}
+
+/// On-disk index of parsed desktop files and their resolved icons,
+/// so startup only has to re-parse what changed since the last run.
+#[derive(Debug)]
+pub struct AppIndex {
+	path: PathBuf,
+	icon_theme: String,
+	icon_dirs_mtime: u64,
+	entries: HashMap<PathBuf, IndexEntry>,
+}
+impl AppIndex {
+	/// Loads the index at `path`, starting empty if it is missing, corrupt or from another version.
+	pub fn load(path: PathBuf) -> Self {
+		AppIndex::load_with_icon_dirs_mtime(path, icon_dirs_mtime())
+	}
+
+	fn load_with_icon_dirs_mtime(path: PathBuf, icon_dirs_mtime: u64) -> Self {
+		let icon_theme = get_icon_theme();
+		let mut index = AppIndex {
+			path,
+			icon_theme,
+			icon_dirs_mtime,
+			entries: HashMap::new(),
+		};
+
+		if let Ok(text) = fs::read_to_string(&index.path)
+			&& let Ok(file) = toml::de::from_str::<IndexFile>(&text)
+			&& file.version == INDEX_VERSION
+		{
+			let same_theme = file.icon_theme == index.icon_theme;
+			// an icon that wasn't there before may have been installed since
+			let same_icon_dirs = file.icon_dirs_mtime == index.icon_dirs_mtime;
+			index.entries = file
+				.entries
+				.into_iter()
+				.map(|mut entry| {
+					if !same_theme {
+						entry.icons.clear();
+					} else if !same_icon_dirs {
+						entry.icons.retain(|icon| icon.path.is_some());
+					}
+					(entry.path.clone(), entry)
+				})
+				.collect();
+		}
+		index
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Gets the desktop file at `path`, only parsing it if it changed since it was indexed.
+	pub fn desktop_file(&mut self, path: &Path) -> Option<DesktopFile> {
+		let stamp = FileStamp::read(path)?;
+		if let Some(entry) = self.entries.get(path)
+			&& entry.stamp == stamp
+		{
+			return Some(entry.desktop_file());
+		}
+
+		let desktop_file = DesktopFile::parse(path.to_path_buf()).ok()?;
+		self.entries
+			.insert(path.to_path_buf(), IndexEntry::new(&desktop_file, stamp));
+		Some(desktop_file)
+	}
+
+	/// Finds all desktop files in the data dirs, dropping entries for files that no longer exist.
+	pub fn discover(&mut self) -> Vec<DesktopFile> {
+		let paths = get_desktop_files().collect::<Vec<_>>();
+		let existing = paths.iter().collect::<HashSet<_>>();
+		self.entries.retain(|path, _| existing.contains(path));
+		paths
+			.iter()
+			.filter_map(|path| self.desktop_file(path))
+			.collect()
+	}
+
+	/// Resolves the icon for an indexed desktop file, reusing the last lookup if there was one.
+	pub fn icon(&mut self, desktop_file: &DesktopFile, size: u16) -> Option<Icon> {
+		if let Some(cached) = self.cached_icon(&desktop_file.path, size) {
+			return cached;
+		}
+		let icon = desktop_file.lookup_icon(size);
+		self.record_icon(&desktop_file.path, size, icon.as_ref());
+		icon
+	}
+
+	fn cached_icon(&self, path: &Path, size: u16) -> Option<Option<Icon>> {
+		self.entries.get(path)?.cached_icon(size)
+	}
+
+	fn record_icon(&mut self, path: &Path, size: u16, icon: Option<&Icon>) {
+		if let Some(entry) = self.entries.get_mut(path) {
+			entry.record_icon(size, icon);
+		}
+	}
+
+	/// Writes the index to disk, replacing the old one atomically.
+	pub fn save(&self) -> io::Result<()> {
+		let file = IndexFile {
+			version: INDEX_VERSION,
+			icon_theme: self.icon_theme.clone(),
+			icon_dirs_mtime: self.icon_dirs_mtime,
+			entries: self.entries.values().cloned().collect(),
+		};
+		let text = toml::ser::to_string(&file)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		write_atomic(&self.path, text)
+	}
+}
+
+/// Finds all desktop files using the shared index under `XDG_CACHE_HOME`.
+pub fn get_indexed_desktop_files() -> Vec<DesktopFile> {
+	APP_INDEX.lock().unwrap().discover()
+}
+
+/// Persists the shared index, call this once icons have been loaded.
+pub fn save_app_index() -> io::Result<()> {
+	APP_INDEX.lock().unwrap().save()
+}
+
+pub(crate) fn cached_icon(path: &Path, size: u16) -> Option<Option<Icon>> {
+	APP_INDEX.lock().unwrap().cached_icon(path, size)
+}
+
+pub(crate) fn record_icon(path: &Path, size: u16, icon: Option<&Icon>) {
+	APP_INDEX.lock().unwrap().record_icon(path, size, icon);
+}
+
+#[test]
+fn test_index_reparses_changed_files() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let file = dir.path().join("test.desktop");
+	fs::write(&file, "[Desktop Entry]\nName=Test\nExec=test").unwrap();
+
+	let index_path = dir.path().join("index.toml");
+	let mut index = AppIndex::load(index_path.clone());
+	let desktop_file = index.desktop_file(&file).unwrap();
+	assert_eq!(desktop_file.name, Some("Test".to_string()));
+	index.save().unwrap();
+
+	let mut index = AppIndex::load(index_path.clone());
+	assert_eq!(index.len(), 1);
+	assert_eq!(
+		index.desktop_file(&file).unwrap().name,
+		Some("Test".to_string())
+	);
+
+	// different size means different stamp, even within the same mtime tick
+	fs::write(&file, "[Desktop Entry]\nName=Changed\nExec=test").unwrap();
+	assert_eq!(
+		index.desktop_file(&file).unwrap().name,
+		Some("Changed".to_string())
+	);
+}
+
+#[test]
+fn test_index_corrupt_or_old_version() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let index_path = dir.path().join("index.toml");
+
+	fs::write(&index_path, "this is not an index").unwrap();
+	assert!(AppIndex::load(index_path.clone()).is_empty());
+
+	let file = dir.path().join("test.desktop");
+	fs::write(&file, "[Desktop Entry]\nName=Test\nExec=test").unwrap();
+	let mut index = AppIndex::load(index_path.clone());
+	index.desktop_file(&file).unwrap();
+	index.save().unwrap();
+	assert_eq!(AppIndex::load(index_path.clone()).len(), 1);
+
+	let text = fs::read_to_string(&index_path).unwrap().replace(
+		&format!("version = {INDEX_VERSION}"),
+		&format!("version = {}", INDEX_VERSION + 1),
+	);
+	fs::write(&index_path, text).unwrap();
+	assert!(AppIndex::load(index_path).is_empty());
+}
+
+#[test]
+fn test_index_retries_missing_icons() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let file = dir.path().join("test.desktop");
+	fs::write(&file, "[Desktop Entry]\nName=Test\nExec=test\nIcon=missing").unwrap();
+	let index_path = dir.path().join("index.toml");
+
+	let mut index = AppIndex::load_with_icon_dirs_mtime(index_path.clone(), 1);
+	index.desktop_file(&file).unwrap();
+	index.record_icon(&file, 48, None);
+	index.save().unwrap();
+
+	let index = AppIndex::load_with_icon_dirs_mtime(index_path.clone(), 1);
+	assert!(matches!(index.cached_icon(&file, 48), Some(None)));
+	index.save().unwrap();
+
+	// installing an icon theme touches the icon dirs, so the lookup gets done again
+	let index = AppIndex::load_with_icon_dirs_mtime(index_path, 2);
+	assert!(index.cached_icon(&file, 48).is_none());
+}
+
+#[test]
+fn test_write_atomic_concurrent() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let path = dir.path().join("icon.png");
+	std::thread::scope(|scope| {
+		for i in 0..8u8 {
+			let path = &path;
+			scope.spawn(move || {
+				for _ in 0..32 {
+					write_atomic(path, [i; 4096]).unwrap();
+				}
+			});
+		}
+	});
+	let contents = fs::read(&path).unwrap();
+	assert_eq!(contents.len(), 4096);
+	assert!(contents.iter().all(|b| *b == contents[0]));
+	// no temporary files are left behind
+	assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
@@ -1,2 +1,26 @@
 pub mod application;
+pub mod atlas;
+pub mod category;
+pub mod collate;
+pub mod config;
+pub mod extrude;
+pub mod fallback_icon;
+pub mod favorites;
+pub mod filter;
+pub mod gltf;
+pub mod icon_cache;
+pub mod icon_theme;
+pub mod index;
+pub mod menu;
+pub mod mime;
+pub mod normalize;
+pub mod palette;
+pub mod query;
+pub mod recent;
+pub mod registry;
+pub mod search;
+pub mod symbolic;
+pub mod usage;
+pub mod watch;
 pub mod xdg;
+pub mod xpm;
@@ -0,0 +1,738 @@
+//! The freedesktop menu spec, which distributions use to arrange apps into submenus
+//! through `menus/applications.menu` and `desktop-directories/*.directory` files.
+//!
+//! `<Move>`, `<LegacyDir>`, `<KDELegacyDirs>` and the `inline` layout attributes are not supported.
+
+use crate::application::Application;
+use crate::collate;
+use crate::xdg::{
+	DesktopFile, Icon, find_themed_icon, get_config_dirs, get_data_dirs, locale_variants,
+};
+use itertools::Itertools;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A menu after evaluating all its rules, ready to show.
+#[derive(Debug, Clone)]
+pub struct Menu {
+	pub name: String,
+	pub directory: Option<MenuDirectory>,
+	pub entries: Vec<MenuEntry>,
+}
+impl Menu {
+	/// The localized name from the `.directory` file, or the menu's `<Name>`.
+	pub fn display_name(&self) -> &str {
+		self.directory
+			.as_ref()
+			.and_then(|d| d.name.as_deref())
+			.unwrap_or(&self.name)
+	}
+
+	pub fn icon(&self, preferred_px_size: u16) -> Option<Icon> {
+		self.directory.as_ref()?.icon(preferred_px_size)
+	}
+
+	pub fn apps(&self) -> impl Iterator<Item = &Application> {
+		self.entries.iter().filter_map(|e| match e {
+			MenuEntry::Application(app) => Some(app),
+			_ => None,
+		})
+	}
+
+	pub fn submenus(&self) -> impl Iterator<Item = &Menu> {
+		self.entries.iter().filter_map(|e| match e {
+			MenuEntry::Menu(menu) => Some(menu),
+			_ => None,
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub enum MenuEntry {
+	Application(Application),
+	Menu(Menu),
+	Separator,
+}
+
+/// The contents of a `.directory` file, with name and comment in the user's locale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuDirectory {
+	pub path: PathBuf,
+	pub name: Option<String>,
+	pub comment: Option<String>,
+	pub icon: Option<String>,
+	pub no_display: bool,
+}
+impl MenuDirectory {
+	pub fn parse(path: PathBuf) -> Result<Self, String> {
+		Self::parse_localized(path, &locale_variants())
+	}
+
+	fn parse_localized(path: PathBuf, locales: &[String]) -> Result<Self, String> {
+		let text = fs::read_to_string(&path).map_err(|e| format!("Failed to open file: {e}"))?;
+
+		let mut values = HashMap::new();
+		let mut in_desktop_entry = false;
+		for line in text.lines() {
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if line.starts_with('[') {
+				in_desktop_entry = line == "[Desktop Entry]";
+				continue;
+			}
+			if in_desktop_entry && let Some((key, value)) = line.split_once('=') {
+				values.insert(key.trim(), value.trim());
+			}
+		}
+		let localized = |key: &str| {
+			locales
+				.iter()
+				.find_map(|locale| values.get(format!("{key}[{locale}]").as_str()))
+				.or_else(|| values.get(key))
+				.map(|v| v.to_string())
+		};
+
+		Ok(MenuDirectory {
+			name: localized("Name"),
+			comment: localized("Comment"),
+			icon: values.get("Icon").map(|v| v.to_string()),
+			no_display: values.get("NoDisplay") == Some(&"true"),
+			path,
+		})
+	}
+
+	pub fn icon(&self, preferred_px_size: u16) -> Option<Icon> {
+		let icon = self.icon.as_deref()?;
+		if Path::new(icon).is_absolute() {
+			return Icon::from_path(PathBuf::from(icon), preferred_px_size);
+		}
+		find_themed_icon(icon, preferred_px_size)
+	}
+}
+
+/// Finds `${XDG_MENU_PREFIX}applications.menu` in the XDG config dirs and evaluates it.
+pub fn load_applications_menu() -> Result<Menu, String> {
+	let prefix = std::env::var("XDG_MENU_PREFIX").unwrap_or_default();
+	let file_name = format!("{prefix}applications.menu");
+	let path = get_config_dirs()
+		.into_iter()
+		.map(|dir| dir.join("menus").join(&file_name))
+		.find(|path| path.is_file())
+		.ok_or_else(|| format!("Could not find {file_name} in any config dir"))?;
+	load_menu(path)
+}
+
+/// Parses and evaluates a `.menu` file, including everything it merges in.
+pub fn load_menu(path: impl AsRef<Path>) -> Result<Menu, String> {
+	let mut loader = Loader {
+		config_dirs: get_config_dirs(),
+		loading: HashSet::new(),
+	};
+	let mut root = loader.load_file(path.as_ref())?;
+	root.consolidate();
+
+	let mut evaluator = Evaluator::default();
+	let resolved = evaluator.resolve(&root, &[], &[], &Layout::default());
+	let allocated = resolved.allocated();
+	Ok(resolved.build(&allocated).unwrap_or_else(|| Menu {
+		name: root.name.clone(),
+		directory: None,
+		entries: Vec::new(),
+	}))
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+	Filename(String),
+	Category(String),
+	All,
+	And(Vec<Rule>),
+	Or(Vec<Rule>),
+	Not(Vec<Rule>),
+}
+impl Rule {
+	fn parse(node: Node) -> Option<Self> {
+		let children = || node.children().filter_map(Rule::parse).collect();
+		Some(match node.tag_name().name() {
+			"Filename" => Rule::Filename(node.text()?.trim().to_string()),
+			"Category" => Rule::Category(node.text()?.trim().to_string()),
+			"All" => Rule::All,
+			"And" => Rule::And(children()),
+			"Or" => Rule::Or(children()),
+			// the children of `<Not>` are implicitly or'ed
+			"Not" => Rule::Not(children()),
+			_ => return None,
+		})
+	}
+
+	fn matches(&self, id: &str, desktop_file: &DesktopFile) -> bool {
+		match self {
+			Rule::Filename(filename) => filename == id,
+			Rule::Category(category) => desktop_file.categories.contains(category),
+			Rule::All => true,
+			Rule::And(rules) => rules.iter().all(|r| r.matches(id, desktop_file)),
+			Rule::Or(rules) => rules.iter().any(|r| r.matches(id, desktop_file)),
+			Rule::Not(rules) => !rules.iter().any(|r| r.matches(id, desktop_file)),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+	Include(Rule),
+	Exclude(Rule),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MergeType {
+	Menus,
+	Files,
+	All,
+}
+
+#[derive(Debug, Clone)]
+enum LayoutItem {
+	Filename(String),
+	Menuname(String),
+	Separator,
+	Merge(MergeType),
+}
+
+#[derive(Debug, Clone)]
+struct Layout {
+	show_empty: bool,
+	items: Vec<LayoutItem>,
+}
+impl Default for Layout {
+	fn default() -> Self {
+		Layout {
+			show_empty: false,
+			items: vec![
+				LayoutItem::Merge(MergeType::Menus),
+				LayoutItem::Merge(MergeType::Files),
+			],
+		}
+	}
+}
+impl Layout {
+	fn parse(node: Node) -> Self {
+		let items = node
+			.children()
+			.filter_map(|child| {
+				Some(match child.tag_name().name() {
+					"Filename" => LayoutItem::Filename(child.text()?.trim().to_string()),
+					"Menuname" => LayoutItem::Menuname(child.text()?.trim().to_string()),
+					"Separator" => LayoutItem::Separator,
+					"Merge" => LayoutItem::Merge(match child.attribute("type")? {
+						"menus" => MergeType::Menus,
+						"files" => MergeType::Files,
+						"all" => MergeType::All,
+						_ => return None,
+					}),
+					_ => return None,
+				})
+			})
+			.collect();
+		Layout {
+			show_empty: node.attribute("show_empty") == Some("true"),
+			items,
+		}
+	}
+}
+
+/// A `<Menu>` element with all merges applied, but not evaluated yet.
+#[derive(Debug, Clone, Default)]
+struct MenuNode {
+	name: String,
+	app_dirs: Vec<PathBuf>,
+	directory_dirs: Vec<PathBuf>,
+	directories: Vec<String>,
+	steps: Vec<Step>,
+	only_unallocated: Option<bool>,
+	deleted: Option<bool>,
+	layout: Option<Layout>,
+	default_layout: Option<Layout>,
+	submenus: Vec<MenuNode>,
+}
+impl MenuNode {
+	/// Appends the contents of another menu with the same name, later elements win.
+	fn absorb(&mut self, other: MenuNode) {
+		self.app_dirs.extend(other.app_dirs);
+		self.directory_dirs.extend(other.directory_dirs);
+		self.directories.extend(other.directories);
+		self.steps.extend(other.steps);
+		self.only_unallocated = other.only_unallocated.or(self.only_unallocated);
+		self.deleted = other.deleted.or(self.deleted);
+		self.layout = other.layout.or(self.layout.take());
+		self.default_layout = other.default_layout.or(self.default_layout.take());
+		self.submenus.extend(other.submenus);
+	}
+
+	/// Merges submenus that share a name, which happens a lot after merging files.
+	fn consolidate(&mut self) {
+		let mut submenus: Vec<MenuNode> = Vec::new();
+		for submenu in std::mem::take(&mut self.submenus) {
+			match submenus.iter_mut().find(|s| s.name == submenu.name) {
+				Some(existing) => existing.absorb(submenu),
+				None => submenus.push(submenu),
+			}
+		}
+		for submenu in &mut submenus {
+			submenu.consolidate();
+		}
+		self.submenus = submenus;
+	}
+}
+
+/// Data dirs from least to most important, the order `<DefaultAppDirs>` and friends expand to.
+fn default_data_dirs(subdir: &str) -> Vec<PathBuf> {
+	let mut dirs = dirs::data_dir()
+		.into_iter()
+		.chain(get_data_dirs())
+		.unique()
+		.map(|dir| dir.join(subdir))
+		.collect::<Vec<_>>();
+	dirs.reverse();
+	dirs
+}
+
+struct Loader {
+	config_dirs: Vec<PathBuf>,
+	/// Files currently being loaded, to stop merge loops.
+	loading: HashSet<PathBuf>,
+}
+impl Loader {
+	fn load_file(&mut self, path: &Path) -> Result<MenuNode, String> {
+		let path = fs::canonicalize(path).map_err(|e| format!("Failed to open menu: {e}"))?;
+		if !self.loading.insert(path.clone()) {
+			return Err(format!("{} merges itself", path.display()));
+		}
+		let text = fs::read_to_string(&path).map_err(|e| format!("Failed to open menu: {e}"))?;
+		let document = Document::parse_with_options(
+			&text,
+			ParsingOptions {
+				allow_dtd: true,
+				..Default::default()
+			},
+		)
+		.map_err(|e| format!("Invalid menu file {}: {e}", path.display()))?;
+
+		let root = document.root_element();
+		if root.tag_name().name() != "Menu" {
+			return Err(format!("{} has no root <Menu>", path.display()));
+		}
+		let menu = self.parse_menu(root, &path);
+		self.loading.remove(&path);
+		Ok(menu)
+	}
+
+	fn parse_menu(&mut self, node: Node, file: &Path) -> MenuNode {
+		let base_dir = file.parent().unwrap_or(Path::new("/"));
+		let resolve = |text: &str| base_dir.join(text.trim());
+
+		let mut menu = MenuNode::default();
+		for child in node.children().filter(|c| c.is_element()) {
+			let text = child.text().unwrap_or_default();
+			match child.tag_name().name() {
+				"Name" => menu.name = text.trim().to_string(),
+				"AppDir" => menu.app_dirs.push(resolve(text)),
+				"DefaultAppDirs" => menu.app_dirs.extend(default_data_dirs("applications")),
+				"DirectoryDir" => menu.directory_dirs.push(resolve(text)),
+				"DefaultDirectoryDirs" => menu
+					.directory_dirs
+					.extend(default_data_dirs("desktop-directories")),
+				"Directory" => menu.directories.push(text.trim().to_string()),
+				"Include" => menu.steps.push(Step::Include(Rule::Or(
+					child.children().filter_map(Rule::parse).collect(),
+				))),
+				"Exclude" => menu.steps.push(Step::Exclude(Rule::Or(
+					child.children().filter_map(Rule::parse).collect(),
+				))),
+				"OnlyUnallocated" => menu.only_unallocated = Some(true),
+				"NotOnlyUnallocated" => menu.only_unallocated = Some(false),
+				"Deleted" => menu.deleted = Some(true),
+				"NotDeleted" => menu.deleted = Some(false),
+				"Layout" => menu.layout = Some(Layout::parse(child)),
+				"DefaultLayout" => menu.default_layout = Some(Layout::parse(child)),
+				"Menu" => menu.submenus.push(self.parse_menu(child, file)),
+				"MergeFile" => {
+					let merge_path = if child.attribute("type") == Some("parent") {
+						self.parent_menu_file(file)
+					} else {
+						Some(resolve(text))
+					};
+					if let Some(merge_path) = merge_path {
+						self.merge_file(&mut menu, &merge_path);
+					}
+				}
+				"MergeDir" => self.merge_dir(&mut menu, &resolve(text)),
+				"DefaultMergeDirs" => {
+					let prefix = std::env::var("XDG_MENU_PREFIX").unwrap_or_default();
+					let dirs = self
+						.config_dirs
+						.iter()
+						.rev()
+						.map(|dir| dir.join(format!("menus/{prefix}applications-merged")))
+						.collect::<Vec<_>>();
+					for dir in dirs {
+						self.merge_dir(&mut menu, &dir);
+					}
+				}
+				// not supported
+				_ => (),
+			}
+		}
+		menu
+	}
+
+	/// The file at the same path relative to the next, less important config dir.
+	fn parent_menu_file(&self, file: &Path) -> Option<PathBuf> {
+		let (i, relative) = self
+			.config_dirs
+			.iter()
+			.enumerate()
+			.find_map(|(i, dir)| Some((i, file.strip_prefix(dir).ok()?)))?;
+		self.config_dirs[i + 1..]
+			.iter()
+			.map(|dir| dir.join(relative))
+			.find(|path| path.is_file())
+	}
+
+	fn merge_file(&mut self, menu: &mut MenuNode, path: &Path) {
+		// a missing or broken merge file shouldn't take the whole menu down with it
+		if let Ok(merged) = self.load_file(path) {
+			menu.absorb(MenuNode {
+				name: String::new(),
+				..merged
+			});
+		}
+	}
+
+	fn merge_dir(&mut self, menu: &mut MenuNode, dir: &Path) {
+		let Ok(entries) = fs::read_dir(dir) else {
+			return;
+		};
+		let mut paths = entries
+			.filter_map(|e| Some(e.ok()?.path()))
+			.filter(|p| p.extension().is_some_and(|e| e == "menu"))
+			.collect::<Vec<_>>();
+		paths.sort();
+		for path in paths {
+			self.merge_file(menu, &path);
+		}
+	}
+}
+
+#[derive(Default)]
+struct Evaluator {
+	/// Desktop file IDs and paths found in each app dir.
+	app_dirs: HashMap<PathBuf, Vec<(String, PathBuf)>>,
+	desktop_files: HashMap<PathBuf, Option<DesktopFile>>,
+}
+impl Evaluator {
+	fn scan(&mut self, dir: &Path) -> &[(String, PathBuf)] {
+		self.app_dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+			WalkDir::new(dir)
+				.follow_links(true)
+				.into_iter()
+				.filter_map(|e| e.ok())
+				.map(|e| e.into_path())
+				.filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "desktop"))
+				.filter_map(|p| {
+					let id = p
+						.strip_prefix(dir)
+						.ok()?
+						.to_string_lossy()
+						.replace('/', "-");
+					Some((id, p))
+				})
+				.collect()
+		})
+	}
+
+	fn desktop_file(&mut self, path: &Path) -> Option<&DesktopFile> {
+		self.desktop_files
+			.entry(path.to_path_buf())
+			.or_insert_with(|| DesktopFile::parse(path.to_path_buf()).ok())
+			.as_ref()
+	}
+
+	/// First pass: which desktop files each menu includes, before unallocated files are known.
+	fn resolve<'a>(
+		&mut self,
+		node: &'a MenuNode,
+		parent_app_dirs: &[PathBuf],
+		parent_directory_dirs: &[PathBuf],
+		parent_default_layout: &Layout,
+	) -> Resolved<'a> {
+		let app_dirs = [parent_app_dirs, &node.app_dirs].concat();
+		let directory_dirs = [parent_directory_dirs, &node.directory_dirs].concat();
+		let default_layout = node
+			.default_layout
+			.clone()
+			.unwrap_or_else(|| parent_default_layout.clone());
+
+		// later app dirs take precedence for the same ID
+		let mut pool = HashMap::new();
+		for dir in &app_dirs {
+			for (id, path) in self.scan(dir).to_vec() {
+				pool.insert(id, path);
+			}
+		}
+		let mut pool = pool.into_iter().collect::<Vec<_>>();
+		pool.sort();
+
+		let mut files = Vec::new();
+		for (id, path) in pool {
+			let Some(desktop_file) = self.desktop_file(&path) else {
+				continue;
+			};
+			let mut included = false;
+			for step in &node.steps {
+				match step {
+					Step::Include(rule) if rule.matches(&id, desktop_file) => included = true,
+					Step::Exclude(rule) if rule.matches(&id, desktop_file) => included = false,
+					_ => (),
+				}
+			}
+			if included {
+				files.push((id, desktop_file.clone()));
+			}
+		}
+
+		let directory = node.directories.iter().rev().find_map(|name| {
+			directory_dirs
+				.iter()
+				.rev()
+				.map(|dir| dir.join(name))
+				.find(|path| path.is_file())
+				.and_then(|path| MenuDirectory::parse(path).ok())
+		});
+
+		Resolved {
+			node,
+			directory,
+			layout: node
+				.layout
+				.clone()
+				.unwrap_or_else(|| default_layout.clone()),
+			files,
+			submenus: node
+				.submenus
+				.iter()
+				.map(|s| self.resolve(s, &app_dirs, &directory_dirs, &default_layout))
+				.collect(),
+		}
+	}
+}
+
+struct Resolved<'a> {
+	node: &'a MenuNode,
+	directory: Option<MenuDirectory>,
+	layout: Layout,
+	files: Vec<(String, DesktopFile)>,
+	submenus: Vec<Resolved<'a>>,
+}
+impl Resolved<'_> {
+	fn deleted(&self) -> bool {
+		self.node.deleted.unwrap_or(false)
+	}
+
+	/// Desktop file IDs that ended up in a menu that isn't `<OnlyUnallocated>`.
+	fn allocated(&self) -> HashSet<String> {
+		let mut allocated = HashSet::new();
+		self.collect_allocated(&mut allocated);
+		allocated
+	}
+	fn collect_allocated(&self, allocated: &mut HashSet<String>) {
+		if self.deleted() {
+			return;
+		}
+		if !self.node.only_unallocated.unwrap_or(false) {
+			allocated.extend(self.files.iter().map(|(id, _)| id.clone()));
+		}
+		for submenu in &self.submenus {
+			submenu.collect_allocated(allocated);
+		}
+	}
+
+	/// Second pass: turns the resolved menu into one ready to show, `None` if it ends up hidden.
+	fn build(self, allocated: &HashSet<String>) -> Option<Menu> {
+		if self.deleted() || self.directory.as_ref().is_some_and(|d| d.no_display) {
+			return None;
+		}
+		let only_unallocated = self.node.only_unallocated.unwrap_or(false);
+		let mut apps = self
+			.files
+			.into_iter()
+			.filter(|(id, _)| !only_unallocated || !allocated.contains(id))
+			.filter_map(|(id, desktop_file)| Some((id, Application::create(desktop_file).ok()?)))
+			.collect::<Vec<_>>();
+		let mut submenus = self
+			.submenus
+			.into_iter()
+			.filter_map(|s| s.build(allocated))
+			.collect::<Vec<_>>();
+
+		let mut entries = Vec::new();
+		for item in &self.layout.items {
+			match item {
+				LayoutItem::Filename(id) => {
+					if let Some(i) = apps.iter().position(|(app_id, _)| app_id == id) {
+						entries.push(MenuEntry::Application(apps.remove(i).1));
+					}
+				}
+				LayoutItem::Menuname(name) => {
+					if let Some(i) = submenus.iter().position(|m| &m.name == name) {
+						entries.push(MenuEntry::Menu(submenus.remove(i)));
+					}
+				}
+				LayoutItem::Separator => entries.push(MenuEntry::Separator),
+				LayoutItem::Merge(merge_type) => {
+					let mut merged = Vec::new();
+					if matches!(merge_type, MergeType::Menus | MergeType::All) {
+						merged.extend(submenus.drain(..).map(MenuEntry::Menu));
+					}
+					if matches!(merge_type, MergeType::Files | MergeType::All) {
+						merged.extend(apps.drain(..).map(|(_, app)| MenuEntry::Application(app)));
+					}
+					merged.sort_by_cached_key(|entry| collate::sort_key(entry_name(entry)));
+					entries.extend(merged);
+				}
+			}
+		}
+
+		// drop separators at the ends and next to each other
+		let mut cleaned: Vec<MenuEntry> = Vec::new();
+		for entry in entries {
+			let separator = matches!(entry, MenuEntry::Separator);
+			if separator
+				&& cleaned
+					.last()
+					.is_none_or(|e| matches!(e, MenuEntry::Separator))
+			{
+				continue;
+			}
+			cleaned.push(entry);
+		}
+		if matches!(cleaned.last(), Some(MenuEntry::Separator)) {
+			cleaned.pop();
+		}
+
+		if cleaned.is_empty() && !self.layout.show_empty {
+			return None;
+		}
+		Some(Menu {
+			name: self.node.name.clone(),
+			directory: self.directory,
+			entries: cleaned,
+		})
+	}
+}
+
+fn entry_name(entry: &MenuEntry) -> &str {
+	match entry {
+		MenuEntry::Application(app) => app.name().unwrap_or_default(),
+		MenuEntry::Menu(menu) => menu.display_name(),
+		MenuEntry::Separator => "",
+	}
+}
+
+#[test]
+fn test_menu_evaluation() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let write = |path: &str, data: &str| {
+		let path = dir.path().join(path);
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, data).unwrap();
+	};
+	write(
+		"apps/firefox.desktop",
+		"[Desktop Entry]\nName=Firefox\nCategories=Network;WebBrowser;",
+	);
+	write(
+		"apps/gimp.desktop",
+		"[Desktop Entry]\nName=GIMP\nCategories=Graphics;",
+	);
+	write("apps/mystery.desktop", "[Desktop Entry]\nName=Mystery");
+	write(
+		"apps/hidden.desktop",
+		"[Desktop Entry]\nName=Hidden\nCategories=Network;\nNoDisplay=true",
+	);
+	write(
+		"apps/sub/editor.desktop",
+		"[Desktop Entry]\nName=Editor\nCategories=Utility;TextEditor;",
+	);
+	write(
+		"dirs/internet.directory",
+		"[Desktop Entry]\nName=Internet\nName[de]=Netz\nIcon=applications-internet",
+	);
+	write(
+		"menus/applications.menu",
+		r#"<!DOCTYPE Menu PUBLIC "-//freedesktop//DTD Menu 1.0//EN"
+ "http://www.freedesktop.org/standards/menu-spec/1.0/menu.dtd">
+<Menu>
+	<Name>Applications</Name>
+	<AppDir>../apps</AppDir>
+	<DirectoryDir>../dirs</DirectoryDir>
+	<DefaultLayout><Merge type="menus"/><Separator/><Merge type="files"/></DefaultLayout>
+	<Menu>
+		<Name>Internet</Name>
+		<Directory>internet.directory</Directory>
+		<Include><Category>Network</Category></Include>
+	</Menu>
+	<Menu>
+		<Name>Graphics</Name>
+		<Include><And><Category>Graphics</Category><Not><Filename>firefox.desktop</Filename></Not></And></Include>
+	</Menu>
+	<Menu><Name>Empty</Name><Include><Category>Nope</Category></Include></Menu>
+	<Menu><Name>Removed</Name><Include><All/></Include><Deleted/></Menu>
+	<MergeFile>extra.menu</MergeFile>
+	<Menu><Name>Other</Name><OnlyUnallocated/><Include><All/></Include></Menu>
+</Menu>"#,
+	);
+	write(
+		"menus/extra.menu",
+		r#"<Menu>
+	<Name>Applications</Name>
+	<Menu><Name>Accessories</Name><Include><Filename>sub-editor.desktop</Filename></Include></Menu>
+	<Menu><Name>Internet</Name><Include><Filename>gimp.desktop</Filename></Include></Menu>
+</Menu>"#,
+	);
+
+	let menu = load_menu(dir.path().join("menus/applications.menu")).unwrap();
+	let names = menu
+		.submenus()
+		.map(|m| m.display_name())
+		.collect::<Vec<_>>();
+	assert_eq!(names, ["Accessories", "Graphics", "Internet", "Other"]);
+	assert_eq!(
+		menu.entries.len(),
+		4,
+		"trailing separator should be dropped"
+	);
+
+	let apps = |name: &str| {
+		menu.submenus()
+			.find(|m| m.name == name)
+			.unwrap()
+			.apps()
+			.map(|a| a.name().unwrap().to_string())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(apps("Accessories"), ["Editor"]);
+	assert_eq!(apps("Internet"), ["Firefox", "GIMP"]);
+	assert_eq!(apps("Other"), ["Mystery"]);
+
+	let directory = MenuDirectory::parse_localized(
+		dir.path().join("dirs/internet.directory"),
+		&["de_DE".to_string(), "de".to_string()],
+	)
+	.unwrap();
+	assert_eq!(directory.name.as_deref(), Some("Netz"));
+	assert_eq!(directory.icon.as_deref(), Some("applications-internet"));
+}
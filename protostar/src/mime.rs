@@ -0,0 +1,913 @@
+use crate::filter::glob_to_regex;
+use crate::index::{get_indexed_desktop_files, write_atomic};
+use crate::xdg::{DesktopFile, get_config_dirs, get_config_home, get_data_dirs};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_APPLICATIONS: &str = "Default Applications";
+const ADDED_ASSOCIATIONS: &str = "Added Associations";
+const REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+
+/// Weight of patterns from the old `globs` file, which has none.
+const DEFAULT_GLOB_WEIGHT: u32 = 50;
+/// Never read more than this much of a file to sniff its type, whatever the magic rules ask for.
+const MAX_SNIFF_LEN: usize = 64 * 1024;
+
+lazy_static! {
+	static ref MIME_DATABASE: MimeDatabase = MimeDatabase::load(&get_mime_dirs());
+}
+
+/// The shared-mime-info `mime` dirs, most important first.
+pub fn get_mime_dirs() -> Vec<PathBuf> {
+	dirs::data_dir()
+		.into_iter()
+		.chain(get_data_dirs())
+		.map(|dir| dir.join("mime"))
+		.filter(|dir| dir.is_dir())
+		.unique()
+		.collect()
+}
+
+/// Every `mimeapps.list` that exists, most important first.
+/// Each dir has the ones for `$XDG_CURRENT_DESKTOP` first, like `kde-mimeapps.list`.
+pub fn get_mimeapps_paths() -> Vec<PathBuf> {
+	let desktops = std::env::var("XDG_CURRENT_DESKTOP")
+		.unwrap_or_default()
+		.split(':')
+		.filter(|d| !d.is_empty())
+		.map(|d| format!("{}-mimeapps.list", d.to_lowercase()))
+		.chain(std::iter::once("mimeapps.list".to_string()))
+		.collect::<Vec<_>>();
+	// the `applications` dirs are deprecated, but still where distros ship their defaults
+	let app_dirs = dirs::data_dir()
+		.into_iter()
+		.chain(get_data_dirs())
+		.map(|dir| dir.join("applications"));
+	get_config_dirs()
+		.into_iter()
+		.chain(app_dirs)
+		.unique()
+		.flat_map(|dir| desktops.iter().map(move |name| dir.join(name)))
+		.filter(|path| path.is_file())
+		.collect()
+}
+
+#[derive(Debug, Clone)]
+struct Glob {
+	weight: u32,
+	mime: String,
+	pattern: String,
+	case_sensitive: bool,
+}
+
+/// The shared-mime-info database: file name globs, magic rules to sniff contents, aliases and subclasses.
+#[derive(Debug, Default)]
+pub struct MimeDatabase {
+	/// Patterns without wildcards like `Makefile`, by lowercase name.
+	literals: HashMap<String, Vec<Glob>>,
+	/// Patterns like `*.tar.gz`, by lowercase extension without the first dot.
+	suffixes: HashMap<String, Vec<Glob>>,
+	/// Everything else, like `*.so.[0-9]*`.
+	globs: Vec<(Regex, Glob)>,
+	/// Highest priority first.
+	magic: Vec<MagicRule>,
+	/// How much of a file the magic rules look at.
+	magic_len: usize,
+	aliases: HashMap<String, String>,
+	parents: HashMap<String, Vec<String>>,
+}
+impl MimeDatabase {
+	/// Loads `globs2` (or `globs`), `magic`, `aliases` and `subclasses` from each of the dirs, most important first.
+	/// Missing or broken files are skipped.
+	pub fn load(dirs: &[PathBuf]) -> Self {
+		let mut db = MimeDatabase::default();
+		let mut seen_globs = HashSet::new();
+		for dir in dirs {
+			let globs = fs::read_to_string(dir.join("globs2"))
+				.map(|text| parse_globs2(&text))
+				.or_else(|_| fs::read_to_string(dir.join("globs")).map(|text| parse_globs(&text)))
+				.unwrap_or_default();
+			for glob in globs {
+				if seen_globs.insert((glob.mime.clone(), glob.pattern.clone())) {
+					db.add_glob(glob);
+				}
+			}
+
+			match fs::read(dir.join("magic")) {
+				Ok(data) => match parse_magic(&data) {
+					Ok(rules) => db.magic.extend(rules),
+					Err(e) => tracing::warn!("Invalid magic in {}: {e}", dir.display()),
+				},
+				Err(e) if e.kind() == ErrorKind::NotFound => (),
+				Err(e) => tracing::warn!("Failed to read magic in {}: {e}", dir.display()),
+			}
+
+			for (alias, mime) in read_pairs(&dir.join("aliases")) {
+				db.aliases.entry(alias).or_insert(mime);
+			}
+			for (mime, parent) in read_pairs(&dir.join("subclasses")) {
+				let parents = db.parents.entry(mime).or_default();
+				if !parents.contains(&parent) {
+					parents.push(parent);
+				}
+			}
+		}
+		// stable, so rules from more important dirs win ties
+		db.magic
+			.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+		db.magic_len = db
+			.magic
+			.iter()
+			.flat_map(|rule| &rule.matchlets)
+			// a corrupt file can have offsets that don't fit, those matchlets never match anyway
+			.filter_map(|m| m.offset.checked_add(m.range)?.checked_add(m.value.len()))
+			.max()
+			.unwrap_or_default()
+			.min(MAX_SNIFF_LEN);
+		db
+	}
+
+	fn add_glob(&mut self, glob: Glob) {
+		let wildcards = ['*', '?', '['];
+		if !glob.pattern.contains(wildcards) {
+			self.literals
+				.entry(glob.pattern.to_lowercase())
+				.or_default()
+				.push(glob);
+		} else if let Some(extension) = glob.pattern.strip_prefix("*.")
+			&& !extension.contains(wildcards)
+		{
+			self.suffixes
+				.entry(extension.to_lowercase())
+				.or_default()
+				.push(glob);
+		} else {
+			let flags = if glob.case_sensitive { "" } else { "(?i)" };
+			match Regex::new(&format!("{flags}{}", glob_to_regex(&glob.pattern))) {
+				Ok(regex) => self.globs.push((regex, glob)),
+				Err(e) => tracing::warn!("Invalid glob {:?}: {e}", glob.pattern),
+			}
+		}
+	}
+
+	/// The canonical name for an alias like `application/x-pdf`, or the type itself.
+	pub fn unalias<'a>(&'a self, mime: &'a str) -> &'a str {
+		self.aliases.get(mime).map(String::as_str).unwrap_or(mime)
+	}
+
+	/// All types `mime` is a subclass of, closest first.
+	/// Every `text/*` type is a `text/plain` too, but `application/octet-stream` is left out
+	/// as it would make every app that opens any file a candidate for everything.
+	pub fn ancestors(&self, mime: &str) -> Vec<String> {
+		let mime = self.unalias(mime);
+		let mut ancestors: Vec<String> = Vec::new();
+		let mut queue = vec![mime.to_string()];
+		while !queue.is_empty() {
+			for current in std::mem::take(&mut queue) {
+				let mut parents = self.parents.get(&current).cloned().unwrap_or_default();
+				if current.starts_with("text/") && current != "text/plain" {
+					parents.push("text/plain".to_string());
+				}
+				for parent in parents {
+					let parent = self.unalias(&parent).to_string();
+					if parent != mime && !ancestors.contains(&parent) {
+						ancestors.push(parent.clone());
+						queue.push(parent);
+					}
+				}
+			}
+		}
+		ancestors
+	}
+
+	/// Whether `mime` is `parent` or one of its subclasses.
+	pub fn is_subclass(&self, mime: &str, parent: &str) -> bool {
+		let parent = self.unalias(parent);
+		self.unalias(mime) == parent || self.ancestors(mime).iter().any(|a| a == parent)
+	}
+
+	/// Types whose globs match a file name best, empty if none do.
+	/// More than one means the name alone is ambiguous, like `*.m` for Objective-C and MATLAB.
+	pub fn mime_types_for_name(&self, name: &str) -> Vec<String> {
+		let lowercase = name.to_lowercase();
+		let mut matches = self
+			.literals
+			.get(&lowercase)
+			.into_iter()
+			.flatten()
+			.filter(|g| !g.case_sensitive || g.pattern == name)
+			.collect::<Vec<_>>();
+		if matches.is_empty() {
+			// the longest extension wins, so `.tar.gz` comes before `.gz`
+			matches = name
+				.match_indices('.')
+				.find_map(|(i, _)| {
+					let extension = &name[i + 1..];
+					let found = self
+						.suffixes
+						.get(&extension.to_lowercase())?
+						.iter()
+						.filter(|g| !g.case_sensitive || g.pattern[2..] == *extension)
+						.collect::<Vec<_>>();
+					(!found.is_empty()).then_some(found)
+				})
+				.unwrap_or_default();
+		}
+		if matches.is_empty() {
+			matches = self
+				.globs
+				.iter()
+				.filter(|(regex, _)| regex.is_match(name))
+				.map(|(_, glob)| glob)
+				.collect();
+		}
+
+		let best = matches.iter().map(|g| g.weight).max().unwrap_or_default();
+		matches
+			.into_iter()
+			.filter(|g| g.weight == best)
+			.map(|g| g.mime.clone())
+			.unique()
+			.collect()
+	}
+
+	/// The type of some file contents according to the magic rules.
+	pub fn mime_type_for_data(&self, data: &[u8]) -> Option<&str> {
+		self.magic
+			.iter()
+			.find(|rule| rule.matches(data))
+			.map(|rule| rule.mime.as_str())
+	}
+
+	/// The type of a file, from its name and if that's not conclusive from its contents.
+	/// Falls back to `text/plain` for anything that looks like text, else `application/octet-stream`.
+	pub fn mime_type_for_path(&self, path: &Path) -> String {
+		let file_type = fs::metadata(path).map(|m| m.file_type());
+		match &file_type {
+			Ok(t) if t.is_dir() => return "inode/directory".to_string(),
+			Ok(t) if t.is_fifo() => return "inode/fifo".to_string(),
+			Ok(t) if t.is_socket() => return "inode/socket".to_string(),
+			Ok(t) if t.is_char_device() => return "inode/chardevice".to_string(),
+			Ok(t) if t.is_block_device() => return "inode/blockdevice".to_string(),
+			_ => (),
+		}
+
+		let globs = path
+			.file_name()
+			.map(|name| self.mime_types_for_name(&name.to_string_lossy()))
+			.unwrap_or_default();
+		if globs.len() == 1 {
+			return globs[0].clone();
+		}
+
+		let data = file_type
+			.is_ok()
+			.then(|| read_head(path, self.magic_len.max(512)))
+			.flatten();
+		if let Some(data) = &data
+			&& let Some(magic) = self.mime_type_for_data(data)
+			&& (globs.is_empty() || globs.iter().any(|g| self.is_subclass(magic, g)))
+		{
+			return magic.to_string();
+		}
+		if let Some(glob) = globs.into_iter().next() {
+			return glob;
+		}
+		match data {
+			Some(data) if data.is_empty() => "application/x-zerosize".to_string(),
+			Some(data) if looks_like_text(&data) => "text/plain".to_string(),
+			_ => "application/octet-stream".to_string(),
+		}
+	}
+}
+
+fn parse_globs2(text: &str) -> Vec<Glob> {
+	text.lines()
+		.filter(|line| !line.starts_with('#'))
+		.filter_map(|line| {
+			let mut fields = line.splitn(4, ':');
+			let weight = fields.next()?.parse().ok()?;
+			let mime = fields.next()?.to_string();
+			let pattern = fields.next()?.to_string();
+			let case_sensitive = fields
+				.next()
+				.is_some_and(|flags| flags.split(',').any(|f| f == "cs"));
+			Some(Glob {
+				weight,
+				mime,
+				pattern,
+				case_sensitive,
+			})
+		})
+		.collect()
+}
+
+fn parse_globs(text: &str) -> Vec<Glob> {
+	text.lines()
+		.filter(|line| !line.starts_with('#'))
+		.filter_map(|line| {
+			let (mime, pattern) = line.split_once(':')?;
+			Some(Glob {
+				weight: DEFAULT_GLOB_WEIGHT,
+				mime: mime.to_string(),
+				pattern: pattern.to_string(),
+				case_sensitive: false,
+			})
+		})
+		.collect()
+}
+
+/// Reads a file of space separated pairs like `aliases` or `subclasses`.
+fn read_pairs(path: &Path) -> Vec<(String, String)> {
+	fs::read_to_string(path)
+		.unwrap_or_default()
+		.lines()
+		.filter_map(|line| line.split_once(' '))
+		.map(|(a, b)| (a.to_string(), b.trim().to_string()))
+		.collect()
+}
+
+fn read_head(path: &Path, len: usize) -> Option<Vec<u8>> {
+	let mut data = Vec::with_capacity(len);
+	fs::File::open(path)
+		.ok()?
+		.take(len as u64)
+		.read_to_end(&mut data)
+		.ok()?;
+	Some(data)
+}
+
+/// Valid UTF-8, possibly cut off mid-character, without control characters other than whitespace.
+fn looks_like_text(data: &[u8]) -> bool {
+	let valid = match std::str::from_utf8(data) {
+		Ok(_) => true,
+		Err(e) => e.error_len().is_none(),
+	};
+	valid
+		&& !data
+			.iter()
+			.any(|b| b.is_ascii_control() && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+}
+
+/// A `[priority:type]` section of the binary `magic` file.
+#[derive(Debug, Clone)]
+struct MagicRule {
+	priority: u32,
+	mime: String,
+	matchlets: Vec<Matchlet>,
+}
+impl MagicRule {
+	/// Matchlets form a tree through their indent, the rule matches if any path from a root to a leaf does.
+	fn matches(&self, data: &[u8]) -> bool {
+		// one entry for each level whose last matchlet matched
+		let mut matched = 0;
+		for (i, matchlet) in self.matchlets.iter().enumerate() {
+			if matchlet.indent > matched {
+				// the parent didn't match
+				continue;
+			}
+			matched = matchlet.indent;
+			if !matchlet.matches(data) {
+				continue;
+			}
+			matched += 1;
+			let leaf = self
+				.matchlets
+				.get(i + 1)
+				.is_none_or(|next| next.indent <= matchlet.indent);
+			if leaf {
+				return true;
+			}
+		}
+		false
+	}
+}
+
+#[derive(Debug, Clone)]
+struct Matchlet {
+	indent: usize,
+	offset: usize,
+	value: Vec<u8>,
+	mask: Option<Vec<u8>>,
+	/// How many offsets from `offset` on to try, at least 1.
+	range: usize,
+}
+impl Matchlet {
+	fn matches(&self, data: &[u8]) -> bool {
+		let Some(last) = self.offset.checked_add(self.range) else {
+			return false;
+		};
+		(self.offset..last.min(data.len())).any(|start| {
+			let Some(window) = start
+				.checked_add(self.value.len())
+				.and_then(|end| data.get(start..end))
+			else {
+				return false;
+			};
+			match &self.mask {
+				Some(mask) => window
+					.iter()
+					.zip(mask)
+					.zip(&self.value)
+					.all(|((d, m), v)| d & m == v & m),
+				None => window == self.value,
+			}
+		})
+	}
+}
+
+/// Parses the binary `magic` file, where each line is `[indent]>offset=<u16 length><value>[&mask][~word-size][+range]`.
+fn parse_magic(data: &[u8]) -> Result<Vec<MagicRule>, String> {
+	let mut data = data
+		.strip_prefix(b"MIME-Magic\0\n")
+		.ok_or("missing MIME-Magic header")?;
+	let mut rules: Vec<MagicRule> = Vec::new();
+	while !data.is_empty() {
+		if data[0] == b'[' {
+			let end = data
+				.iter()
+				.position(|b| *b == b'\n')
+				.ok_or("unterminated section")?;
+			let header = std::str::from_utf8(&data[1..end]).map_err(|e| e.to_string())?;
+			let (priority, mime) = header
+				.trim_end_matches(']')
+				.split_once(':')
+				.ok_or_else(|| format!("invalid section [{header}"))?;
+			rules.push(MagicRule {
+				priority: priority
+					.parse()
+					.map_err(|_| format!("invalid priority {priority:?}"))?,
+				mime: mime.to_string(),
+				matchlets: Vec::new(),
+			});
+			data = &data[end + 1..];
+			continue;
+		}
+
+		let rule = rules.last_mut().ok_or("matchlet outside of a section")?;
+		let indent = take_number(&mut data).unwrap_or(0);
+		if !take_byte(&mut data, b'>') {
+			return Err(format!("expected `>` in {}", rule.mime));
+		}
+		let offset =
+			take_number(&mut data).ok_or_else(|| format!("missing offset in {}", rule.mime))?;
+		if !take_byte(&mut data, b'=') {
+			return Err(format!("expected `=` in {}", rule.mime));
+		}
+		let len = take(&mut data, 2).ok_or("truncated value length")?;
+		let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+		let mut value = take(&mut data, len).ok_or("truncated value")?.to_vec();
+		let mut mask = take_byte(&mut data, b'&')
+			.then(|| take(&mut data, len).map(<[u8]>::to_vec))
+			.map(|mask| mask.ok_or("truncated mask"))
+			.transpose()?;
+		let word_size = if take_byte(&mut data, b'~') {
+			take_number(&mut data).unwrap_or(1)
+		} else {
+			1
+		};
+		let range = if take_byte(&mut data, b'+') {
+			take_number(&mut data).unwrap_or(1).max(1)
+		} else {
+			1
+		};
+		// anything else is an extension we don't know, which the spec says to ignore
+		let end = data
+			.iter()
+			.position(|b| *b == b'\n')
+			.ok_or("unterminated matchlet")?;
+		data = &data[end + 1..];
+
+		// multi-byte values are stored big endian, but meant to be compared in host byte order
+		if cfg!(target_endian = "little") && word_size > 1 && len.is_multiple_of(word_size) {
+			value.chunks_mut(word_size).for_each(<[u8]>::reverse);
+			if let Some(mask) = &mut mask {
+				mask.chunks_mut(word_size).for_each(<[u8]>::reverse);
+			}
+		}
+		rule.matchlets.push(Matchlet {
+			indent,
+			offset,
+			value,
+			mask,
+			range,
+		});
+	}
+	Ok(rules)
+}
+
+fn take_number(data: &mut &[u8]) -> Option<usize> {
+	let digits = data.iter().take_while(|b| b.is_ascii_digit()).count();
+	let number = std::str::from_utf8(&data[..digits]).ok()?.parse().ok()?;
+	*data = &data[digits..];
+	Some(number)
+}
+
+fn take_byte(data: &mut &[u8], byte: u8) -> bool {
+	match data.split_first() {
+		Some((first, rest)) if *first == byte => {
+			*data = rest;
+			true
+		}
+		_ => false,
+	}
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+	let (taken, rest) = data.split_at_checked(len)?;
+	*data = rest;
+	Some(taken)
+}
+
+/// The associations from one `mimeapps.list`, desktop file IDs by MIME type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeAppsList {
+	pub defaults: HashMap<String, Vec<String>>,
+	pub added: HashMap<String, Vec<String>>,
+	pub removed: HashMap<String, Vec<String>>,
+}
+impl MimeAppsList {
+	pub fn parse(text: &str) -> Self {
+		let mut list = MimeAppsList::default();
+		let mut group = None;
+		for line in text.lines().map(str::trim) {
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+				group = match name {
+					DEFAULT_APPLICATIONS => Some(&mut list.defaults),
+					ADDED_ASSOCIATIONS => Some(&mut list.added),
+					REMOVED_ASSOCIATIONS => Some(&mut list.removed),
+					_ => None,
+				};
+				continue;
+			}
+			if let Some(group) = &mut group
+				&& let Some((mime, ids)) = line.split_once('=')
+			{
+				let ids = ids
+					.split(';')
+					.map(str::trim)
+					.filter(|id| !id.is_empty())
+					.map(str::to_string);
+				group
+					.entry(mime.trim().to_string())
+					.or_default()
+					.extend(ids);
+			}
+		}
+		list
+	}
+}
+
+/// Resolves which apps open which types, following the MIME applications associations spec.
+pub struct MimeApps {
+	/// Most important first.
+	lists: Vec<MimeAppsList>,
+	/// The installed apps, each ID only once.
+	apps: Vec<(String, DesktopFile)>,
+}
+impl MimeApps {
+	/// Reads every `mimeapps.list` and indexes the installed desktop files.
+	pub fn load() -> Self {
+		let lists = get_mimeapps_paths()
+			.iter()
+			.filter_map(|path| fs::read_to_string(path).ok())
+			.map(|text| MimeAppsList::parse(&text))
+			.collect();
+		MimeApps::new(lists, get_indexed_desktop_files())
+	}
+
+	/// Desktop files earlier in the list win if there are several with the same ID.
+	pub fn new(lists: Vec<MimeAppsList>, desktop_files: Vec<DesktopFile>) -> Self {
+		let mut ids = HashSet::new();
+		let apps = desktop_files
+			.into_iter()
+			.map(|df| (df.id(), df))
+			.filter(|(id, _)| ids.insert(id.clone()))
+			.collect();
+		MimeApps { lists, apps }
+	}
+
+	fn app(&self, id: &str) -> Option<usize> {
+		self.apps.iter().position(|(app_id, _)| app_id == id)
+	}
+
+	/// The app to open `mime` with: the first installed one from `Default Applications`,
+	/// else the most preferred associated app, repeating for the parent types if there's neither.
+	pub fn default_app_for(&self, db: &MimeDatabase, mime: &str) -> Option<&DesktopFile> {
+		let mime = db.unalias(mime).to_string();
+		std::iter::once(mime.clone())
+			.chain(db.ancestors(&mime))
+			.find_map(|mime| {
+				self.lists
+					.iter()
+					.flat_map(|list| list.defaults.get(&mime))
+					.flatten()
+					.find_map(|id| self.app(id))
+					.or_else(|| self.associated_apps(db, &mime).first().copied())
+			})
+			.map(|i| &self.apps[i].1)
+	}
+
+	/// Every app that can open `mime` or one of its parent types, most preferred first.
+	pub fn apps_for(&self, db: &MimeDatabase, mime: &str) -> Vec<&DesktopFile> {
+		let mime = db.unalias(mime).to_string();
+		std::iter::once(mime.clone())
+			.chain(db.ancestors(&mime))
+			.flat_map(|mime| self.associated_apps(db, &mime))
+			.unique()
+			.map(|i| &self.apps[i].1)
+			.collect()
+	}
+
+	/// `Added Associations` in order, then apps listing the type in `MimeType`.
+	/// A removal hides an app from the list it's in and all less important ones.
+	fn associated_apps(&self, db: &MimeDatabase, mime: &str) -> Vec<usize> {
+		let mut removed = HashSet::new();
+		let mut apps = Vec::new();
+		for list in &self.lists {
+			for id in list.added.get(mime).into_iter().flatten() {
+				if !removed.contains(id)
+					&& let Some(i) = self.app(id)
+				{
+					apps.push(i);
+				}
+			}
+			removed.extend(list.removed.get(mime).into_iter().flatten());
+		}
+		for (i, (id, app)) in self.apps.iter().enumerate() {
+			if !removed.contains(id) && app.mime_types.iter().any(|m| db.unalias(m) == mime) {
+				apps.push(i);
+			}
+		}
+		apps.into_iter().unique().collect()
+	}
+}
+
+/// Makes `id` the default app for `mime` in the `mimeapps.list` at `path`,
+/// also adding it as the first association and undoing any removal, like file managers do.
+/// Everything else in the file is kept as it was.
+pub fn write_default_app(path: &Path, mime: &str, id: &str) -> std::io::Result<()> {
+	let text = match fs::read_to_string(path) {
+		Ok(text) => text,
+		Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+		Err(e) => return Err(e),
+	};
+	let list = MimeAppsList::parse(&text);
+	let added = std::iter::once(id)
+		.chain(
+			list.added
+				.get(mime)
+				.into_iter()
+				.flatten()
+				.map(String::as_str),
+		)
+		.unique()
+		.map(|id| format!("{id};"))
+		.collect::<String>();
+	let removed = list
+		.removed
+		.get(mime)
+		.into_iter()
+		.flatten()
+		.filter(|r| *r != id)
+		.map(|id| format!("{id};"))
+		.collect::<String>();
+
+	let text = set_key(&text, DEFAULT_APPLICATIONS, mime, Some(&format!("{id};")));
+	let text = set_key(&text, ADDED_ASSOCIATIONS, mime, Some(&added));
+	let text = set_key(
+		&text,
+		REMOVED_ASSOCIATIONS,
+		mime,
+		(!removed.is_empty()).then_some(&removed),
+	);
+
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	write_atomic(path, text)
+}
+
+/// Sets or with `None` removes `key` in `[group]` of a key file, adding the group if needed.
+fn set_key(text: &str, group: &str, key: &str, value: Option<&str>) -> String {
+	let mut lines = text.lines().map(str::to_string).collect::<Vec<_>>();
+	let header = format!("[{group}]");
+	let Some(start) = lines.iter().position(|l| l.trim() == header) else {
+		if let Some(value) = value {
+			if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+				lines.push(String::new());
+			}
+			lines.push(header);
+			lines.push(format!("{key}={value}"));
+		}
+		return lines.join("\n") + "\n";
+	};
+	let end = lines[start + 1..]
+		.iter()
+		.position(|l| l.trim_start().starts_with('['))
+		.map_or(lines.len(), |i| start + 1 + i);
+	let existing = (start + 1..end).find(|i| {
+		lines[*i]
+			.split_once('=')
+			.is_some_and(|(k, _)| k.trim() == key)
+	});
+	match (existing, value) {
+		(Some(i), Some(value)) => lines[i] = format!("{key}={value}"),
+		(Some(i), None) => {
+			lines.remove(i);
+			// an empty group would be left behind otherwise
+			if lines[start + 1..end - 1]
+				.iter()
+				.all(|l| l.trim().is_empty())
+			{
+				lines.drain(start..end - 1);
+				if start == lines.len() {
+					while lines.last().is_some_and(|l| l.trim().is_empty()) {
+						lines.pop();
+					}
+				}
+			}
+		}
+		(None, Some(value)) => {
+			// after the last entry, before any blank lines separating the next group
+			let last = (start..end)
+				.rev()
+				.find(|i| !lines[*i].trim().is_empty())
+				.unwrap_or(start);
+			lines.insert(last + 1, format!("{key}={value}"));
+		}
+		(None, None) => (),
+	}
+	lines.join("\n") + "\n"
+}
+
+pub fn mime_database() -> &'static MimeDatabase {
+	&MIME_DATABASE
+}
+
+/// The MIME type of a file, see [`MimeDatabase::mime_type_for_path`].
+pub fn mime_for_path(path: &Path) -> String {
+	MIME_DATABASE.mime_type_for_path(path)
+}
+
+/// The app that opens `mime` by default, with the current associations.
+pub fn default_app_for(mime: &str) -> Option<DesktopFile> {
+	MimeApps::load()
+		.default_app_for(&MIME_DATABASE, mime)
+		.cloned()
+}
+
+/// Every app that can open `mime`, most preferred first.
+pub fn apps_for(mime: &str) -> Vec<DesktopFile> {
+	MimeApps::load()
+		.apps_for(&MIME_DATABASE, mime)
+		.into_iter()
+		.cloned()
+		.collect()
+}
+
+/// Makes `id` the user's default app for `mime`, in `$XDG_CONFIG_HOME/mimeapps.list`.
+pub fn set_default_app(mime: &str, id: &str) -> std::io::Result<()> {
+	write_default_app(&get_config_home().join("mimeapps.list"), mime, id)
+}
+
+#[test]
+fn test_mime_database() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let mime_dir = dir.path().join("mime");
+	fs::create_dir(&mime_dir).unwrap();
+	fs::write(
+		mime_dir.join("globs2"),
+		"# comment\n50:text/plain:*.txt\n50:application/gzip:*.gz\n50:application/x-compressed-tar:*.tar.gz\n50:text/x-makefile:makefile\n50:application/x-sharedlib:*.so.[0-9]*\n50:text/x-objcsrc:*.m\n50:text/x-matlab:*.m\n10:text/x-c:*.C:cs\n",
+	)
+	.unwrap();
+	fs::write(
+		mime_dir.join("subclasses"),
+		"application/x-compressed-tar application/gzip\ntext/x-matlab text/x-octave\n",
+	)
+	.unwrap();
+	fs::write(
+		mime_dir.join("aliases"),
+		"application/x-gzip application/gzip\n",
+	)
+	.unwrap();
+	let mut magic = b"MIME-Magic\0\n[50:image/png]\n>0=\0\x04\x89PNG\n".to_vec();
+	// only `AB..CD`, with `CD` nested under `AB`
+	magic.extend(b"[40:text/x-matlab]\n>0=\0\x02AB\n1>2=\0\x02CD+2\n");
+	// offsets that overflow never match instead of panicking
+	magic.extend(b"[60:text/x-broken]\n>18446744073709551615=\0\x01a+18446744073709551615\n");
+	fs::write(mime_dir.join("magic"), magic).unwrap();
+	let db = MimeDatabase::load(&[mime_dir]);
+
+	assert_eq!(db.mime_types_for_name("NOTES.TXT"), ["text/plain"]);
+	assert_eq!(
+		db.mime_types_for_name("a.tar.gz"),
+		["application/x-compressed-tar"]
+	);
+	assert_eq!(db.mime_types_for_name("Makefile"), ["text/x-makefile"]);
+	assert_eq!(
+		db.mime_types_for_name("libfoo.so.1"),
+		["application/x-sharedlib"]
+	);
+	assert_eq!(db.mime_types_for_name("a.m").len(), 2);
+	assert_eq!(db.mime_types_for_name("a.C"), ["text/x-c"]);
+	assert!(db.mime_types_for_name("a.c").is_empty());
+
+	assert_eq!(db.unalias("application/x-gzip"), "application/gzip");
+	assert_eq!(
+		db.ancestors("text/x-matlab"),
+		["text/x-octave", "text/plain"]
+	);
+	assert!(db.is_subclass("application/x-compressed-tar", "application/x-gzip"));
+
+	let write = |name: &str, data: &[u8]| {
+		let path = dir.path().join(name);
+		fs::write(&path, data).unwrap();
+		db.mime_type_for_path(&path)
+	};
+	assert_eq!(write("image", b"\x89PNG\r\n"), "image/png");
+	assert_eq!(write("ambiguous.m", b"AB_CD"), "text/x-matlab");
+	assert_eq!(write("ambiguous2.m", b"AB___CD"), "text/x-objcsrc");
+	assert_eq!(write("readme", "grüße\n".as_bytes()), "text/plain");
+	assert_eq!(write("binary", b"\0\x01\x02"), "application/octet-stream");
+	assert_eq!(write("empty", b""), "application/x-zerosize");
+	assert_eq!(db.mime_type_for_path(dir.path()), "inode/directory");
+}
+
+#[test]
+fn test_mime_apps() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let mime_dir = dir.path().join("mime");
+	fs::create_dir(&mime_dir).unwrap();
+	fs::write(mime_dir.join("subclasses"), "text/markdown text/plain\n").unwrap();
+	let db = MimeDatabase::load(&[mime_dir]);
+	let app = |id: &str, mime_types: &str| {
+		let path = dir.path().join(id);
+		fs::write(
+			&path,
+			format!("[Desktop Entry]\nName={id}\nExec=app\nMimeType={mime_types}"),
+		)
+		.unwrap();
+		DesktopFile::parse(path).unwrap()
+	};
+	let apps = vec![
+		app("editor.desktop", "text/plain;"),
+		app("notes.desktop", "text/markdown;"),
+		app("viewer.desktop", "text/markdown;image/png;"),
+	];
+	let user = MimeAppsList::parse(
+		"[Default Applications]\ntext/markdown=missing.desktop;notes.desktop;\n\n[Removed Associations]\nimage/png=viewer.desktop;\n",
+	);
+	let system = MimeAppsList::parse(
+		"[Added Associations]\nimage/png=viewer.desktop;editor.desktop;\n[Default Applications]\ntext/markdown=viewer.desktop\n",
+	);
+	let mime_apps = MimeApps::new(vec![user, system], apps);
+	let ids = |apps: Vec<&DesktopFile>| apps.iter().map(|a| a.id()).collect::<Vec<_>>();
+
+	assert_eq!(
+		mime_apps
+			.default_app_for(&db, "text/markdown")
+			.unwrap()
+			.id(),
+		"notes.desktop"
+	);
+	assert_eq!(
+		ids(mime_apps.apps_for(&db, "text/markdown")),
+		["notes.desktop", "viewer.desktop", "editor.desktop"]
+	);
+	assert_eq!(
+		ids(mime_apps.apps_for(&db, "image/png")),
+		["editor.desktop"]
+	);
+	assert_eq!(
+		mime_apps.default_app_for(&db, "text/x-rust").unwrap().id(),
+		"editor.desktop"
+	);
+	assert!(mime_apps.default_app_for(&db, "audio/ogg").is_none());
+
+	let path = dir.path().join("mimeapps.list");
+	fs::write(
+		&path,
+		"# keep me\n[Default Applications]\ntext/plain=editor.desktop;\n\n[Removed Associations]\nimage/png=viewer.desktop;\n",
+	)
+	.unwrap();
+	write_default_app(&path, "image/png", "viewer.desktop").unwrap();
+	write_default_app(&path, "text/plain", "notes.desktop").unwrap();
+	assert_eq!(
+		fs::read_to_string(&path).unwrap(),
+		"# keep me\n[Default Applications]\ntext/plain=notes.desktop;\nimage/png=viewer.desktop;\n\n[Added Associations]\nimage/png=viewer.desktop;\ntext/plain=notes.desktop;\n"
+	);
+	assert_eq!(
+		set_key("[A]\na=1\n\n[B]\nb=2\n", "B", "b", None),
+		"[A]\na=1\n"
+	);
+}
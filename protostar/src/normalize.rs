@@ -0,0 +1,82 @@
+use image::RgbaImage;
+use image::imageops::{self, FilterType};
+
+/// How much of the square the artwork gets to fill, the rest is an even transparent margin
+/// so icons don't touch the edge of the hexagon.
+pub const SAFE_AREA: f32 = 0.875;
+
+/// The smallest rectangle holding every pixel that isn't fully transparent, as `(x, y, width, height)`.
+pub fn opaque_bounds(image: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+	let mut bounds: Option<(u32, u32, u32, u32)> = None;
+	for (x, y, pixel) in image.enumerate_pixels() {
+		if pixel.0[3] == 0 {
+			continue;
+		}
+		let (min_x, min_y, max_x, max_y) = bounds.get_or_insert((x, y, x, y));
+		*min_x = (*min_x).min(x);
+		*min_y = (*min_y).min(y);
+		*max_x = (*max_x).max(x);
+		*max_y = (*max_y).max(y);
+	}
+	bounds.map(|(min_x, min_y, max_x, max_y)| (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+}
+
+/// The side of the square in the middle of a `size` pixel icon that the artwork fills.
+pub fn safe_size(size: u32) -> u32 {
+	((size as f32 * SAFE_AREA).round() as u32).max(1)
+}
+
+/// Makes an icon look the same size as every other one: trims its transparent margins, scales it
+/// to fill [`SAFE_AREA`] of a `size` pixel square without changing its aspect ratio, and centers it.
+pub fn normalize_icon(image: &RgbaImage, size: u32) -> RgbaImage {
+	let mut canvas = RgbaImage::new(size, size);
+	let Some((x, y, width, height)) = opaque_bounds(image) else {
+		return canvas;
+	};
+	let trimmed = imageops::crop_imm(image, x, y, width, height).to_image();
+
+	let safe_size = safe_size(size);
+	let scale = safe_size as f32 / width.max(height) as f32;
+	let scaled_width = ((width as f32 * scale).round() as u32).clamp(1, safe_size);
+	let scaled_height = ((height as f32 * scale).round() as u32).clamp(1, safe_size);
+	let scaled = if (scaled_width, scaled_height) == (width, height) {
+		trimmed
+	} else {
+		imageops::resize(&trimmed, scaled_width, scaled_height, FilterType::Lanczos3)
+	};
+
+	imageops::overlay(
+		&mut canvas,
+		&scaled,
+		((size - scaled_width) / 2) as i64,
+		((size - scaled_height) / 2) as i64,
+	);
+	canvas
+}
+
+#[test]
+fn test_normalize_icon() {
+	use image::Rgba;
+
+	let red = Rgba([255, 0, 0, 255]);
+	assert_eq!(opaque_bounds(&RgbaImage::new(4, 4)), None);
+
+	// a wide bar off in a corner of a non-square image ends up centered and letterboxed
+	let mut image = RgbaImage::new(40, 30);
+	for x in 0..20 {
+		for y in 0..10 {
+			image.put_pixel(x, y, red);
+		}
+	}
+	assert_eq!(opaque_bounds(&image), Some((0, 0, 20, 10)));
+	let normalized = normalize_icon(&image, 64);
+	assert_eq!(normalized.dimensions(), (64, 64));
+	// 56 of the 64 pixels are the safe area, so the 2:1 bar becomes 56x28
+	assert_eq!(opaque_bounds(&normalized), Some((4, 18, 56, 28)));
+	assert_eq!(normalized.get_pixel(32, 32), &red);
+
+	// fully transparent icons stay empty instead of failing
+	let empty = normalize_icon(&RgbaImage::new(16, 16), 32);
+	assert_eq!(empty.dimensions(), (32, 32));
+	assert_eq!(opaque_bounds(&empty), None);
+}
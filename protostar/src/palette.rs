@@ -0,0 +1,98 @@
+use crate::config::Color;
+use crate::xdg::{Icon, IconType};
+use image::RgbaImage;
+use std::fs;
+use std::io::{self, ErrorKind};
+
+/// Bits kept per channel when bucketing pixels, fewer merges more shades into the same color.
+const BUCKET_BITS: u32 = 4;
+
+/// The color that stands out most in an image as `[r, g, b]` in sRGB, or `None` if it's fully transparent.
+///
+/// Pixels are bucketed by color and each bucket is weighted by how saturated it is, so an accent
+/// color beats a larger white or gray background, which is what makes tinted tiles easy to tell apart.
+/// A grayscale icon still gets its most common gray.
+pub fn dominant_color(image: &RgbaImage) -> Option<[u8; 3]> {
+	let shift = 8 - BUCKET_BITS;
+	let mut buckets = vec![(0.0f32, [0u64; 3], 0u64); 1 << (3 * BUCKET_BITS)];
+	for pixel in image.pixels() {
+		let [r, g, b, a] = pixel.0;
+		// mostly transparent pixels are antialiasing and shadows
+		if a < 128 {
+			continue;
+		}
+		let index = ((r as usize >> shift) << (2 * BUCKET_BITS))
+			| ((g as usize >> shift) << BUCKET_BITS)
+			| (b as usize >> shift);
+		let bucket = &mut buckets[index];
+		bucket.0 += 0.1 + saturation([r, g, b]);
+		bucket.1[0] += r as u64;
+		bucket.1[1] += g as u64;
+		bucket.1[2] += b as u64;
+		bucket.2 += 1;
+	}
+	let (_, sums, count) = buckets
+		.into_iter()
+		.filter(|(_, _, count)| *count > 0)
+		.max_by(|(a, _, _), (b, _, _)| a.total_cmp(b))?;
+	// the average of the bucket instead of its corner, so the color is one the icon actually has
+	Some(sums.map(|sum| (sum / count) as u8))
+}
+
+/// HSV saturation, 0 for grays and 1 for pure colors.
+fn saturation([r, g, b]: [u8; 3]) -> f32 {
+	let max = r.max(g).max(b);
+	let min = r.min(g).min(b);
+	if max == 0 {
+		0.0
+	} else {
+		(max - min) as f32 / max as f32
+	}
+}
+
+/// The [`dominant_color`] of an icon, rasterizing it first if needed and caching the result next to
+/// its render in [`crate::icon_cache`]. 3D icons don't have one.
+pub fn icon_color(icon: &Icon) -> io::Result<Option<Color>> {
+	let png = match icon.icon_type {
+		IconType::Gltf => return Ok(None),
+		IconType::Png => icon.clone(),
+		_ => icon.clone().cached_process(icon.size)?,
+	};
+	let path = crate::icon_cache::get_or_derive(&png.path, "color", |data| {
+		let image = image::load_from_memory(data)
+			.map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?
+			.to_rgba8();
+		// an empty file records that there's no color, so it doesn't get computed again
+		Ok(dominant_color(&image)
+			.map(|[r, g, b]| format!("#{r:02x}{g:02x}{b:02x}"))
+			.unwrap_or_default()
+			.into_bytes())
+	})?;
+	let text = fs::read_to_string(path)?;
+	if text.is_empty() {
+		return Ok(None);
+	}
+	Color::try_from(text)
+		.map(Some)
+		.map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+#[test]
+fn test_dominant_color() {
+	use image::Rgba;
+
+	assert_eq!(dominant_color(&RgbaImage::new(8, 8)), None);
+
+	// a small red accent on a mostly white icon wins over the white
+	let mut image = RgbaImage::from_pixel(10, 10, Rgba([255, 255, 255, 255]));
+	for x in 0..10 {
+		image.put_pixel(x, 0, Rgba([200, 20, 20, 255]));
+		image.put_pixel(x, 1, Rgba([202, 22, 22, 255]));
+	}
+	assert_eq!(dominant_color(&image), Some([201, 21, 21]));
+
+	// transparent pixels don't count, and grayscale icons keep their gray
+	let mut image = RgbaImage::from_pixel(10, 10, Rgba([0, 0, 255, 0]));
+	image.put_pixel(0, 0, Rgba([90, 90, 90, 255]));
+	assert_eq!(dominant_color(&image), Some([90, 90, 90]));
+}
@@ -0,0 +1,438 @@
+use crate::application::{Application, launch_command, open_path, shell_quote};
+use crate::recent::{RecentFile, recent_files};
+use crate::search::{MatchField, SearchIndex, match_text};
+use stardust_xr_fusion::{node::NodeResult, spatial::SpatialRefAspect};
+use std::fs;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+/// Score for results from providers that recognise an explicit syntax (`>`, a path, arithmetic),
+/// so they rank above any fuzzy match.
+pub const SCORE_SYNTAX: u32 = 10_000;
+
+/// What happens when a query result is chosen.
+#[derive(Debug, Clone)]
+pub enum QueryAction {
+	Launch(Application),
+	/// Launch one of the application's desktop actions by its ID.
+	LaunchAction(Application, String),
+	/// Run a shell command.
+	Run(String),
+	/// Open a file or directory with its default application.
+	Open(PathBuf),
+	/// Copy text to the clipboard.
+	Copy(String),
+}
+impl QueryAction {
+	pub fn execute<T: SpatialRefAspect + Clone>(&self, launch_space: &T) -> NodeResult<()> {
+		match self {
+			QueryAction::Launch(app) => app.launch(launch_space),
+			QueryAction::LaunchAction(app, action) => app.launch_action(action, launch_space),
+			QueryAction::Run(command) => launch_command(launch_space, command.clone()),
+			QueryAction::Open(path) => open_path(path, launch_space),
+			QueryAction::Copy(text) => {
+				launch_command(launch_space, format!("wl-copy -- {}", shell_quote(text)))
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+	pub title: String,
+	pub subtitle: Option<String>,
+	/// Byte ranges of `title` that matched the query, for highlighting.
+	pub ranges: Vec<Range<usize>>,
+	/// On the same scale as [`crate::search::SearchResult::score`].
+	pub score: u32,
+	pub action: QueryAction,
+}
+
+/// A source of results for the launcher search bar.
+pub trait QueryProvider: Send + Sync {
+	/// Results for `query`, which is trimmed and never empty.
+	fn query(&self, query: &str) -> Vec<QueryResult>;
+}
+
+/// Runs a query through several providers and merges their results into one ranked list.
+#[derive(Default)]
+pub struct QueryEngine {
+	providers: Vec<Box<dyn QueryProvider>>,
+}
+impl QueryEngine {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// An engine with all the built in providers.
+	pub fn with_defaults(apps: Vec<Application>) -> Self {
+		QueryEngine::new()
+			.provider(CalculatorProvider)
+			.provider(CommandProvider)
+			.provider(PathProvider)
+			.provider(AppProvider::new(apps))
+			.provider(RecentFilesProvider::load())
+	}
+
+	pub fn provider(mut self, provider: impl QueryProvider + 'static) -> Self {
+		self.providers.push(Box::new(provider));
+		self
+	}
+
+	/// Best results first, ties keep the order the providers were added in.
+	pub fn query(&self, query: &str) -> Vec<QueryResult> {
+		let query = query.trim();
+		if query.is_empty() {
+			return Vec::new();
+		}
+		let mut results = self
+			.providers
+			.iter()
+			.flat_map(|p| p.query(query))
+			.collect::<Vec<_>>();
+		results.sort_by_key(|r| std::cmp::Reverse(r.score));
+		results
+	}
+}
+impl std::fmt::Debug for QueryEngine {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("QueryEngine")
+			.field("providers", &self.providers.len())
+			.finish()
+	}
+}
+
+/// Fuzzy searches applications and their desktop actions.
+pub struct AppProvider {
+	apps: Vec<Application>,
+	index: SearchIndex,
+}
+impl AppProvider {
+	pub fn new(apps: Vec<Application>) -> Self {
+		let index = SearchIndex::new(&apps);
+		AppProvider { apps, index }
+	}
+}
+impl QueryProvider for AppProvider {
+	fn query(&self, query: &str) -> Vec<QueryResult> {
+		self.index
+			.search(query)
+			.into_iter()
+			.map(|result| {
+				let app = &self.apps[result.index];
+				let title = app.name().unwrap_or_default().to_string();
+				match result.field {
+					MatchField::Action => QueryResult {
+						subtitle: Some(title),
+						action: app
+							.actions()
+							.iter()
+							.find(|a| a.name.as_deref() == Some(result.text.as_str()))
+							.map(|a| QueryAction::LaunchAction(app.clone(), a.id.clone()))
+							.unwrap_or_else(|| QueryAction::Launch(app.clone())),
+						title: result.text,
+						ranges: result.ranges,
+						score: result.score,
+					},
+					MatchField::Name => QueryResult {
+						title,
+						subtitle: app.generic_name().map(str::to_string),
+						ranges: result.ranges,
+						score: result.score,
+						action: QueryAction::Launch(app.clone()),
+					},
+					_ => QueryResult {
+						title,
+						subtitle: Some(result.text),
+						ranges: Vec::new(),
+						score: result.score,
+						action: QueryAction::Launch(app.clone()),
+					},
+				}
+			})
+			.collect()
+	}
+}
+
+/// Evaluates arithmetic like `2 * (3 + 4) ^ 2`, choosing the result copies it.
+pub struct CalculatorProvider;
+impl QueryProvider for CalculatorProvider {
+	fn query(&self, query: &str) -> Vec<QueryResult> {
+		let expression = query.strip_prefix('=').unwrap_or(query);
+		let has_operator = expression
+			.trim_start_matches(['-', '+'])
+			.contains(['+', '-', '*', '/', '%', '^', '(', '×', '÷']);
+		if !has_operator || !expression.contains(|c: char| c.is_ascii_digit()) {
+			return Vec::new();
+		}
+		let Some(value) = evaluate(expression) else {
+			return Vec::new();
+		};
+
+		let value = format_number(value);
+		vec![QueryResult {
+			title: value.clone(),
+			subtitle: Some(expression.trim().to_string()),
+			ranges: Vec::new(),
+			score: SCORE_SYNTAX,
+			action: QueryAction::Copy(value),
+		}]
+	}
+}
+
+/// Evaluates an arithmetic expression with `+ - * / % ^`, parentheses and unary signs.
+pub fn evaluate(expression: &str) -> Option<f64> {
+	let mut parser = Calculator {
+		chars: expression.chars().peekable(),
+	};
+	let value = parser.expression()?;
+	parser.skip_whitespace();
+	(parser.chars.peek().is_none() && value.is_finite()).then_some(value)
+}
+
+fn format_number(value: f64) -> String {
+	if value.fract() == 0.0 && value.abs() < 1e15 {
+		return format!("{}", value as i64);
+	}
+	let text = format!("{value:.12}");
+	text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+struct Calculator<'a> {
+	chars: Peekable<Chars<'a>>,
+}
+impl Calculator<'_> {
+	fn skip_whitespace(&mut self) {
+		while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+	}
+	fn eat(&mut self, options: &[char]) -> Option<char> {
+		self.skip_whitespace();
+		self.chars.next_if(|c| options.contains(c))
+	}
+
+	fn expression(&mut self) -> Option<f64> {
+		let mut value = self.term()?;
+		while let Some(op) = self.eat(&['+', '-']) {
+			let rhs = self.term()?;
+			value = if op == '+' { value + rhs } else { value - rhs };
+		}
+		Some(value)
+	}
+
+	fn term(&mut self) -> Option<f64> {
+		let mut value = self.unary()?;
+		while let Some(op) = self.eat(&['*', '/', '%', '×', '÷']) {
+			let rhs = self.unary()?;
+			value = match op {
+				'*' | '×' => value * rhs,
+				'/' | '÷' => value / rhs,
+				_ => value % rhs,
+			};
+		}
+		Some(value)
+	}
+
+	fn unary(&mut self) -> Option<f64> {
+		match self.eat(&['-', '+']) {
+			Some('-') => Some(-self.unary()?),
+			Some(_) => self.unary(),
+			None => self.power(),
+		}
+	}
+
+	fn power(&mut self) -> Option<f64> {
+		let base = self.primary()?;
+		if self.eat(&['^']).is_some() {
+			// right associative and binds tighter than unary minus, so 2^3^2 is 2^9 and -2^2 is -4
+			return Some(base.powf(self.unary()?));
+		}
+		Some(base)
+	}
+
+	fn primary(&mut self) -> Option<f64> {
+		if self.eat(&['(']).is_some() {
+			let value = self.expression()?;
+			self.eat(&[')'])?;
+			return Some(value);
+		}
+		self.skip_whitespace();
+		let mut number = String::new();
+		while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+			number.push(c);
+		}
+		number.parse().ok()
+	}
+}
+
+/// Runs a shell command typed after a `>` prefix.
+pub struct CommandProvider;
+impl QueryProvider for CommandProvider {
+	fn query(&self, query: &str) -> Vec<QueryResult> {
+		let Some(command) = query.strip_prefix('>').map(str::trim) else {
+			return Vec::new();
+		};
+		if command.is_empty() {
+			return Vec::new();
+		}
+		vec![QueryResult {
+			title: command.to_string(),
+			subtitle: Some("Run command".to_string()),
+			ranges: Vec::new(),
+			score: SCORE_SYNTAX,
+			action: QueryAction::Run(command.to_string()),
+		}]
+	}
+}
+
+/// Opens paths starting with `/` or `~`, completing the last component from the filesystem.
+pub struct PathProvider;
+const PATH_COMPLETIONS: usize = 8;
+impl QueryProvider for PathProvider {
+	fn query(&self, query: &str) -> Vec<QueryResult> {
+		let path = match query.strip_prefix('~') {
+			Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+				let Some(home) = dirs::home_dir() else {
+					return Vec::new();
+				};
+				home.join(rest.trim_start_matches('/'))
+			}
+			_ if query.starts_with('/') => PathBuf::from(query),
+			_ => return Vec::new(),
+		};
+
+		let mut results = Vec::new();
+		if path.exists() {
+			results.push(path_result(&path, SCORE_SYNTAX));
+		}
+
+		// complete the last component from its parent directory
+		let (dir, prefix) = if query.ends_with('/') {
+			(path.as_path(), "")
+		} else {
+			match (path.parent(), path.file_name().and_then(|n| n.to_str())) {
+				(Some(dir), Some(prefix)) => (dir, prefix),
+				_ => return results,
+			}
+		};
+		let Ok(entries) = fs::read_dir(dir) else {
+			return results;
+		};
+		let mut completions = entries
+			.filter_map(|e| e.ok())
+			.map(|e| e.path())
+			.filter(|p| p != &path)
+			.filter_map(|p| {
+				let name = p.file_name()?.to_str()?;
+				// hidden files only when asked for
+				if name.starts_with('.') && !prefix.starts_with('.') {
+					return None;
+				}
+				let (score, _) =
+					match_text(name, prefix).or(prefix.is_empty().then(|| (0, vec![])))?;
+				Some((p, score))
+			})
+			.collect::<Vec<_>>();
+		completions.sort_by_key(|(p, score)| (std::cmp::Reverse(*score), p.clone()));
+		results.extend(
+			completions
+				.into_iter()
+				.take(PATH_COMPLETIONS)
+				.map(|(p, score)| path_result(&p, SCORE_SYNTAX / 2 + score)),
+		);
+		results
+	}
+}
+
+fn path_result(path: &Path, score: u32) -> QueryResult {
+	QueryResult {
+		title: path
+			.file_name()
+			.map(|n| n.to_string_lossy().to_string())
+			.unwrap_or_else(|| path.to_string_lossy().to_string()),
+		subtitle: Some(path.to_string_lossy().to_string()),
+		ranges: Vec::new(),
+		score,
+		action: QueryAction::Open(path.to_path_buf()),
+	}
+}
+
+/// Fuzzy searches the names of recently used files.
+pub struct RecentFilesProvider {
+	files: Vec<PathBuf>,
+}
+const RECENT_FILE_RESULTS: usize = 10;
+impl RecentFilesProvider {
+	/// Takes the local files from the recently used list, see [`recent_files`].
+	pub fn load() -> Self {
+		let files = recent_files()
+			.iter()
+			.filter_map(RecentFile::path)
+			.filter(|p| p.exists())
+			.collect();
+		RecentFilesProvider { files }
+	}
+}
+impl QueryProvider for RecentFilesProvider {
+	fn query(&self, query: &str) -> Vec<QueryResult> {
+		let mut results = self
+			.files
+			.iter()
+			.filter_map(|path| {
+				let name = path.file_name()?.to_str()?;
+				let (score, ranges) = match_text(name, query)?;
+				Some(QueryResult {
+					title: name.to_string(),
+					subtitle: Some(path.to_string_lossy().to_string()),
+					ranges,
+					// below an application that matches equally well
+					score: score * 80 / 100,
+					action: QueryAction::Open(path.clone()),
+				})
+			})
+			.collect::<Vec<_>>();
+		results.sort_by_key(|r| std::cmp::Reverse(r.score));
+		results.truncate(RECENT_FILE_RESULTS);
+		results
+	}
+}
+
+#[test]
+fn test_calculator() {
+	assert_eq!(evaluate("1 + 2 * 3"), Some(7.0));
+	assert_eq!(evaluate("(1 + 2) * 3"), Some(9.0));
+	assert_eq!(evaluate("2 ^ 3 ^ 2"), Some(512.0));
+	assert_eq!(evaluate("-4 / 2"), Some(-2.0));
+	assert_eq!(evaluate("-2^2"), Some(-4.0));
+	assert_eq!(evaluate("2^-1"), Some(0.5));
+	assert_eq!(evaluate("7 % 4"), Some(3.0));
+	assert_eq!(evaluate("1 / 0"), None);
+	assert_eq!(evaluate("1 +"), None);
+	assert_eq!(evaluate("firefox"), None);
+	assert_eq!(format_number(0.1 + 0.2), "0.3");
+
+	let results = CalculatorProvider.query("=2*21");
+	assert_eq!(results[0].title, "42");
+	assert!(CalculatorProvider.query("42").is_empty());
+}
+
+#[test]
+fn test_query_engine_merges_providers() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	fs::write(dir.path().join("notes.txt"), "").unwrap();
+	fs::write(dir.path().join("numbers.txt"), "").unwrap();
+
+	let engine = QueryEngine::new()
+		.provider(CommandProvider)
+		.provider(PathProvider);
+	let results = engine.query(&format!("{}/n", dir.path().display()));
+	assert_eq!(results.len(), 2);
+	assert!(matches!(results[0].action, QueryAction::Open(_)));
+
+	let results = engine.query("> echo hi");
+	assert_eq!(results.len(), 1);
+	assert!(matches!(&results[0].action, QueryAction::Run(c) if c == "echo hi"));
+
+	assert!(engine.query("   ").is_empty());
+}
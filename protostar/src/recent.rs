@@ -0,0 +1,238 @@
+use crate::watch::FileWatcher;
+use crate::xdg::file_uri_to_path;
+use lazy_static::lazy_static;
+use roxmltree::{Document, Node};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const BOOKMARK_NS: &str = "http://www.freedesktop.org/standards/desktop-bookmarks";
+const MIME_NS: &str = "http://www.freedesktop.org/standards/shared-mime-info";
+
+lazy_static! {
+	static ref RECENT_FILES: Mutex<SharedRecentFiles> =
+		Mutex::new(SharedRecentFiles::load(get_recent_files_path()));
+}
+
+pub fn get_recent_files_path() -> PathBuf {
+	dirs::data_dir()
+		.unwrap_or_else(|| dirs::home_dir().unwrap().join(".local/share"))
+		.join("recently-used.xbel")
+}
+
+/// One `<bookmark>` in `recently-used.xbel`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentFile {
+	pub uri: String,
+	pub mime_type: Option<String>,
+	/// Unix seconds of the last time any app touched this file.
+	pub modified: u64,
+	pub applications: Vec<RecentApplication>,
+}
+impl RecentFile {
+	/// The local path, `None` for remote URIs.
+	pub fn path(&self) -> Option<PathBuf> {
+		file_uri_to_path(&self.uri)
+	}
+
+	/// The last time the given app used this file.
+	pub fn application(&self, name: &str) -> Option<&RecentApplication> {
+		self.applications.iter().find(|a| a.name == name)
+	}
+}
+
+/// An app that opened a [`RecentFile`], as registered by the app itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentApplication {
+	/// Usually the program name or the app ID, there's no standard.
+	pub name: String,
+	/// Command line the file was opened with, like `gedit %u`.
+	pub exec: String,
+	pub modified: u64,
+	pub count: u32,
+}
+impl RecentApplication {
+	/// The program the file was opened with, without path.
+	pub fn executable(&self) -> Option<&str> {
+		let program = self.exec.split_whitespace().next()?;
+		Some(program.rsplit('/').next().unwrap_or(program))
+	}
+}
+
+/// Parses an XBEL document, most recently modified files first.
+pub fn parse_recent_files(xbel: &str) -> Result<Vec<RecentFile>, String> {
+	let document = Document::parse(xbel).map_err(|e| format!("Invalid recent files: {e}"))?;
+	let mut files = document
+		.root_element()
+		.children()
+		.filter(|n| n.has_tag_name("bookmark"))
+		.filter_map(parse_bookmark)
+		.collect::<Vec<_>>();
+	files.sort_by_key(|f| std::cmp::Reverse(f.modified));
+	Ok(files)
+}
+
+fn parse_bookmark(node: Node) -> Option<RecentFile> {
+	let uri = node.attribute("href")?.to_string();
+	let metadata = node
+		.children()
+		.filter(|n| n.has_tag_name("info"))
+		.flat_map(|info| info.children())
+		.find(|n| n.has_tag_name("metadata"));
+	let mime_type = metadata
+		.and_then(|m| {
+			m.children()
+				.find(|n| n.has_tag_name((MIME_NS, "mime-type")))
+		})
+		.and_then(|n| n.attribute("type"))
+		.map(str::to_string);
+	let applications = metadata
+		.and_then(|m| {
+			m.children()
+				.find(|n| n.has_tag_name((BOOKMARK_NS, "applications")))
+		})
+		.map(|apps| {
+			apps.children()
+				.filter(|n| n.has_tag_name((BOOKMARK_NS, "application")))
+				.filter_map(|app| {
+					Some(RecentApplication {
+						name: app.attribute("name")?.to_string(),
+						// the spec has exec quoted as one shell word, which nobody needs here
+						exec: app
+							.attribute("exec")
+							.unwrap_or_default()
+							.trim_matches('\'')
+							.to_string(),
+						modified: app
+							.attribute("modified")
+							.and_then(parse_timestamp)
+							.unwrap_or_default(),
+						count: app
+							.attribute("count")
+							.and_then(|c| c.parse().ok())
+							.unwrap_or(1),
+					})
+				})
+				.collect()
+		})
+		.unwrap_or_default();
+	let modified = ["modified", "visited", "added"]
+		.iter()
+		.filter_map(|attr| node.attribute(*attr).and_then(parse_timestamp))
+		.max()
+		.unwrap_or_default();
+
+	Some(RecentFile {
+		uri,
+		mime_type,
+		modified,
+		applications,
+	})
+}
+
+/// Parses the UTC ISO 8601 timestamps XBEL uses, like `2024-03-01T12:30:00.123456Z`, into unix seconds.
+fn parse_timestamp(text: &str) -> Option<u64> {
+	let (date, time) = text.trim_end_matches('Z').split_once('T')?;
+	let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+	let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+	let mut time = time.splitn(3, ':');
+	let hour = time.next()?.parse::<i64>().ok()?;
+	let minute = time.next()?.parse::<i64>().ok()?;
+	let second = time.next()?.split('.').next()?.parse::<i64>().ok()?;
+	if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+		return None;
+	}
+
+	// days since the epoch, from Howard Hinnant's `days_from_civil`
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	let days = era * 146097 + day_of_era - 719468;
+
+	u64::try_from(days * 86400 + hour * 3600 + minute * 60 + second).ok()
+}
+
+struct SharedRecentFiles {
+	path: PathBuf,
+	files: Arc<Vec<RecentFile>>,
+	/// `None` if inotify isn't available, then the list is only read once.
+	watcher: Option<FileWatcher>,
+}
+impl SharedRecentFiles {
+	fn load(path: PathBuf) -> Self {
+		let watcher = FileWatcher::new(&path).ok();
+		SharedRecentFiles {
+			files: Arc::new(read_recent_files(&path)),
+			path,
+			watcher,
+		}
+	}
+
+	fn refresh(&mut self) -> Arc<Vec<RecentFile>> {
+		if self.watcher.as_mut().is_some_and(FileWatcher::changed) {
+			self.files = Arc::new(read_recent_files(&self.path));
+		}
+		self.files.clone()
+	}
+}
+
+fn read_recent_files(path: &Path) -> Vec<RecentFile> {
+	match fs::read_to_string(path) {
+		Ok(xbel) => parse_recent_files(&xbel).unwrap_or_else(|e| {
+			tracing::warn!("{e}");
+			Vec::new()
+		}),
+		Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+		Err(e) => {
+			tracing::warn!("Failed to read {}: {e}", path.display());
+			Vec::new()
+		}
+	}
+}
+
+/// Every recently used file, most recent first, reloaded whenever another app adds to the list.
+pub fn recent_files() -> Arc<Vec<RecentFile>> {
+	RECENT_FILES.lock().unwrap().refresh()
+}
+
+#[test]
+fn test_parse_recent_files() {
+	let files = parse_recent_files(
+		r#"<?xml version="1.0" encoding="UTF-8"?>
+<xbel version="1.0"
+      xmlns:bookmark="http://www.freedesktop.org/standards/desktop-bookmarks"
+      xmlns:mime="http://www.freedesktop.org/standards/shared-mime-info">
+  <bookmark href="file:///home/user/notes.txt" added="2024-03-01T12:00:00Z" modified="2024-03-01T12:30:00.123456Z" visited="2024-03-01T12:00:00Z">
+    <info>
+      <metadata owner="http://freedesktop.org">
+        <mime:mime-type type="text/plain"/>
+        <bookmark:applications>
+          <bookmark:application name="gedit" exec="&apos;gedit %u&apos;" modified="2024-03-01T12:30:00Z" count="3"/>
+        </bookmark:applications>
+      </metadata>
+    </info>
+  </bookmark>
+  <bookmark href="file:///home/user/Old%20Photo.png" added="1970-01-02T00:00:00Z" modified="1970-01-02T00:00:00Z" visited="1970-01-02T00:00:00Z"/>
+</xbel>"#,
+	)
+	.unwrap();
+
+	assert_eq!(files.len(), 2);
+	assert_eq!(files[0].uri, "file:///home/user/notes.txt");
+	assert_eq!(files[0].mime_type.as_deref(), Some("text/plain"));
+	assert_eq!(files[0].modified, 1709296200);
+	let gedit = files[0].application("gedit").unwrap();
+	assert_eq!(gedit.exec, "gedit %u");
+	assert_eq!(gedit.executable(), Some("gedit"));
+	assert_eq!(gedit.count, 3);
+
+	assert_eq!(files[1].modified, 86400);
+	assert_eq!(
+		files[1].path(),
+		Some(PathBuf::from("/home/user/Old Photo.png"))
+	);
+	assert!(files[1].applications.is_empty());
+}
@@ -0,0 +1,151 @@
+use crate::application::Application;
+use crate::collate;
+use crate::config::config;
+use crate::index::get_indexed_desktop_files;
+use crate::xdg::{AppSource, DesktopFile};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// All the applications a launcher shows, de-duplicated and sorted by name.
+#[derive(Debug, Clone, Default)]
+pub struct AppRegistry {
+	apps: Vec<Application>,
+	ids: HashMap<String, usize>,
+}
+impl AppRegistry {
+	/// Builds a registry from the desktop files in the XDG data dirs.
+	/// Apps hidden or from sources disabled in the config are left out.
+	pub fn discover() -> Self {
+		let config = config();
+		Self::new(
+			get_indexed_desktop_files()
+				.into_iter()
+				.filter(|d| !config.is_hidden(d)),
+		)
+	}
+
+	/// Builds a registry from every desktop file under `dir`.
+	pub fn from_dir(dir: impl AsRef<Path>) -> Self {
+		let config = config();
+		Self::new(
+			WalkDir::new(dir)
+				.follow_links(true)
+				.into_iter()
+				.filter_map(|entry| entry.ok())
+				.map(|entry| entry.into_path())
+				.filter(|path| path.is_file() && path.extension().is_some_and(|e| e == "desktop"))
+				.filter_map(|path| DesktopFile::parse(path).ok())
+				.filter(|d| !config.is_hidden(d)),
+		)
+	}
+
+	pub fn new(desktop_files: impl IntoIterator<Item = DesktopFile>) -> Self {
+		let mut apps = desktop_files
+			.into_iter()
+			.filter_map(|d| Application::create(d).ok())
+			.collect::<Vec<_>>();
+
+		// Prefer user overrides over system entries over sandboxed ones
+		apps.sort_by_key(|app| app.source());
+
+		let mut seen_ids = HashSet::new();
+		let mut seen_entries = HashMap::new();
+		apps.retain(|app| {
+			if !seen_ids.insert(app.id()) {
+				return false;
+			}
+			// The same app packaged both natively and as a flatpak or snap
+			let entry = (
+				app.name().map(collate::fold),
+				app.desktop_file().icon.clone(),
+			);
+			match seen_entries.get(&entry) {
+				Some(source) if *source != app.source() && entry.0.is_some() => false,
+				_ => {
+					seen_entries.insert(entry, app.source());
+					true
+				}
+			}
+		});
+
+		apps.sort_by_cached_key(|app| {
+			(collate::sort_key(app.name().unwrap_or_default()), app.id())
+		});
+		let ids = apps
+			.iter()
+			.enumerate()
+			.map(|(i, app)| (app.id(), i))
+			.collect();
+
+		AppRegistry { apps, ids }
+	}
+
+	pub fn len(&self) -> usize {
+		self.apps.len()
+	}
+	pub fn is_empty(&self) -> bool {
+		self.apps.is_empty()
+	}
+
+	/// All applications, sorted by name.
+	pub fn apps(&self) -> &[Application] {
+		&self.apps
+	}
+	pub fn iter(&self) -> impl Iterator<Item = &Application> {
+		self.apps.iter()
+	}
+	pub fn into_apps(self) -> Vec<Application> {
+		self.apps
+	}
+
+	/// Looks up an application by its desktop file ID, e.g. `org.gnome.Nautilus.desktop`.
+	pub fn get(&self, id: &str) -> Option<&Application> {
+		self.ids.get(id).map(|i| &self.apps[*i])
+	}
+
+	pub fn by_category<'a>(&'a self, category: &'a str) -> impl Iterator<Item = &'a Application> {
+		self.apps
+			.iter()
+			.filter(move |app| app.categories().iter().any(|c| c == category))
+	}
+
+	pub fn by_source(&self, source: AppSource) -> impl Iterator<Item = &Application> {
+		self.apps.iter().filter(move |app| app.source() == source)
+	}
+}
+
+#[test]
+fn test_registry_dedup_and_sort() {
+	use std::fs;
+
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let write = |path: &str, name: &str, icon: &str| {
+		let path = dir.path().join(path);
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(
+			path,
+			format!("[Desktop Entry]\nName={name}\nExec=test\nIcon={icon}\nCategories=Game;"),
+		)
+		.unwrap();
+	};
+	write("b.desktop", "Beta", "b");
+	write("a.desktop", "alpha", "a");
+	write("c.desktop", "éclair", "c");
+	write("flatpak/exports/org.example.Eclair.desktop", "Éclair", "c");
+	fs::write(
+		dir.path().join("hidden.desktop"),
+		"[Desktop Entry]\nName=Hidden\nNoDisplay=true",
+	)
+	.unwrap();
+
+	let registry = AppRegistry::from_dir(dir.path());
+	let names = registry
+		.iter()
+		.map(|app| app.name().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(names, ["alpha", "Beta", "éclair"]);
+	assert!(registry.get("a.desktop").is_some());
+	assert_eq!(registry.by_category("Game").count(), 3);
+	assert_eq!(registry.by_source(AppSource::Flatpak).count(), 0);
+}
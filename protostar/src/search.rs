@@ -0,0 +1,368 @@
+use crate::application::Application;
+use crate::collate::{self, fold_char};
+use std::ops::Range;
+
+const SCORE_MATCH: i32 = 16;
+const BONUS_WORD_START: i32 = 8;
+const BONUS_PREFIX: i32 = 12;
+const BONUS_CONSECUTIVE: i32 = 8;
+const PENALTY_GAP: i32 = 1;
+const BONUS_EXACT: i32 = 32;
+
+/// Which part of an application's desktop file a query matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchField {
+	Name,
+	GenericName,
+	Keyword,
+	Executable,
+	Action,
+	Category,
+}
+impl MatchField {
+	/// How much a match in this field counts compared to one in the name, in percent.
+	fn weight(self) -> i32 {
+		match self {
+			MatchField::Name => 100,
+			MatchField::GenericName => 75,
+			MatchField::Keyword => 65,
+			MatchField::Executable => 60,
+			MatchField::Action => 50,
+			MatchField::Category => 40,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+	/// Position of the application in the list the [`SearchIndex`] was built from.
+	pub index: usize,
+	pub score: u32,
+	pub field: MatchField,
+	/// The text that matched, e.g. the name or one of the keywords.
+	pub text: String,
+	/// Byte ranges of `text` that matched the query, for highlighting.
+	pub ranges: Vec<Range<usize>>,
+}
+
+/// Buffers reused between alignments, so searching doesn't allocate per field.
+#[derive(Default)]
+struct Scratch {
+	scores: Vec<Option<i32>>,
+	previous: Vec<usize>,
+}
+
+/// A searchable string, folded ahead of time so each keystroke only has to fold the query.
+#[derive(Debug, Clone)]
+struct Field {
+	kind: MatchField,
+	text: String,
+	folded: Vec<char>,
+	/// Byte range in `text` of the original char each folded char came from.
+	origins: Vec<Range<usize>>,
+	word_starts: Vec<bool>,
+}
+impl Field {
+	fn new(kind: MatchField, text: &str) -> Self {
+		let mut folded = Vec::with_capacity(text.len());
+		let mut origins = Vec::with_capacity(text.len());
+		let mut word_starts = Vec::with_capacity(text.len());
+
+		let mut prev: Option<char> = None;
+		for (i, c) in text.char_indices() {
+			let word_start = match prev {
+				None => true,
+				Some(prev) => {
+					!prev.is_alphanumeric() && c.is_alphanumeric()
+						|| prev.is_lowercase() && c.is_uppercase()
+						|| !prev.is_ascii_digit() && c.is_ascii_digit()
+				}
+			};
+			for (n, f) in fold_char(c).enumerate() {
+				folded.push(f);
+				origins.push(i..i + c.len_utf8());
+				word_starts.push(word_start && n == 0);
+			}
+			prev = Some(c);
+		}
+
+		Field {
+			kind,
+			text: text.to_string(),
+			folded,
+			origins,
+			word_starts,
+		}
+	}
+
+	fn bonus(&self, j: usize) -> i32 {
+		SCORE_MATCH
+			+ match j {
+				0 => BONUS_PREFIX + BONUS_WORD_START,
+				_ if self.word_starts[j] => BONUS_WORD_START,
+				_ => 0,
+			}
+	}
+
+	/// Finds the best scoring alignment of `query` in this field, if all its chars appear in order.
+	/// Returns the score and the position in the folded text of the last matched char.
+	fn align(&self, query: &[char], scratch: &mut Scratch) -> Option<(i32, usize)> {
+		let (m, n) = (query.len(), self.folded.len());
+		if m == 0 || m > n {
+			return None;
+		}
+
+		// cheap subsequence check before doing the full alignment
+		let mut remaining = self.folded.iter();
+		if !query.iter().all(|q| remaining.any(|c| c == q)) {
+			return None;
+		}
+
+		// scores[i * n + j]: best score with query[i] matched at folded[j]
+		let scores = &mut scratch.scores;
+		let previous = &mut scratch.previous;
+		scores.clear();
+		scores.resize(m * n, None);
+		previous.clear();
+		previous.resize(m * n, 0);
+
+		for (j, c) in self.folded.iter().enumerate() {
+			if *c == query[0] {
+				scores[j] = Some(self.bonus(j));
+			}
+		}
+		for i in 1..m {
+			// best (score + gap penalty offset, position) of query[i - 1] at k <= j - 2
+			let mut best_gapped: Option<(i32, usize)> = None;
+			for j in i..n {
+				if j >= 2
+					&& let Some(score) = scores[(i - 1) * n + j - 2]
+				{
+					let candidate = score + PENALTY_GAP * (j - 2) as i32;
+					if best_gapped.is_none_or(|(best, _)| candidate > best) {
+						best_gapped = Some((candidate, j - 2));
+					}
+				}
+				if self.folded[j] != query[i] {
+					continue;
+				}
+
+				let consecutive =
+					scores[(i - 1) * n + j - 1].map(|s| (s + BONUS_CONSECUTIVE, j - 1));
+				let gapped = best_gapped.map(|(s, k)| (s - PENALTY_GAP * (j - 1) as i32, k));
+				let best = match (consecutive, gapped) {
+					(Some(c), Some(g)) if g.0 > c.0 => Some(g),
+					(Some(c), _) => Some(c),
+					(None, g) => g,
+				};
+				if let Some((score, k)) = best {
+					scores[i * n + j] = Some(score + self.bonus(j));
+					previous[i * n + j] = k;
+				}
+			}
+		}
+
+		let (j, mut score) = (0..n)
+			.filter_map(|j| Some((j, scores[(m - 1) * n + j]?)))
+			.max_by_key(|(j, score)| (*score, std::cmp::Reverse(*j)))?;
+		if m == n {
+			score += BONUS_EXACT;
+		}
+		Some((score, j))
+	}
+
+	/// Walks back through the last [`Field::align`] to find where each query char matched.
+	fn positions(&self, query_len: usize, last: usize, scratch: &Scratch) -> Vec<usize> {
+		let n = self.folded.len();
+		let mut positions = vec![0; query_len];
+		let mut j = last;
+		for i in (0..query_len).rev() {
+			positions[i] = j;
+			j = scratch.previous[i * n + j];
+		}
+		positions
+	}
+
+	/// Turns matched folded positions into merged byte ranges of the original text.
+	fn ranges(&self, positions: &[usize]) -> Vec<Range<usize>> {
+		let mut ranges: Vec<Range<usize>> = Vec::new();
+		for origin in positions.iter().map(|p| self.origins[*p].clone()) {
+			match ranges.last_mut() {
+				Some(last) if last.end >= origin.start => last.end = last.end.max(origin.end),
+				_ => ranges.push(origin),
+			}
+		}
+		ranges
+	}
+}
+
+/// Pre-processed searchable text for a list of applications.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+	/// Fields of each application, and its position when sorted by name to break ties.
+	apps: Vec<(usize, Vec<Field>)>,
+}
+impl SearchIndex {
+	pub fn new<'a>(apps: impl IntoIterator<Item = &'a Application>) -> Self {
+		let apps = apps.into_iter().collect::<Vec<_>>();
+		let mut by_name = (0..apps.len()).collect::<Vec<_>>();
+		by_name.sort_by_cached_key(|i| collate::sort_key(apps[*i].name().unwrap_or_default()));
+		let mut name_ranks = vec![0; apps.len()];
+		for (rank, i) in by_name.into_iter().enumerate() {
+			name_ranks[i] = rank;
+		}
+
+		let apps = apps
+			.into_iter()
+			.zip(name_ranks)
+			.map(|(app, name_rank)| {
+				let fields = app
+					.name()
+					.map(|n| Field::new(MatchField::Name, n))
+					.into_iter()
+					.chain(
+						app.generic_name()
+							.map(|n| Field::new(MatchField::GenericName, n)),
+					)
+					.chain(
+						app.keywords()
+							.iter()
+							.map(|k| Field::new(MatchField::Keyword, k)),
+					)
+					.chain(
+						app.executable()
+							.map(|e| Field::new(MatchField::Executable, e)),
+					)
+					.chain(
+						app.actions()
+							.iter()
+							.filter_map(|a| a.name.as_deref())
+							.map(|n| Field::new(MatchField::Action, n)),
+					)
+					.chain(
+						app.categories()
+							.iter()
+							.map(|c| Field::new(MatchField::Category, c)),
+					)
+					.collect();
+				(name_rank, fields)
+			})
+			.collect();
+		SearchIndex { apps }
+	}
+
+	/// Ranks all applications against `query`, best match first.
+	/// Applications that don't match at all are left out, so an empty query returns nothing.
+	pub fn search(&self, query: &str) -> Vec<SearchResult> {
+		let query = fold_query(query);
+
+		let mut scratch = Scratch::default();
+		let mut results = self
+			.apps
+			.iter()
+			.enumerate()
+			.filter_map(|(index, (_, fields))| {
+				let (field, score) = fields
+					.iter()
+					.filter_map(|field| {
+						let (score, _) = field.align(&query, &mut scratch)?;
+						Some((field, score * field.kind.weight() / 100))
+					})
+					.max_by_key(|(field, score)| (*score, std::cmp::Reverse(field.text.len())))?;
+
+				// only the winning field needs its match positions
+				let (_, last) = field.align(&query, &mut scratch)?;
+				let positions = field.positions(query.len(), last, &scratch);
+				Some(SearchResult {
+					index,
+					score: score.max(0) as u32,
+					field: field.kind,
+					text: field.text.clone(),
+					ranges: field.ranges(&positions),
+				})
+			})
+			.collect::<Vec<_>>();
+
+		results.sort_by_key(|r| {
+			(
+				std::cmp::Reverse(r.score),
+				r.text.len(),
+				self.apps[r.index].0,
+			)
+		});
+		results
+	}
+}
+
+/// Fuzzy matches a single string, for searching things that aren't applications.
+/// Returns the score and the byte ranges of `text` that matched.
+pub fn match_text(text: &str, query: &str) -> Option<(u32, Vec<Range<usize>>)> {
+	let query = fold_query(query);
+	let field = Field::new(MatchField::Name, text);
+	let mut scratch = Scratch::default();
+	let (score, last) = field.align(&query, &mut scratch)?;
+	let positions = field.positions(query.len(), last, &scratch);
+	Some((score.max(0) as u32, field.ranges(&positions)))
+}
+
+fn fold_query(query: &str) -> Vec<char> {
+	query
+		.chars()
+		.filter(|c| !c.is_whitespace())
+		.flat_map(fold_char)
+		.collect()
+}
+
+/// Convenience for searching a list once, build a [`SearchIndex`] when searching on every keystroke.
+pub fn search(apps: &[Application], query: &str) -> Vec<SearchResult> {
+	SearchIndex::new(apps).search(query)
+}
+
+#[test]
+#[allow(clippy::single_range_in_vec_init)]
+fn test_search_ranking() {
+	use crate::xdg::DesktopFile;
+	use std::fs;
+
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let apps = [
+		"Name=Firefox\nGenericName=Web Browser\nExec=firefox %u\nKeywords=internet;www;",
+		"Name=Thunderbird\nGenericName=Mail Client\nExec=thunderbird",
+		"Name=Éditeur de texte\nExec=gnome-text-editor\nCategories=Utility;TextEditor;",
+		"Name=Files\nExec=nautilus\nActions=new-window;\n\n[Desktop Action new-window]\nName=Open a New Window",
+	]
+	.iter()
+	.enumerate()
+	.map(|(i, data)| {
+		let file = dir.path().join(format!("{i}.desktop"));
+		fs::write(&file, format!("[Desktop Entry]\n{data}")).unwrap();
+		Application::create(DesktopFile::parse(file).unwrap()).unwrap()
+	})
+	.collect::<Vec<_>>();
+	let index = SearchIndex::new(&apps);
+
+	// prefix of the name beats a scattered match
+	let results = index.search("fi");
+	assert_eq!(results[0].index, 3);
+	assert_eq!(results[0].ranges, [0..2]);
+	assert_eq!(results[1].index, 0);
+
+	// diacritics and case are folded, ranges point into the original text
+	let results = index.search("edit");
+	assert_eq!(results[0].index, 2);
+	assert_eq!(results[0].field, MatchField::Name);
+	assert_eq!(results[0].ranges, [0..5]);
+	assert_eq!(&results[0].text[results[0].ranges[0].clone()], "Édit");
+
+	// word starts in other fields
+	let results = index.search("web br");
+	assert_eq!(results[0].index, 0);
+	assert_eq!(results[0].field, MatchField::GenericName);
+	assert_eq!(results[0].ranges, [0..3, 4..6]);
+	assert_eq!(index.search("www")[0].field, MatchField::Keyword);
+	assert_eq!(index.search("nautilus")[0].field, MatchField::Executable);
+	assert_eq!(index.search("new window")[0].field, MatchField::Action);
+
+	assert!(index.search("xyz").is_empty());
+	assert!(index.search("").is_empty());
+}
@@ -0,0 +1,95 @@
+use crate::config::Color;
+use std::path::Path;
+
+/// GTK's colors for the state classes symbolic icons can use, from the Adwaita palette.
+const SUCCESS_COLOR: &str = "#33d17a";
+const WARNING_COLOR: &str = "#f5c211";
+const ERROR_COLOR: &str = "#e01b24";
+/// Colors symbolic icons get drawn in, which GTK replaces with the foreground color too.
+const PLACEHOLDER_COLORS: [&str; 4] = ["#2e3436", "#222222", "#bebebe", "#474747"];
+
+/// Symbolic icons are single color glyphs meant to be drawn in the text color, named `*-symbolic`.
+pub fn is_symbolic(path: &Path) -> bool {
+	path.file_stem()
+		.and_then(|stem| stem.to_str())
+		.is_some_and(|stem| stem.ends_with("-symbolic"))
+}
+
+/// `#rrggbb` in sRGB, SVG renderers don't all take an alpha channel.
+pub fn svg_color(color: Color) -> String {
+	let mut hex = String::from(color);
+	hex.truncate(7);
+	hex
+}
+
+/// Recolors a symbolic SVG the way GTK does, so it can be rendered with any SVG renderer.
+/// `currentColor` and the placeholder colors become `foreground`, and the `success`, `warning` and
+/// `error` style classes get their state colors.
+pub fn recolor_symbolic(svg: &str, foreground: &str) -> String {
+	let mut svg = svg.replace("currentColor", foreground);
+	for placeholder in PLACEHOLDER_COLORS {
+		svg = replace_ignore_ascii_case(&svg, placeholder, foreground);
+	}
+	let style = format!(
+		"<style>\
+			.foreground-fill{{fill:{foreground}}}\
+			.foreground-stroke{{stroke:{foreground}}}\
+			.success{{fill:{SUCCESS_COLOR}}}\
+			.warning{{fill:{WARNING_COLOR}}}\
+			.error{{fill:{ERROR_COLOR}}}\
+		</style>"
+	);
+	// right after the root element opens, so it applies to the whole document
+	let Some(root_start) = svg.find("<svg") else {
+		return svg;
+	};
+	let Some(root_end) = svg[root_start..].find('>').map(|i| root_start + i + 1) else {
+		return svg;
+	};
+	// a self closing root has nothing to style
+	if svg[..root_end].ends_with("/>") {
+		return svg;
+	}
+	svg.insert_str(root_end, &style);
+	svg
+}
+
+fn replace_ignore_ascii_case(text: &str, from: &str, to: &str) -> String {
+	let lower = text.to_ascii_lowercase();
+	let mut result = String::with_capacity(text.len());
+	let mut last = 0;
+	for (start, _) in lower.match_indices(from) {
+		result.push_str(&text[last..start]);
+		result.push_str(to);
+		last = start + from.len();
+	}
+	result.push_str(&text[last..]);
+	result
+}
+
+#[test]
+fn test_recolor_symbolic() {
+	assert!(is_symbolic(Path::new("/icons/mail-send-symbolic.svg")));
+	assert!(!is_symbolic(Path::new("/icons/mail-send.svg")));
+	assert_eq!(
+		svg_color(Color::try_from("#ff8000".to_string()).unwrap()),
+		"#ff8000"
+	);
+
+	let svg = r##"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+	<path d="M0 0h8v8H0z" fill="#2E3436"/>
+	<path d="M8 8h8v8H8z" style="fill:currentColor"/>
+	<circle class="error" cx="4" cy="12" r="4"/>
+</svg>"##;
+	let recolored = recolor_symbolic(svg, "#ffffff");
+	assert!(!recolored.contains("#2E3436"));
+	assert!(!recolored.contains("currentColor"));
+	assert_eq!(recolored.matches("#ffffff").count(), 4);
+	assert!(recolored.contains(
+		"height=\"16\"><style>.foreground-fill{fill:#ffffff}.foreground-stroke{stroke:#ffffff}"
+	));
+	assert!(recolored.contains(".error{fill:#e01b24}"));
+
+	assert_eq!(recolor_symbolic("<svg/>", "#ffffff"), "<svg/>");
+}
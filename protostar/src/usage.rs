@@ -0,0 +1,178 @@
+use crate::application::Application;
+use crate::index::write_atomic;
+use crate::xdg::get_state_home;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long it takes for a launch to count half as much.
+const HALF_LIFE_SECS: f64 = 3.0 * 24.0 * 60.0 * 60.0;
+/// Older launches barely move the score, so only this many are kept per app.
+const MAX_LAUNCHES: usize = 32;
+
+lazy_static! {
+	static ref USAGE: Mutex<UsageStore> = Mutex::new(UsageStore::load(get_usage_path()));
+}
+
+pub fn get_usage_path() -> PathBuf {
+	get_state_home().join("protostar").join("usage.toml")
+}
+
+fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct UsageFile {
+	/// Launch times in unix seconds, keyed by desktop file ID.
+	launches: HashMap<String, Vec<u64>>,
+}
+
+/// Launch history of apps, used to rank the ones used often and recently first.
+#[derive(Debug)]
+pub struct UsageStore {
+	path: PathBuf,
+	launches: HashMap<String, Vec<u64>>,
+}
+impl UsageStore {
+	/// Loads the store, starting fresh if it's missing or unreadable.
+	pub fn load(path: PathBuf) -> Self {
+		let launches = fs::read_to_string(&path)
+			.ok()
+			.and_then(|text| toml::de::from_str::<UsageFile>(&text).ok())
+			.unwrap_or_default()
+			.launches;
+		UsageStore { path, launches }
+	}
+
+	pub fn record(&mut self, id: &str, timestamp: u64) {
+		let launches = self.launches.entry(id.to_string()).or_default();
+		launches.push(timestamp);
+		launches.sort_unstable();
+		if launches.len() > MAX_LAUNCHES {
+			launches.drain(..launches.len() - MAX_LAUNCHES);
+		}
+	}
+
+	pub fn launch_count(&self, id: &str) -> usize {
+		self.launches.get(id).map_or(0, Vec::len)
+	}
+
+	/// Every launch adds up to 1, decaying exponentially with its age.
+	pub fn frecency(&self, id: &str) -> f64 {
+		self.frecency_at(id, now())
+	}
+
+	fn frecency_at(&self, id: &str, now: u64) -> f64 {
+		self.launches.get(id).map_or(0.0, |launches| {
+			launches
+				.iter()
+				.map(|t| {
+					let age = now.saturating_sub(*t) as f64;
+					0.5_f64.powf(age / HALF_LIFE_SECS)
+				})
+				.sum()
+		})
+	}
+
+	/// Sorts apps by descending frecency, apps that were never launched keep their order at the end.
+	pub fn sort_by_frecency(&self, apps: &mut [Application]) {
+		let now = now();
+		// stable, so ties keep their order. Scores are never negative, so their bits sort like they do
+		apps.sort_by_cached_key(|app| Reverse(self.frecency_at(&app.id(), now).to_bits()));
+	}
+
+	/// The `n` apps with the highest frecency, leaving out ones that were never launched.
+	pub fn top_n<'a>(&self, apps: &'a [Application], n: usize) -> Vec<&'a Application> {
+		let now = now();
+		let mut scored = apps
+			.iter()
+			.map(|app| (self.frecency_at(&app.id(), now), app))
+			.filter(|(score, _)| *score > 0.0)
+			.collect::<Vec<_>>();
+		scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+		scored.into_iter().take(n).map(|(_, app)| app).collect()
+	}
+
+	/// Writes the store to disk, replacing the old one atomically.
+	pub fn save(&self) -> std::io::Result<()> {
+		let file = UsageFile {
+			launches: self.launches.clone(),
+		};
+		let text = toml::ser::to_string(&file)
+			.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		write_atomic(&self.path, text)
+	}
+}
+
+/// Records a launch of the app with this desktop file ID in the shared store under `XDG_STATE_HOME`.
+pub fn record_launch(id: &str) -> std::io::Result<()> {
+	let mut usage = USAGE.lock().unwrap();
+	// another launcher may have recorded launches since we loaded
+	*usage = UsageStore::load(usage.path.clone());
+	usage.record(id, now());
+	usage.save()
+}
+
+/// Sorts apps by how often and how recently they were launched, see [`UsageStore::sort_by_frecency`].
+pub fn sort_by_frecency(apps: &mut [Application]) {
+	USAGE.lock().unwrap().sort_by_frecency(apps)
+}
+
+/// The `n` most used apps, see [`UsageStore::top_n`].
+pub fn top_n(apps: &[Application], n: usize) -> Vec<&Application> {
+	USAGE.lock().unwrap().top_n(apps, n)
+}
+
+#[test]
+fn test_frecency() {
+	use crate::xdg::DesktopFile;
+
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let apps = ["alpha", "beta", "gamma", "delta"]
+		.iter()
+		.map(|name| {
+			let file = dir.path().join(format!("{name}.desktop"));
+			fs::write(&file, format!("[Desktop Entry]\nName={name}")).unwrap();
+			Application::create(DesktopFile::parse(file).unwrap()).unwrap()
+		})
+		.collect::<Vec<_>>();
+
+	let path = dir.path().join("usage.toml");
+	let mut store = UsageStore::load(path.clone());
+	let now = now();
+	let day = 24 * 60 * 60;
+	// one launch today beats two launches a month ago
+	store.record("gamma.desktop", now);
+	store.record("beta.desktop", now - 30 * day);
+	store.record("beta.desktop", now - 31 * day);
+	assert!(store.frecency_at("gamma.desktop", now) > store.frecency_at("beta.desktop", now));
+	assert_eq!(store.frecency_at("alpha.desktop", now), 0.0);
+	store.save().unwrap();
+
+	let store = UsageStore::load(path);
+	assert_eq!(store.launch_count("beta.desktop"), 2);
+
+	let mut sorted = apps.clone();
+	store.sort_by_frecency(&mut sorted);
+	let names = sorted.iter().map(|a| a.name().unwrap()).collect::<Vec<_>>();
+	assert_eq!(names, ["gamma", "beta", "alpha", "delta"]);
+
+	let top = store
+		.top_n(&apps, 5)
+		.iter()
+		.map(|a| a.name().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(top, ["gamma", "beta"]);
+}
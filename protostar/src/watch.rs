@@ -0,0 +1,106 @@
+use inotify::{Inotify, WatchMask};
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Tells when a file has been written, replaced or removed, without blocking.
+///
+/// The parent directory is watched rather than the file itself,
+/// so saves that write a temporary file and rename it over the old one are noticed too.
+#[derive(Debug)]
+pub struct FileWatcher {
+	inotify: Inotify,
+	file_name: OsString,
+	buffer: Vec<u8>,
+}
+impl FileWatcher {
+	/// Creates the parent directory if needed, as it has to exist to be watched.
+	pub fn new(path: &Path) -> std::io::Result<Self> {
+		let parent = path
+			.parent()
+			.map(Path::to_path_buf)
+			.unwrap_or_else(|| PathBuf::from("/"));
+		let file_name = path
+			.file_name()
+			.ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "path has no file name"))?
+			.to_os_string();
+		fs::create_dir_all(&parent)?;
+
+		let inotify = Inotify::init()?;
+		inotify.watches().add(
+			&parent,
+			WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE | WatchMask::DELETE,
+		)?;
+		Ok(FileWatcher {
+			inotify,
+			file_name,
+			buffer: vec![0; 4096],
+		})
+	}
+
+	/// Whether the file changed since the last call, draining all pending events.
+	pub fn changed(&mut self) -> bool {
+		let mut changed = false;
+		// stops at `WouldBlock`, once there's nothing left to read
+		while let Ok(events) = self.inotify.read_events(&mut self.buffer) {
+			let mut any = false;
+			for event in events {
+				any = true;
+				changed |= event.name == Some(self.file_name.as_os_str());
+			}
+			if !any {
+				break;
+			}
+		}
+		changed
+	}
+
+	/// Blocks until the file changes.
+	fn wait(&mut self) -> std::io::Result<()> {
+		loop {
+			let events = self.inotify.read_events_blocking(&mut self.buffer)?;
+			if events
+				.into_iter()
+				.any(|event| event.name == Some(self.file_name.as_os_str()))
+			{
+				return Ok(());
+			}
+		}
+	}
+
+	/// Calls `reload` on a thread of its own every time the file changes.
+	pub fn spawn(mut self, reload: impl Fn() + Send + 'static) -> std::io::Result<()> {
+		let name = format!("watch {}", self.file_name.to_string_lossy());
+		std::thread::Builder::new().name(name).spawn(move || {
+			while self.wait().is_ok() {
+				reload();
+			}
+		})?;
+		Ok(())
+	}
+}
+
+#[test]
+fn test_file_watcher() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let path = dir.path().join("watched.toml");
+	let mut watcher = FileWatcher::new(&path).unwrap();
+	assert!(!watcher.changed());
+
+	fs::write(dir.path().join("other.toml"), "").unwrap();
+	assert!(!watcher.changed());
+
+	let tmp_path = dir.path().join("watched.toml.tmp");
+	fs::write(&tmp_path, "a = 1").unwrap();
+	fs::rename(&tmp_path, &path).unwrap();
+	assert!(watcher.changed());
+	assert!(!watcher.changed());
+
+	let (sender, receiver) = std::sync::mpsc::channel();
+	watcher.spawn(move || sender.send(()).unwrap()).unwrap();
+	fs::write(&path, "a = 2").unwrap();
+	receiver
+		.recv_timeout(std::time::Duration::from_secs(5))
+		.unwrap();
+}
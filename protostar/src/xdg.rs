@@ -1,5 +1,7 @@
+use crate::icon_cache::get_icon_cache_dir;
+use crate::normalize::{normalize_icon, opaque_bounds, safe_size};
+use crate::symbolic::{is_symbolic, recolor_symbolic, svg_color};
 use color_eyre::eyre::Result;
-use freedesktop_icons_greedy::lookup;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -7,55 +9,16 @@ use resvg::render;
 use resvg::tiny_skia::{Pixmap, Transform};
 use resvg::usvg::{FitTo, Tree};
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
-use std::collections::HashMap;
 use std::ffi::OsString;
-use std::fs::create_dir_all;
 use std::io::{BufRead, BufReader, ErrorKind};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Mutex;
 use std::{env, fs};
+use usvg_text_layout::{TreeTextToPath, fontdb};
 use walkdir::WalkDir;
 
-#[serde_as]
-#[derive(Deserialize, Serialize)]
-struct ImageCache {
-	path: PathBuf,
-	#[serde_as(as = "Vec<(_, _)>")]
-	pub map: HashMap<(String, u16), PathBuf>,
-}
-
-impl ImageCache {
-	fn new(path: PathBuf) -> Self {
-		if let Ok(text) = std::fs::read_to_string(&path)
-			&& let Ok(cache) = toml::de::from_str(&text)
-		{
-			return cache;
-		}
-
-		ImageCache {
-			path,
-			map: HashMap::new(),
-		}
-	}
-
-	fn insert(&mut self, k: (String, u16), v: PathBuf) {
-		self.map.insert(k, v);
-	}
-
-	fn save(&self) {
-		std::fs::write(&self.path, toml::ser::to_string_pretty(self).unwrap()).unwrap();
-	}
-}
-
-lazy_static! {
-	static ref IMAGE_CACHE: Mutex<ImageCache> = Mutex::new(ImageCache::new(
-		get_image_cache_dir().join("imagecache.map")
-	));
-}
-
-fn get_data_dirs() -> Vec<PathBuf> {
+pub fn get_data_dirs() -> Vec<PathBuf> {
 	std::env::var("XDG_DATA_DIRS") // parse XDG_DATA_DIRS
 		.unwrap_or_default()
 		.split(':')
@@ -68,6 +31,74 @@ fn get_data_dirs() -> Vec<PathBuf> {
 		.collect()
 }
 
+pub fn get_config_home() -> PathBuf {
+	std::env::var("XDG_CONFIG_HOME")
+		.ok()
+		.filter(|dir| !dir.is_empty())
+		.map(PathBuf::from)
+		.unwrap_or_else(|| dirs::home_dir().unwrap().join(".config"))
+}
+
+/// `$XDG_CONFIG_HOME` followed by `$XDG_CONFIG_DIRS`, most important first.
+pub fn get_config_dirs() -> Vec<PathBuf> {
+	let config_dirs = std::env::var("XDG_CONFIG_DIRS")
+		.ok()
+		.filter(|dirs| !dirs.is_empty())
+		.unwrap_or_else(|| "/etc/xdg".to_string());
+	std::iter::once(get_config_home())
+		.chain(config_dirs.split(':').map(PathBuf::from))
+		.unique()
+		.collect()
+}
+
+/// Suffixes for localized keys like `Name[de_DE]`, best match first, from `LC_ALL`, `LC_MESSAGES` or `LANG`.
+pub fn locale_variants() -> Vec<String> {
+	["LC_ALL", "LC_MESSAGES", "LANG"]
+		.iter()
+		.find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+		.map(|locale| locale_variants_of(&locale))
+		.unwrap_or_default()
+}
+
+/// `lang_COUNTRY.ENCODING@MODIFIER` matches `lang_COUNTRY@MODIFIER`, `lang_COUNTRY`, `lang@MODIFIER` and `lang`, in that order.
+fn locale_variants_of(locale: &str) -> Vec<String> {
+	let (locale, modifier) = match locale.split_once('@') {
+		Some((locale, modifier)) => (locale, Some(modifier)),
+		None => (locale, None),
+	};
+	let locale = locale.split('.').next().unwrap_or_default();
+	let (lang, country) = match locale.split_once('_') {
+		Some((lang, country)) => (lang, Some(country)),
+		None => (locale, None),
+	};
+	if lang.is_empty() || lang == "C" || lang == "POSIX" {
+		return Vec::new();
+	}
+
+	let mut variants = Vec::new();
+	if let (Some(country), Some(modifier)) = (country, modifier) {
+		variants.push(format!("{lang}_{country}@{modifier}"));
+	}
+	if let Some(country) = country {
+		variants.push(format!("{lang}_{country}"));
+	}
+	if let Some(modifier) = modifier {
+		variants.push(format!("{lang}@{modifier}"));
+	}
+	variants.push(lang.to_string());
+	variants
+}
+
+#[test]
+fn test_locale_variants() {
+	assert_eq!(
+		locale_variants_of("de_DE.UTF-8@euro"),
+		["de_DE@euro", "de_DE", "de@euro", "de"]
+	);
+	assert_eq!(locale_variants_of("fr"), ["fr"]);
+	assert!(locale_variants_of("C.UTF-8").is_empty());
+}
+
 fn get_app_dirs() -> Vec<PathBuf> {
 	get_data_dirs()
 		.into_iter()
@@ -107,7 +138,7 @@ fn test_parse_desktop_file() {
 	// Create a temporary directory and a test desktop file
 	let dir = tempdir::TempDir::new("test").unwrap();
 	let file = dir.path().join("test.desktop");
-	let data = "[Desktop Entry]\nName=Test\nExec=test\nCategories=A;B;C\nIcon=test.png";
+	let data = "[Desktop Entry]\nName=Test\nExec=test\nCategories=A;B;C\nIcon=test.png\nMimeType=text/plain;image/png;";
 	fs::write(&file, data).unwrap();
 
 	// Parse the test desktop file
@@ -121,18 +152,54 @@ fn test_parse_desktop_file() {
 		vec!["A".to_string(), "B".to_string(), "C".to_string()]
 	);
 	assert_eq!(desktop_file.icon, Some("test.png".to_string()));
+	assert_eq!(desktop_file.mime_types, vec!["text/plain", "image/png"]);
+	assert_eq!(desktop_file.id(), "test.desktop");
+}
+
+#[test]
+fn test_parse_desktop_actions() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let file = dir.path().join("test.desktop");
+	let data = "[Desktop Entry]\nName=Browser\nGenericName=Web Browser\nExec=env FOO=1 /usr/bin/browser %u\nKeywords=web;internet;\nActions=new-window;private;\n\n[Desktop Action private]\nName=New Private Window\nExec=browser --private\n\n[Desktop Action new-window]\nName=New Window\nExec=browser --new-window\n\n[Desktop Action unlisted]\nName=Unlisted";
+	fs::write(&file, data).unwrap();
+
+	let desktop_file = DesktopFile::parse(file).unwrap();
+	assert_eq!(desktop_file.name, Some("Browser".to_string()));
+	assert_eq!(desktop_file.generic_name, Some("Web Browser".to_string()));
+	assert_eq!(desktop_file.keywords, vec!["web", "internet"]);
+	assert_eq!(desktop_file.executable(), Some("browser"));
+	let action_names = desktop_file
+		.actions
+		.iter()
+		.map(|a| a.name.as_deref().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(action_names, ["New Window", "New Private Window"]);
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(into = "PathBuf", from = "PathBuf")]
 pub struct DesktopFile {
-	path: PathBuf,
+	pub(crate) path: PathBuf,
 	pub name: Option<String>,
+	pub generic_name: Option<String>,
 	pub command: Option<String>,
 	pub categories: Vec<String>,
+	pub keywords: Vec<String>,
+	/// MIME types the app can open, from the `MimeType` key.
+	pub mime_types: Vec<String>,
 	pub icon: Option<String>,
+	pub actions: Vec<DesktopAction>,
 	pub no_display: bool,
 }
 
+/// An additional action from a `[Desktop Action <id>]` group, like "New Private Window".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DesktopAction {
+	pub id: String,
+	pub name: Option<String>,
+	pub command: Option<String>,
+	pub icon: Option<String>,
+}
+
 impl From<DesktopFile> for PathBuf {
 	fn from(df: DesktopFile) -> Self {
 		df.path
@@ -144,7 +211,43 @@ impl From<PathBuf> for DesktopFile {
 	}
 }
 
+/// Where a desktop file was installed from, in order of precedence when the same app shows up twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AppSource {
+	User,
+	System,
+	Flatpak,
+	Snap,
+}
+
 impl DesktopFile {
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The desktop file ID as defined by the spec: the path relative to its `applications` dir with `/` replaced by `-`.
+	pub fn id(&self) -> String {
+		get_app_dirs()
+			.iter()
+			.find_map(|dir| self.path.strip_prefix(dir).ok())
+			.unwrap_or_else(|| Path::new(self.path.file_name().unwrap_or_default()))
+			.to_string_lossy()
+			.replace('/', "-")
+	}
+
+	pub fn source(&self) -> AppSource {
+		let path = self.path.to_string_lossy();
+		if path.contains("/flatpak/") {
+			AppSource::Flatpak
+		} else if path.contains("/snapd/") || path.starts_with("/snap/") {
+			AppSource::Snap
+		} else if dirs::home_dir().is_some_and(|home| self.path.starts_with(home)) {
+			AppSource::User
+		} else {
+			AppSource::System
+		}
+	}
+
 	pub fn parse(path: PathBuf) -> Result<Self, String> {
 		// Open the file in read-only mode
 		let file = match fs::File::open(
@@ -160,11 +263,17 @@ impl DesktopFile {
 
 		// Create temporary variables to hold the parsed values
 		let mut name = None;
+		let mut generic_name = None;
 		let mut command = None;
 		let mut categories = Vec::new();
+		let mut keywords = Vec::new();
+		let mut mime_types = Vec::new();
 		let mut icon = None;
+		let mut action_ids = None;
+		let mut actions: Vec<DesktopAction> = Vec::new();
 		let mut no_display = false;
 		let mut desktop_entry_found = false;
+		let mut in_action = false;
 
 		let re = Regex::new(r"^\[([^\]]*)\]$").unwrap();
 
@@ -183,11 +292,21 @@ impl DesktopFile {
 			if let Some(captures) = re.captures(&line) {
 				let entry = captures.get(1).unwrap();
 				desktop_entry_found = entry.as_str().contains("Desktop Entry");
-			}
-
-			if !desktop_entry_found {
+				in_action = match entry.as_str().strip_prefix("Desktop Action ") {
+					Some(id) => {
+						actions.push(DesktopAction {
+							id: id.to_string(),
+							name: None,
+							command: None,
+							icon: None,
+						});
+						true
+					}
+					None => false,
+				};
 				continue;
 			}
+
 			// Split the line into a key-value pair by looking for the first "=" character
 			let parts = line.split_once('=');
 			let (key, value) = match parts {
@@ -195,39 +314,86 @@ impl DesktopFile {
 				None => continue,
 			};
 
+			if in_action && let Some(action) = actions.last_mut() {
+				match key {
+					"Name" => action.name = Some(value.to_string()),
+					"Exec" => action.command = Some(value.to_string()),
+					"Icon" => action.icon = Some(value.to_string()),
+					_ => (),
+				}
+				continue;
+			}
+			if !desktop_entry_found {
+				continue;
+			}
+
 			// Parse the key-value pair based on the key
 			match key {
 				"Name" => name = Some(value.to_string()),
+				"GenericName" => generic_name = Some(value.to_string()),
 				"Exec" => command = Some(value.to_string()),
-				"Categories" => {
-					categories = value
-						.split(';')
-						.map(|s| s.to_string())
-						.filter(|s| !s.is_empty())
-						.collect()
-				}
+				"Categories" => categories = split_list(value),
+				"Keywords" => keywords = split_list(value),
+				"MimeType" => mime_types = split_list(value),
 				"Icon" => icon = Some(value.to_string()),
+				"Actions" => action_ids = Some(split_list(value)),
 				"NoDisplay" => no_display = value == "true",
 				_ => (), // Ignore unknown keys
 			}
 		}
 
+		// Only actions listed in the `Actions` key are valid, in that order
+		let actions = action_ids
+			.unwrap_or_default()
+			.iter()
+			.filter_map(|id| actions.iter().find(|a| &a.id == id).cloned())
+			.collect();
+
 		// Create and return a new DesktopFile instance with the parsed values
 		Ok(DesktopFile {
 			path,
 			name,
+			generic_name,
 			command,
 			categories,
+			keywords,
+			mime_types,
 			icon,
+			actions,
 			no_display,
 		})
 	}
+
+	/// The name of the program that `Exec` runs, without its directory or arguments.
+	pub fn executable(&self) -> Option<&str> {
+		let command = self.command.as_deref()?;
+		let program = command
+			.split_whitespace()
+			.find(|arg| *arg != "env" && !arg.contains('='))?;
+		Some(program.rsplit('/').next().unwrap_or(program))
+	}
 }
 
-const ICON_SIZES: [u16; 7] = [512, 256, 128, 64, 48, 32, 24];
+/// Splits a `;` separated list value like `Categories` or `Keywords`.
+fn split_list(value: &str) -> Vec<String> {
+	value
+		.split(';')
+		.map(|s| s.to_string())
+		.filter(|s| !s.is_empty())
+		.collect()
+}
 
 impl DesktopFile {
 	pub fn get_icon(&self, preferred_px_size: u16) -> Option<Icon> {
+		if let Some(cached) = crate::index::cached_icon(&self.path, preferred_px_size) {
+			return cached;
+		}
+		let icon = self.lookup_icon(preferred_px_size);
+		crate::index::record_icon(&self.path, preferred_px_size, icon.as_ref());
+		icon
+	}
+
+	pub(crate) fn lookup_icon(&self, preferred_px_size: u16) -> Option<Icon> {
 		// Get the name of the icon from the DesktopFile struct
 		let icon_name = self.icon.as_ref()?;
 		let test_icon_path = self.path.join(Path::new(icon_name));
@@ -237,49 +403,42 @@ impl DesktopFile {
 			return Some(icon);
 		}
 
-		if let Some(cache_icon_path) = IMAGE_CACHE
-			.lock()
-			.unwrap()
-			.map
-			.get(&(icon_name.clone(), preferred_px_size))
-			&& cache_icon_path.exists()
-			&& let Some(icon) = Icon::from_path(cache_icon_path.to_owned(), preferred_px_size)
-		{
-			return Some(icon);
-		}
-		let preferred_theme = match linicon_theme::get_icon_theme() {
-			Some(t) => t,
-			None => "hicolor".to_owned(),
-		};
+		find_themed_icon(icon_name, preferred_px_size)
+	}
+}
 
-		if let Some(icon_path) = lookup(icon_name)
-			.with_size(preferred_px_size)
-			.with_theme(preferred_theme.as_str())
-			.with_greed()
-			.find() && let Some(icon) = Icon::from_path(icon_path, preferred_px_size)
-		{
-			return Some(icon);
-		}
+/// The icon theme from the protostar config, falling back to the desktop environment's and then `hicolor`.
+pub fn get_icon_theme() -> String {
+	crate::config::config()
+		.icon_theme
+		.clone()
+		.or_else(linicon_theme::get_icon_theme)
+		.unwrap_or_else(|| "hicolor".to_owned())
+}
 
-		for icon_size in ICON_SIZES {
-			if let Some(icon_path) = lookup(icon_name)
-				.with_size(icon_size)
-				.with_theme(preferred_theme.as_str())
-				.with_greed()
-				.find() && let Some(icon) = Icon::from_path(icon_path, preferred_px_size)
-			{
-				return Some(icon);
-			}
-		}
-		None
+/// Looks up an icon by name in the user's icon theme, like `applications-games`,
+/// picking the sharpest file for `preferred_px_size` physical pixels.
+pub fn find_themed_icon(icon_name: &str, preferred_px_size: u16) -> Option<Icon> {
+	let file = crate::icon_theme::find_icon_for_pixels(icon_name, preferred_px_size as u32)?;
+	let icon = Icon::from_path(file.path, preferred_px_size)?;
+	if file.scalable {
+		return Some(icon);
 	}
+	Some(Icon {
+		size: file.size.try_into().unwrap_or(u16::MAX),
+		scale: file.scale.try_into().unwrap_or(1),
+		..icon
+	})
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Icon {
 	pub icon_type: IconType,
 	pub path: PathBuf,
+	/// Logical size in pixels, SVGs get rendered at this size.
 	pub size: u16,
+	/// Physical pixels per logical pixel, like 2 for icons from `@2` theme directories.
+	pub scale: u16,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -287,6 +446,10 @@ pub enum IconType {
 	Png,
 	Svg,
 	Gltf,
+	Xpm,
+	Ico,
+	Webp,
+	Bmp,
 }
 impl Icon {
 	pub fn from_path(path: PathBuf, size: u16) -> Option<Icon> {
@@ -294,40 +457,36 @@ impl Icon {
 			Some("png") => IconType::Png,
 			Some("svg") => IconType::Svg,
 			Some("glb") | Some("gltf") => IconType::Gltf,
+			Some("xpm") => IconType::Xpm,
+			Some("ico") => IconType::Ico,
+			Some("webp") => IconType::Webp,
+			Some("bmp") => IconType::Bmp,
 			_ => return None,
 		};
 		Some(Icon {
 			icon_type,
 			path,
 			size,
+			scale: 1,
 		})
 	}
 
+	/// The size of the image in physical pixels.
+	pub fn pixel_size(&self) -> u32 {
+		self.size as u32 * self.scale as u32
+	}
+
+	/// Turns the icon into a normalized PNG of `size` pixels square, see [`normalize_icon`].
+	/// glTF models are left as they are.
 	pub fn cached_process(self, size: u16) -> Result<Icon, std::io::Error> {
-		let image_name = self
-			.path
-			.with_extension("")
-			.file_name()
-			.unwrap()
-			.to_str()
-			.unwrap()
-			.to_owned();
-
-		if !IMAGE_CACHE
-			.lock()
-			.unwrap()
-			.map
-			.contains_key(&(image_name.clone(), size))
-		{
-			IMAGE_CACHE
-				.lock()
-				.unwrap()
-				.insert((image_name, size), self.path.clone());
-			IMAGE_CACHE.lock().unwrap().save();
-		}
 		match self.icon_type {
+			IconType::Gltf => Ok(self),
+			// already went through here
+			IconType::Png if self.path.starts_with(get_icon_cache_dir()) => Ok(self),
 			IconType::Svg => Ok(Icon::from_path(get_png_from_svg(self.path, size)?, size).unwrap()),
-			_ => Ok(self),
+			IconType::Png | IconType::Xpm | IconType::Ico | IconType::Webp | IconType::Bmp => {
+				Ok(Icon::from_path(get_png_from_raster(self.path, size)?, size).unwrap())
+			}
 		}
 	}
 }
@@ -338,9 +497,13 @@ fn test_get_icon_path() {
 	let desktop_file = DesktopFile {
 		path: PathBuf::new(),
 		name: None,
+		generic_name: None,
 		command: None,
 		categories: vec![],
+		keywords: vec![],
+		mime_types: vec![],
 		icon: Some("com.belmoussaoui.ashpd.demo".into()),
+		actions: vec![],
 		no_display: false,
 	};
 
@@ -351,48 +514,183 @@ fn test_get_icon_path() {
 	assert!(icon.is_some());
 }
 
-pub fn get_image_cache_dir() -> PathBuf {
-	let cache_dir;
+/// Converts a `file://` URI to a path, decoding percent escapes.
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+	let path = uri.strip_prefix("file://")?;
+	// skip the host part, which is usually empty or localhost
+	let path = &path[path.find('/')?..];
+
+	let mut bytes = Vec::with_capacity(path.len());
+	let mut chars = path.bytes();
+	while let Some(b) = chars.next() {
+		if b == b'%' {
+			let hex = [chars.next()?, chars.next()?];
+			bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+		} else {
+			bytes.push(b);
+		}
+	}
+	Some(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+/// Converts an absolute path to a `file://` URI, percent encoding anything that isn't safe in one.
+pub fn path_to_file_uri(path: &Path) -> String {
+	let mut uri = String::from("file://");
+	for b in path.as_os_str().as_bytes() {
+		if b.is_ascii_alphanumeric() || b"/-._~".contains(b) {
+			uri.push(*b as char);
+		} else {
+			uri.push_str(&format!("%{b:02X}"));
+		}
+	}
+	uri
+}
+
+#[test]
+fn test_file_uri_to_path() {
+	assert_eq!(
+		file_uri_to_path("file:///home/user/My%20Notes.txt"),
+		Some(PathBuf::from("/home/user/My Notes.txt"))
+	);
+	assert_eq!(
+		file_uri_to_path("file://localhost/tmp/a"),
+		Some(PathBuf::from("/tmp/a"))
+	);
+	assert_eq!(file_uri_to_path("https://example.com"), None);
+	let path = Path::new("/tmp/100% done/ü.txt");
+	assert_eq!(
+		path_to_file_uri(path),
+		"file:///tmp/100%25%20done/%C3%BC.txt"
+	);
+	assert_eq!(
+		file_uri_to_path(&path_to_file_uri(path)).as_deref(),
+		Some(path)
+	);
+}
+
+pub fn get_cache_home() -> PathBuf {
 	if let Ok(xdg_cache_home) = std::env::var("XDG_CACHE_HOME") {
-		cache_dir =
-			PathBuf::from_str(&xdg_cache_home).unwrap_or(dirs::home_dir().unwrap().join(".cache"))
+		PathBuf::from_str(&xdg_cache_home).unwrap_or(dirs::home_dir().unwrap().join(".cache"))
 	} else {
-		cache_dir = dirs::home_dir().unwrap().join(".cache");
+		dirs::home_dir().unwrap().join(".cache")
 	}
-	let image_cache_dir = cache_dir.join("protostar_icon_cache");
-	create_dir_all(&image_cache_dir).expect("Could not create image cache directory");
-	image_cache_dir
 }
 
-pub fn get_png_from_svg(svg_path: impl AsRef<Path>, size: u16) -> Result<PathBuf, std::io::Error> {
-	let svg_path = fs::canonicalize(svg_path)?;
-	let svg_data = fs::read(svg_path.as_path())?;
-	let tree = Tree::from_data(svg_data.as_slice(), &resvg::usvg::Options::default())
-		.map_err(|_| ErrorKind::InvalidData)?;
+pub fn get_state_home() -> PathBuf {
+	if let Ok(xdg_state_home) = std::env::var("XDG_STATE_HOME") {
+		PathBuf::from_str(&xdg_state_home).unwrap_or(dirs::home_dir().unwrap().join(".local/state"))
+	} else {
+		dirs::home_dir().unwrap().join(".local/state")
+	}
+}
 
-	let png_path = get_image_cache_dir().join(format!(
-		"{}-{}-{}.png",
-		svg_path.file_name().unwrap().to_str().unwrap(),
-		svg_data.len(),
-		size
-	));
+lazy_static! {
+	/// Loaded once, scanning the system fonts takes a while.
+	static ref FONT_DB: fontdb::Database = {
+		let mut db = fontdb::Database::new();
+		db.load_system_fonts();
+		db
+	};
+}
 
-	if png_path.exists() {
-		return Ok(png_path);
+/// Renders an SVG to a PNG of `size` pixels square in the shared icon cache, laid out like [`normalize_icon`] does.
+/// Symbolic icons get drawn in `appearance.symbolic_icon_color` instead of black.
+pub fn get_png_from_svg(svg_path: impl AsRef<Path>, size: u16) -> Result<PathBuf, std::io::Error> {
+	let svg_path = fs::canonicalize(svg_path)?;
+	if is_symbolic(&svg_path) {
+		let foreground = svg_color(crate::config::config().appearance.symbolic_icon_color);
+		// the color is part of the name so changing it renders the icon again
+		let kind = format!("{}-{size}.png", &foreground[1..]);
+		return crate::icon_cache::get_or_derive(&svg_path, &kind, |svg_data| {
+			let svg = recolor_symbolic(&String::from_utf8_lossy(svg_data), &foreground);
+			render_svg(svg.as_bytes(), size)
+		});
 	}
+	crate::icon_cache::get_or_render(&svg_path, size, |svg_data| render_svg(svg_data, size))
+}
 
-	let mut pixmap = Pixmap::new(size.into(), size.into()).unwrap();
-	render(
+/// Renders twice instead of going through [`normalize_icon`], first to find where the artwork is,
+/// then straight into the safe area, so vector icons stay sharp instead of being resampled.
+fn render_svg(svg_data: &[u8], size: u16) -> Result<Vec<u8>, std::io::Error> {
+	let mut tree = Tree::from_data(svg_data, &resvg::usvg::Options::default())
+		.map_err(|_| ErrorKind::InvalidData)?;
+	tree.convert_text(&FONT_DB, false);
+
+	// fit the longer side, fitting the width of a tall SVG into a square would clip its bottom
+	let (width, height) = (tree.size.width(), tree.size.height());
+	let size = size as u32;
+	let (fit, pixmap_width, pixmap_height) = if width >= height {
+		let height = (size as f64 * height / width).ceil() as u32;
+		(FitTo::Width(size), size, height.max(1))
+	} else {
+		let width = (size as f64 * width / height).ceil() as u32;
+		(FitTo::Height(size), width.max(1), size)
+	};
+	let fitted = rasterize(
 		&tree,
-		FitTo::Width(size.into()),
+		fit,
 		Transform::identity(),
-		pixmap.as_mut(),
-	);
-	pixmap
-		.save_png(&png_path)
-		.map_err(|_| ErrorKind::InvalidData)?;
-	Ok(png_path)
+		pixmap_width,
+		pixmap_height,
+	)?;
+	let Some((x, y, width, height)) = opaque_bounds(&fitted) else {
+		return encode_png(&image::RgbaImage::new(size, size));
+	};
+
+	let scale = safe_size(size) as f32 / width.max(height) as f32;
+	let offset = |start: u32, length: u32| {
+		(size as f32 - length as f32 * scale) / 2.0 - start as f32 * scale
+	};
+	let transform =
+		Transform::from_row(scale, 0.0, 0.0, scale, offset(x, width), offset(y, height));
+	encode_png(&rasterize(&tree, fit, transform, size, size)?)
 }
+
+fn rasterize(
+	tree: &Tree,
+	fit: FitTo,
+	transform: Transform,
+	width: u32,
+	height: u32,
+) -> Result<image::RgbaImage, std::io::Error> {
+	let mut pixmap = Pixmap::new(width, height).ok_or(ErrorKind::InvalidData)?;
+	render(tree, fit, transform, pixmap.as_mut());
+	Ok(image::RgbaImage::from_fn(width, height, |x, y| {
+		let color = pixmap.pixel(x, y).unwrap().demultiply();
+		image::Rgba([color.red(), color.green(), color.blue(), color.alpha()])
+	}))
+}
+
+/// Converts a raster icon to a [`normalize_icon`]ed PNG of `size` pixels square in the shared icon cache,
+/// including formats that can't be used as a texture directly. ICO files have several sizes and the largest is used.
+pub fn get_png_from_raster(
+	image_path: impl AsRef<Path>,
+	size: u16,
+) -> Result<PathBuf, std::io::Error> {
+	let image_path = fs::canonicalize(image_path)?;
+	let is_xpm = image_path.extension().is_some_and(|ext| ext == "xpm");
+	crate::icon_cache::get_or_render(&image_path, size, |image_data| {
+		let image = if is_xpm {
+			let text = String::from_utf8_lossy(image_data);
+			crate::xpm::decode_xpm(&text)
+				.map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?
+		} else {
+			image::load_from_memory(image_data)
+				.map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?
+				.to_rgba8()
+		};
+		encode_png(&normalize_icon(&image, size as u32))
+	})
+}
+
+pub(crate) fn encode_png(image: &image::RgbaImage) -> Result<Vec<u8>, std::io::Error> {
+	let mut png = std::io::Cursor::new(Vec::new());
+	image
+		.write_to(&mut png, image::ImageFormat::Png)
+		.map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+	Ok(png.into_inner())
+}
+
 #[test]
 fn test_render_svg_to_png() {
 	use image::GenericImageView;
@@ -425,3 +723,74 @@ fn test_render_svg_to_png() {
 	fs::remove_file(&svg_path).unwrap();
 	fs::remove_file(&png_path).unwrap();
 }
+
+#[test]
+fn test_render_svg_sharp() {
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let svg_path = dir.path().join("margin.svg");
+	// a square with a quarter of the width as margin all around, like most theme icons
+	fs::write(
+		&svg_path,
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 100\">
+	<rect x=\"25\" y=\"25\" width=\"50\" height=\"50\" fill=\"#07c\"/>
+</svg>",
+	)
+	.unwrap();
+	let png_path = get_png_from_svg(&svg_path, 64).unwrap();
+	let image = image::open(&png_path).unwrap().to_rgba8();
+	// it fills the 56 pixel safe area with hard edges, a resampled render would have soft ones
+	assert_eq!(opaque_bounds(&image), Some((4, 4, 56, 56)));
+	assert_eq!(image.get_pixel(4, 4).0[3], 255);
+	assert_eq!(image.get_pixel(59, 59).0[3], 255);
+	fs::remove_file(&png_path).unwrap();
+}
+
+#[test]
+fn test_render_raster_to_png() {
+	use image::codecs::{bmp::BmpEncoder, ico::IcoEncoder, webp::WebPEncoder};
+	use image::{ColorType, GenericImageView, ImageEncoder, Rgba, RgbaImage};
+
+	let dir = tempdir::TempDir::new("test").unwrap();
+	let image = RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255]));
+	let encode = |name: &str, encoder: &dyn Fn(fs::File)| {
+		let path = dir.path().join(name);
+		encoder(fs::File::create(&path).unwrap());
+		path
+	};
+	let sources = [
+		encode("test.ico", &|f| {
+			IcoEncoder::new(f)
+				.write_image(&image, 64, 64, ColorType::Rgba8)
+				.unwrap()
+		}),
+		encode("test.webp", &|f| {
+			WebPEncoder::new_lossless(f)
+				.write_image(&image, 64, 64, ColorType::Rgba8)
+				.unwrap()
+		}),
+		encode("test.bmp", &|f| {
+			BmpEncoder::new(&mut std::io::BufWriter::new(f))
+				.write_image(&image, 64, 64, ColorType::Rgba8)
+				.unwrap()
+		}),
+	];
+	let xpm_path = dir.path().join("test.xpm");
+	fs::write(
+		&xpm_path,
+		"/* XPM */\nstatic char * test_xpm[] = {\n\"2 2 1 1\",\n\"r c red\",\n\"rr\",\n\"rr\"\n};\n",
+	)
+	.unwrap();
+
+	for path in sources.iter().chain([&xpm_path]) {
+		let icon = Icon::from_path(path.clone(), 32).unwrap();
+		assert_ne!(icon.icon_type, IconType::Png);
+		let png = icon.cached_process(32).unwrap();
+		assert_eq!(png.icon_type, IconType::Png);
+
+		let output = image::open(&png.path).unwrap();
+		// every format comes out at the same size, small ones scaled up and large ones down
+		assert_eq!(output.dimensions(), (32, 32), "{}", path.display());
+		assert_eq!(output.get_pixel(16, 16), Rgba([255, 0, 0, 255]));
+		fs::remove_file(&png.path).unwrap();
+	}
+}
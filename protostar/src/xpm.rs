@@ -0,0 +1,218 @@
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+/// Icons are never this large, a header saying so is corrupt or trying to exhaust memory.
+const MAX_SIZE: u32 = 4096;
+/// The X11 color names that show up in practice, the full list has hundreds.
+const COLOR_NAMES: [(&str, [u8; 3]); 20] = [
+	("black", [0, 0, 0]),
+	("white", [255, 255, 255]),
+	("red", [255, 0, 0]),
+	("green", [0, 255, 0]),
+	("blue", [0, 0, 255]),
+	("yellow", [255, 255, 0]),
+	("cyan", [0, 255, 255]),
+	("magenta", [255, 0, 255]),
+	("gray", [190, 190, 190]),
+	("grey", [190, 190, 190]),
+	("lightgray", [211, 211, 211]),
+	("lightgrey", [211, 211, 211]),
+	("darkgray", [169, 169, 169]),
+	("darkgrey", [169, 169, 169]),
+	("gray50", [127, 127, 127]),
+	("grey50", [127, 127, 127]),
+	("orange", [255, 165, 0]),
+	("brown", [165, 42, 42]),
+	("navy", [0, 0, 128]),
+	("purple", [160, 32, 240]),
+];
+
+/// Decodes an XPM2 or XPM3 image, the format a lot of older apps ship their icons in.
+pub fn decode_xpm(text: &str) -> Result<RgbaImage, String> {
+	// XPM3 is C source with the lines as string literals, XPM2 is just the lines
+	let lines = if text.trim_start().starts_with("! XPM2") {
+		text.lines().skip(1).map(str::to_string).collect::<Vec<_>>()
+	} else {
+		string_literals(text)
+	};
+	let mut lines = lines.into_iter();
+
+	let header = lines.next().ok_or("missing XPM header")?;
+	let values = header
+		.split_whitespace()
+		.take(4)
+		.map(|v| v.parse::<u32>())
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|_| format!("invalid XPM header {header:?}"))?;
+	let [width, height, colors, chars_per_pixel] = values[..] else {
+		return Err(format!("invalid XPM header {header:?}"));
+	};
+	let chars_per_pixel = chars_per_pixel as usize;
+	if chars_per_pixel == 0 {
+		return Err("XPM pixels need at least one character".to_string());
+	}
+	if width > MAX_SIZE || height > MAX_SIZE {
+		return Err(format!("XPM of {width}x{height} is too large"));
+	}
+
+	let mut palette = HashMap::new();
+	for _ in 0..colors {
+		let line = lines.next().ok_or("missing XPM colors")?;
+		let key = line
+			.get(..chars_per_pixel)
+			.ok_or_else(|| format!("invalid XPM color {line:?}"))?;
+		palette.insert(key.to_string(), parse_color(&line[chars_per_pixel..])?);
+	}
+
+	// check the pixels are all there before allocating for them
+	let rows = lines.take(height as usize).collect::<Vec<_>>();
+	if rows.len() < height as usize {
+		return Err("missing XPM pixels".to_string());
+	}
+	let row_len = width as usize * chars_per_pixel;
+	if let Some(y) = rows.iter().position(|row| row.len() < row_len) {
+		return Err(format!("XPM row {y} is too short"));
+	}
+
+	let mut image = RgbaImage::new(width, height);
+	for (y, line) in (0..height).zip(rows) {
+		for x in 0..width {
+			let start = x as usize * chars_per_pixel;
+			let key = line
+				.get(start..start + chars_per_pixel)
+				.ok_or_else(|| format!("XPM row {y} is too short"))?;
+			let color = palette
+				.get(key)
+				.ok_or_else(|| format!("XPM pixel {key:?} is not in the palette"))?;
+			image.put_pixel(x, y, *color);
+		}
+	}
+	Ok(image)
+}
+
+/// The contents of every `"..."` in C source, skipping comments.
+fn string_literals(text: &str) -> Vec<String> {
+	let mut literals = Vec::new();
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'/' if chars.next_if_eq(&'*').is_some() => {
+				while let Some(c) = chars.next() {
+					if c == '*' && chars.next_if_eq(&'/').is_some() {
+						break;
+					}
+				}
+			}
+			'"' => {
+				let mut literal = String::new();
+				while let Some(c) = chars.next() {
+					match c {
+						'"' => break,
+						'\\' => literal.extend(chars.next()),
+						c => literal.push(c),
+					}
+				}
+				literals.push(literal);
+			}
+			_ => (),
+		}
+	}
+	literals
+}
+
+/// Parses the part of a color line after the pixel characters, like `c #ff0000 m black`.
+/// Only the color visual is used, falling back to grayscale and then monochrome.
+fn parse_color(spec: &str) -> Result<Rgba<u8>, String> {
+	let words = spec.split_whitespace().collect::<Vec<_>>();
+	let visual = |key: &str| {
+		let start = words.iter().position(|w| *w == key)? + 1;
+		let len = words[start..]
+			.iter()
+			.position(|w| ["c", "m", "g", "g4", "s"].contains(w))
+			.unwrap_or(words.len() - start);
+		// color names can have spaces, like `light grey`
+		Some(words[start..start + len].join(""))
+	};
+	let value = ["c", "g", "g4", "m"]
+		.iter()
+		.find_map(|key| visual(key))
+		.filter(|v| !v.is_empty())
+		.ok_or_else(|| format!("XPM color {spec:?} has no value"))?;
+
+	if value.eq_ignore_ascii_case("none") {
+		return Ok(Rgba([0, 0, 0, 0]));
+	}
+	if let Some(hex) = value.strip_prefix('#') {
+		// each channel has 1 to 4 hex digits, only the most significant byte matters
+		let digits = hex.len() / 3;
+		if !hex.len().is_multiple_of(3) || !(1..=4).contains(&digits) {
+			return Err(format!("invalid XPM color {value:?}"));
+		}
+		let mut rgb = [0; 3];
+		for (channel, chunk) in rgb.iter_mut().zip(hex.as_bytes().chunks(digits)) {
+			let chunk = std::str::from_utf8(chunk).map_err(|e| e.to_string())?;
+			let value = u16::from_str_radix(chunk, 16)
+				.map_err(|_| format!("invalid XPM color {value:?}"))?;
+			*channel = match digits {
+				1 => (value * 17) as u8,
+				2 => value as u8,
+				n => (value >> (4 * (n - 2))) as u8,
+			};
+		}
+		return Ok(Rgba([rgb[0], rgb[1], rgb[2], 255]));
+	}
+	let name = value.to_ascii_lowercase();
+	let [r, g, b] = COLOR_NAMES
+		.iter()
+		.find(|(n, _)| *n == name)
+		.map(|(_, rgb)| *rgb)
+		.ok_or_else(|| format!("unknown XPM color {value:?}"))?;
+	Ok(Rgba([r, g, b, 255]))
+}
+
+#[test]
+fn test_decode_xpm() {
+	let image = decode_xpm(
+		r#"/* XPM */
+static char * test_xpm[] = {
+/* width height colors chars_per_pixel */
+"3 2 4 2",
+"   c None",
+".. c #FF0000",
+"++ c #0000ffff0000 m white",
+"@@ s accent c light grey",
+"  ..++",
+"@@\"\"  "
+};"#,
+	);
+	// the last row uses a character that's not in the palette
+	assert!(image.unwrap_err().contains("not in the palette"));
+
+	let image = decode_xpm(
+		r#"/* XPM */
+static char * test_xpm[] = {
+"3 2 4 2",
+"   c None",
+".. c #FF0000",
+"++ c #0000ffff0000 m white",
+"@@ s accent c light grey",
+"  ..++",
+"@@    "
+};"#,
+	)
+	.unwrap();
+	assert_eq!(image.dimensions(), (3, 2));
+	assert_eq!(image.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+	assert_eq!(image.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+	assert_eq!(image.get_pixel(2, 0), &Rgba([0, 255, 0, 255]));
+	assert_eq!(image.get_pixel(0, 1), &Rgba([211, 211, 211, 255]));
+
+	// corrupt headers are rejected before anything gets allocated for them
+	assert!(decode_xpm("! XPM2\n65535 65535 1 1\na c #fff\na\n").is_err());
+	assert!(decode_xpm("! XPM2\n2 2 1 1\na c #fff\naa\n").is_err());
+	assert!(decode_xpm("! XPM2\n2 1 1 1\na c #fff\na\n").is_err());
+
+	let image = decode_xpm("! XPM2\n2 1 2 1\na c #fff\nb c black\nab\n").unwrap();
+	assert_eq!(image.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+	assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 0, 255]));
+}
@@ -1,6 +1,10 @@
 use glam::{Quat, Vec3};
 use mint::{Quaternion, Vector3};
 use protostar::application::Application;
+use protostar::config::Color;
+use protostar::favorites::is_favorite;
+use protostar::gltf::{Normalization, model_bounds};
+use protostar::palette::icon_color;
 use protostar::xdg::{DesktopFile, Icon, IconType};
 use serde::{Deserialize, Serialize};
 use stardust_xr_asteroids::elements::{
@@ -15,66 +19,162 @@ use stardust_xr_fusion::{
 	fields::{CylinderShape, Shape},
 	spatial::Transform,
 };
+use std::collections::HashMap;
 use std::f32::consts::{FRAC_PI_2, PI};
 use std::sync::OnceLock;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::app_launcher::AppLauncher;
-use crate::{ACTIVATION_DISTANCE, APP_SIZE, DEFAULT_HEX_COLOR, MODEL_SCALE};
+use crate::{config, rgba};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct App {
 	pub app: Application,
 	#[serde(skip)]
 	icon: OnceLock<Icon>,
+	/// Only set when `appearance.tint_from_icon` is on.
+	#[serde(skip)]
+	tint: OnceLock<Color>,
+	/// Only set for glTF icons, fits them into `appearance.app_size` whatever units they were made in.
+	#[serde(skip)]
+	model_fit: OnceLock<Normalization>,
 	pos: Vector3<f32>,
 	rot: Quaternion<f32>,
 	#[serde(skip)]
 	launched: AtomicBool,
+	#[serde(skip)]
+	id: OnceLock<String>,
 }
 impl App {
 	pub fn new(desktop_entry: DesktopFile) -> Result<Self, NodeError> {
-		let app = Application::create(desktop_entry)?;
-		Ok(App {
+		Ok(App::from_application(Application::create(desktop_entry)?))
+	}
+
+	pub fn from_application(app: Application) -> Self {
+		App {
 			app,
 			icon: OnceLock::default(),
+			tint: OnceLock::default(),
+			model_fit: OnceLock::default(),
 			pos: [0.0; 3].into(),
 			rot: Quat::IDENTITY.into(),
 			launched: AtomicBool::new(false),
-		})
+			id: OnceLock::default(),
+		}
+	}
+
+	/// The desktop file ID, cached as launchers look it up on every reify to order favorites.
+	pub fn id(&self) -> &str {
+		self.id.get_or_init(|| self.app.id())
+	}
+
+	/// Swaps in a rebuilt app list, keeping where each app that's still there was dragged to
+	/// and whether it's launching.
+	pub fn replace_all(apps: &mut Vec<App>, mut new_apps: Vec<App>) {
+		let old = apps
+			.iter()
+			.map(|app| (app.id(), app))
+			.collect::<HashMap<_, _>>();
+		for app in &mut new_apps {
+			if let Some(old) = old.get(app.id()) {
+				app.pos = old.pos;
+				app.rot = old.rot;
+				app.launched
+					.store(old.launched.load(Ordering::Relaxed), Ordering::Relaxed);
+			}
+		}
+		*apps = new_apps;
 	}
 
 	pub fn load_icon(&self) {
+		let icon_size = config().icon_size;
 		if self.icon.get().is_none()
 			&& let Some(icon) = self
 				.app
-				.icon(64, true)
-				.and_then(|i| i.cached_process(64).ok())
+				.icon(icon_size, true)
+				.and_then(|i| self.fit_model(i))
+				.or_else(|| self.app.icon(icon_size, false))
+				.and_then(|i| i.cached_process(icon_size).ok())
+				// otherwise every app without an icon would be the same blank tile
+				.or_else(|| self.app.fallback_icon(icon_size))
 		{
+			if config().appearance.tint_from_icon
+				&& let Some(color) = icon_color(&icon)
+					.inspect_err(|e| {
+						tracing::warn!("Failed to get icon color for {}: {e}", self.id())
+					})
+					.ok()
+					.flatten()
+			{
+				let _ = self.tint.set(color);
+			}
 			let _ = self.icon.set(icon);
 		}
 	}
 
+	/// Works out how to fit a glTF icon into the app, dropping it if it's malformed
+	/// so the 2D icon gets used instead. Other icons are passed through.
+	fn fit_model(&self, icon: Icon) -> Option<Icon> {
+		if icon.icon_type != IconType::Gltf {
+			return Some(icon);
+		}
+		match model_bounds(&icon.path) {
+			Ok(bounds) => {
+				let _ = self
+					.model_fit
+					.set(bounds.normalization(config().appearance.app_size));
+				Some(icon)
+			}
+			Err(e) => {
+				tracing::warn!(
+					"Ignoring 3D icon {} of {}: {e}",
+					icon.path.display(),
+					self.id()
+				);
+				None
+			}
+		}
+	}
+
 	// Helper functions for creating app components
 	fn create_model(&self) -> impl Element<Self> {
+		let appearance = config().appearance.clone();
 		match self.icon.get().as_ref().map(|i| (i.icon_type.clone(), i)) {
-			Some((IconType::Gltf, icon)) => Model::direct(icon.path.clone())
-				.unwrap()
-				.transform(Transform::from_rotation_scale(
-					Quat::from_rotation_x(PI / 2.0) * Quat::from_rotation_y(PI),
-					[MODEL_SCALE; 3],
-				))
-				.build(),
+			Some((IconType::Gltf, icon)) => {
+				let rotation = Quat::from_rotation_x(PI / 2.0) * Quat::from_rotation_y(PI);
+				// the model is centered before it's rotated, so its offset gets rotated too
+				let fit = self.model_fit.get().copied().unwrap_or(Normalization {
+					scale: appearance.model_scale,
+					translation: Vec3::ZERO,
+				});
+				Model::direct(icon.path.clone())
+					.unwrap()
+					.transform(Transform::from_translation_rotation_scale(
+						(rotation * fit.translation).to_array(),
+						rotation,
+						[fit.scale; 3],
+					))
+					.build()
+			}
 			other => {
 				let model = Model::namespaced("protostar", "hexagon/hexagon")
 					.transform(Transform::from_rotation_scale(
 						Quat::from_rotation_x(PI / 2.0) * Quat::from_rotation_y(PI),
-						[APP_SIZE / 2.0; 3],
+						[appearance.app_size / 2.0; 3],
 					))
-					.part(
-						ModelPart::new("Hex")
-							.mat_param("color", MaterialParameter::Color(DEFAULT_HEX_COLOR)),
-					);
+					.part(ModelPart::new("Hex").mat_param(
+						"color",
+						MaterialParameter::Color(rgba(if is_favorite(self.id()) {
+							appearance.favorite_hex_color
+						} else if let Some(tint) = self.tint.get() {
+							Color {
+								a: appearance.hex_color.a,
+								..*tint
+							}
+						} else {
+							appearance.hex_color
+						})),
+					));
 
 				match other {
 					Some((IconType::Png, icon)) => model.part(ModelPart::new("Icon").mat_param(
@@ -91,9 +191,12 @@ impl App {
 impl Reify for App {
 	#[tracing::instrument(skip_all)]
 	fn reify(&self) -> impl Element<Self> {
+		let appearance = config().appearance.clone();
+		let activation_distance = appearance.activation_distance;
+
 		// The field shape for the grabbable
 		let field_shape = Shape::Cylinder(CylinderShape {
-			radius: APP_SIZE / 2.0,
+			radius: appearance.app_size / 2.0,
 			length: 0.01,
 		});
 
@@ -103,8 +206,8 @@ impl Reify for App {
 
 		Lines::new([line_from_points(vec![
 			Vec3::from([0.0; 3]),
-			(length < ACTIVATION_DISTANCE) as u32 as f32
-				* direction * length.clamp(0.0, ACTIVATION_DISTANCE),
+			(length < activation_distance) as u32 as f32
+				* direction * length.clamp(0.0, activation_distance),
 		])])
 		.build()
 		.child(
@@ -120,7 +223,7 @@ impl Reify for App {
 			.grab_stop({
 				move |state: &mut Self| {
 					let pos_vec = Vec3::from(state.pos);
-					if pos_vec.length() > ACTIVATION_DISTANCE {
+					if pos_vec.length() > activation_distance {
 						// state.app.launch(launch_space)
 						state.launched.store(true, Ordering::Relaxed);
 					} else {
@@ -155,7 +258,7 @@ impl Reify for App {
 					})
 					.align_x(XAlign::Center)
 					.align_y(YAlign::Bottom)
-					.pos([0.0, -APP_SIZE * 0.35, 0.002])
+					.pos([0.0, -appearance.app_size * 0.35, 0.002])
 					.build(),
 			),
 		)
@@ -1,15 +1,14 @@
 mod app;
 mod app_launcher;
+mod reload;
 
 pub use app::App;
+use protostar::config::Color;
+pub use protostar::config::config;
+pub use reload::Reload;
 use stardust_xr_fusion::values::color::{Rgba, color_space::LinearRgb, rgba_linear};
 
-// Constants from original implementation
-pub const APP_SIZE: f32 = 0.06;
-pub const PADDING: f32 = 0.005;
-pub const MODEL_SCALE: f32 = 0.03;
-pub const ACTIVATION_DISTANCE: f32 = 0.05;
-
-pub const DEFAULT_HEX_COLOR: Rgba<f32, LinearRgb> = rgba_linear!(0.0395, 0.8848, 0.3148, 1.0);
-pub const BTN_SELECTED_COLOR: Rgba<f32, LinearRgb> = rgba_linear!(0.0, 1.0, 0.0, 1.0);
-pub const BTN_COLOR: Rgba<f32, LinearRgb> = rgba_linear!(1.0, 1.0, 0.0, 1.0);
+/// Converts a color from the config for use as a material parameter.
+pub fn rgba(color: Color) -> Rgba<f32, LinearRgb> {
+	rgba_linear!(color.r, color.g, color.b, color.a)
+}
@@ -1,5 +1,6 @@
 use stardust_xr_asteroids::{ClientState, CustomElement, Element, Migrate, Reify, client, elements::Spatial};
 use clap::Parser;
+use protostar::config::{config, force_kiosk, skip_in_kiosk};
 use protostar::xdg::DesktopFile;
 use serde::{Deserialize, Serialize};
 use single::App;
@@ -12,6 +13,9 @@ use tracing_subscriber::EnvFilter;
 struct Args {
 	// #[clap(short, long)]
 	desktop_file: PathBuf,
+	/// Refuse to show apps that aren't allowlisted in the `[kiosk]` config section
+	#[clap(long)]
+	kiosk: bool,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -24,7 +28,9 @@ async fn main() {
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Single {
+	#[serde(skip_serializing_if = "skip_in_kiosk")]
 	app: Option<App>,
 }
 impl Migrate for Single {
@@ -34,9 +40,18 @@ impl ClientState for Single {
 	const APP_ID: &'static str = "org.stardustxr.protostar.single";
 
 	fn initial_state_update(&mut self) {
-		let desktop_file_path = Args::parse().desktop_file;
+		let args = Args::parse();
+		if args.kiosk {
+			force_kiosk();
+		}
 
-		let app = App::new(DesktopFile::parse(desktop_file_path).unwrap()).unwrap();
+		let desktop_file = DesktopFile::parse(args.desktop_file).unwrap();
+		let config = config();
+		if config.kiosk_enabled() && !config.kiosk.apps.contains(&desktop_file.id()) {
+			tracing::error!("{} is not allowlisted for kiosk mode", desktop_file.id());
+			std::process::exit(1);
+		}
+		let app = App::new(desktop_file).unwrap();
 		app.load_icon();
 		self.app.replace(app);
 	}
@@ -0,0 +1,89 @@
+use protostar::config::apps_changes;
+use stardust_xr_asteroids::{Context, CustomElement, ValidState};
+use stardust_xr_fusion::{
+	node::{NodeError, NodeType},
+	root::FrameInfo,
+	spatial::{Spatial, SpatialRef, Transform},
+};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Rebuilds the state after the config got reloaded with [changes to the apps](protostar::config::Config::changes_apps).
+/// `load` runs on a thread of its own so frames keep coming while apps are discovered and their icons rendered,
+/// then `apply` hands the result to the state on the frame after it's done.
+pub struct Reload<State: ValidState, T: Send + 'static> {
+	load: Arc<dyn Fn() -> T + Send + Sync>,
+	apply: Box<dyn Fn(&mut State, T) + Send + Sync>,
+}
+impl<State: ValidState, T: Send + 'static> Reload<State, T> {
+	pub fn new(
+		load: impl Fn() -> T + Send + Sync + 'static,
+		apply: impl Fn(&mut State, T) + Send + Sync + 'static,
+	) -> Self {
+		Reload {
+			load: Arc::new(load),
+			apply: Box::new(apply),
+		}
+	}
+}
+impl<State: ValidState, T: Send + 'static> CustomElement<State> for Reload<State, T> {
+	/// The value of [`apps_changes`] the state was last rebuilt for, and the rebuild in progress.
+	type Inner = (Spatial, u64, Option<JoinHandle<T>>);
+	type Resource = ();
+	type Error = NodeError;
+
+	fn create_inner(
+		&self,
+		_asteroids_context: &stardust_xr_asteroids::Context,
+		info: stardust_xr_asteroids::CreateInnerInfo,
+		_resource: &mut Self::Resource,
+	) -> Result<Self::Inner, Self::Error> {
+		let spatial =
+			Spatial::create(info.parent_space.client().get_root(), Transform::identity())?;
+		Ok((spatial, apps_changes(), None))
+	}
+
+	fn diff(&self, _old_self: &Self, _inner: &mut Self::Inner, _resource: &mut Self::Resource) {}
+
+	fn frame(
+		&self,
+		_context: &Context,
+		_info: &FrameInfo,
+		state: &mut State,
+		inner: &mut Self::Inner,
+	) {
+		if let Some(loading) = inner.2.take() {
+			if !loading.is_finished() {
+				inner.2 = Some(loading);
+				return;
+			}
+			match loading.join() {
+				Ok(loaded) => (self.apply)(state, loaded),
+				Err(_) => tracing::error!("Failed to reload the apps, keeping the current ones"),
+			}
+		}
+		// changes made while loading get picked up once that's applied
+		let changes = apps_changes();
+		if inner.1 != changes {
+			inner.1 = changes;
+			let load = self.load.clone();
+			match std::thread::Builder::new()
+				.name("reload apps".to_string())
+				.spawn(move || load())
+			{
+				Ok(loading) => inner.2 = Some(loading),
+				Err(e) => tracing::error!("Failed to reload the apps: {e}"),
+			}
+		}
+	}
+
+	fn spatial_aspect(&self, inner: &Self::Inner) -> SpatialRef {
+		inner.0.clone().as_spatial_ref()
+	}
+}
+impl<State: ValidState, T: Send + 'static> Debug for Reload<State, T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("Reload").finish()
+	}
+}
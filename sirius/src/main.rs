@@ -1,9 +1,13 @@
 use clap::Parser;
 use glam::Quat;
 use mint::{Quaternion, Vector3};
-use protostar::xdg::DesktopFile;
+use protostar::{
+	config::{force_kiosk, skip_in_kiosk},
+	favorites::favorites,
+	registry::AppRegistry,
+};
 use serde::{Deserialize, Serialize};
-use single::{App, BTN_COLOR, BTN_SELECTED_COLOR};
+use single::{App, Reload, config, rgba};
 use stardust_xr_asteroids::{
 	ClientState, CustomElement, Element, Migrate, Reify, Transformable, client,
 	elements::{Button, Grabbable, Model, ModelPart, PointerMode, Spatial},
@@ -13,7 +17,6 @@ use stardust_xr_fusion::{
 };
 use std::path::PathBuf;
 use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
-use walkdir::WalkDir;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
@@ -39,14 +42,23 @@ async fn main() {
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-	/// Directory to scan for desktop files
-	apps_directory: PathBuf,
+	/// Directory to scan for desktop files, defaults to `sirius.apps_directory` in the config
+	/// or all installed apps
+	apps_directory: Option<PathBuf>,
+	/// Only show the apps allowlisted in the `[kiosk]` config section and lock the launcher in place
+	#[clap(long)]
+	kiosk: bool,
 }
 
+/// Nothing gets saved in kiosk mode, so every session starts the same.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Sirius {
+	#[serde(skip_serializing_if = "skip_in_kiosk")]
 	visible: bool,
+	#[serde(skip_serializing_if = "skip_in_kiosk")]
 	pos: Vector3<f32>,
+	#[serde(skip_serializing_if = "skip_in_kiosk")]
 	rot: Quaternion<f32>,
 	#[serde(skip)]
 	apps: Vec<App>,
@@ -72,42 +84,78 @@ impl ClientState for Sirius {
 
 	fn initial_state_update(&mut self) {
 		let args = Args::parse();
-		if !args.apps_directory.is_dir() {
-			panic!(
-				"{} is not a directory",
-				args.apps_directory.to_string_lossy()
-			)
+		if args.kiosk {
+			force_kiosk();
+		}
+		if config().kiosk_enabled() {
+			// every kiosk session starts the same, whatever was saved last time
+			*self = Self::default();
 		}
+		match load_apps() {
+			Ok(apps) => self.apps = apps,
+			Err(e) => {
+				tracing::error!("{e}");
+				std::process::exit(1);
+			}
+		}
+	}
+}
 
-		let walkdir = WalkDir::new(args.apps_directory.canonicalize().unwrap());
+/// Discovers the apps, at startup and again when the config changes them.
+/// A directory from the config is checked when it's loaded, so this only fails
+/// for one passed on the command line or one removed since.
+fn load_apps() -> Result<Vec<App>, String> {
+	let registry = match Args::parse()
+		.apps_directory
+		.or_else(|| config().sirius.apps_directory.clone())
+	{
+		Some(apps_directory) => {
+			if !apps_directory.is_dir() {
+				return Err(format!("{} is not a directory", apps_directory.display()));
+			}
+			let apps_directory = apps_directory
+				.canonicalize()
+				.map_err(|e| format!("{}: {e}", apps_directory.display()))?;
+			AppRegistry::from_dir(apps_directory)
+		}
+		None => AppRegistry::discover(),
+	};
 
-		self.apps = walkdir
-			.into_iter()
-			.filter_map(|path| path.ok())
-			.map(|entry| entry.into_path())
-			.filter(|path| {
-				path.is_file()
-					&& path.extension().is_some()
-					&& path.extension().unwrap() == "desktop"
-			})
-			.filter_map(|path| App::new(DesktopFile::parse(path).ok()?).ok())
-			.collect();
-	}
+	Ok(registry
+		.into_apps()
+		.into_iter()
+		.map(App::from_application)
+		.collect())
 }
 impl Reify for Sirius {
 	fn reify(&self) -> impl Element<Self> {
+		let config = config();
+		let spacing = config.sirius.spacing;
+		let kiosk = config.kiosk_enabled();
+
 		Grabbable::new(
 			Shape::Box([0.1; 3].into()),
 			self.pos,
 			self.rot,
-			|state: &mut Self, pos, rot| {
-				state.pos = pos;
-				state.rot = rot;
+			move |state: &mut Self, pos, rot| {
+				if !kiosk {
+					state.pos = pos;
+					state.rot = rot;
+				}
 			},
 		)
 		.pointer_mode(PointerMode::Align)
-		.reparentable(true)
+		.reparentable(!kiosk)
 		.build()
+		.child(
+			// favorites and the rest of the config apply on the next reify,
+			// only changes to which apps are shown need the apps rebuilt
+			Reload::new(load_apps, |state: &mut Sirius, apps| match apps {
+				Ok(apps) => App::replace_all(&mut state.apps, apps),
+				Err(e) => tracing::warn!("Failed to reload apps, keeping the current ones: {e}"),
+			})
+			.build(),
+		)
 		.child(
 			Button::new(|state: &mut Sirius| {
 				state.visible = !state.visible;
@@ -121,30 +169,32 @@ impl Reify for Sirius {
 				.transform(Transform::identity())
 				.part(ModelPart::new("?????").mat_param(
 					"color",
-					MaterialParameter::Color(if self.visible {
-						BTN_SELECTED_COLOR
+					MaterialParameter::Color(rgba(if self.visible {
+						config.appearance.button_selected_color
 					} else {
-						BTN_COLOR
-					}),
+						config.appearance.button_color
+					})),
 				))
 				.build(),
 		)
 		.stable_children(
 			self.visible
 				.then(|| {
-					self.apps.iter().enumerate().filter_map(|(pos, app)| {
-						let mut starpos = (pos as f32 + 1.0) / 10.0;
-						match starpos % 0.2 == 0.0 {
-							true => starpos = -starpos / 2.0,
-							false => starpos = (starpos - 0.1) / 2.0,
-						}
+					let ids = self.apps.iter().map(App::id).collect::<Vec<_>>();
+					let order = favorites().order(&ids);
+					order.into_iter().enumerate().filter_map(move |(pos, i)| {
+						let app = &self.apps[i];
+						// alternate sides, moving outwards
+						let n = pos + 1;
+						let starpos = match n % 2 == 0 {
+							true => -(n as f32) * spacing / 2.0,
+							false => (n - 1) as f32 * spacing / 2.0,
+						};
 
 						Some((
 							app.app.name()?.to_string(),
 							Spatial::default().pos([starpos, 0.1, 0.0]).build().child(
-								app.reify_substate(move |state: &mut Sirius| {
-									state.apps.get_mut(pos)
-								}),
+								app.reify_substate(move |state: &mut Sirius| state.apps.get_mut(i)),
 							),
 						))
 					})